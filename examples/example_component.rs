@@ -1,4 +1,4 @@
-use sparse_ecs::{Component, ecs_and, world::World};
+use sparse_ecs::{Component, ecs_join, world::World};
 
 #[derive(Component, Copy, Clone)]
 struct Position {
@@ -35,17 +35,11 @@ impl Default for Velocity {
 }
 
 fn move_system(world: &mut World) {
-    let (positions, velocities) = world.get_two_mut::<Position, Velocity>();
-    if let (Some(positions), Some(velocities)) = (positions, velocities) {
-        for (entity, pos) in positions.iter_mut() {
-            ecs_and!(velocities, entity, velocity, {
-                continue;
-            });
-            pos.x += velocity.x;
-            pos.y += velocity.y;
-            pos.z += velocity.z;
-        }
-    }
+    ecs_join!(world, (pos: mut Position, velocity: Velocity) => {
+        pos.x += velocity.x;
+        pos.y += velocity.y;
+        pos.z += velocity.z;
+    });
 }
 
 fn main() {
@@ -71,23 +71,20 @@ fn spawn_world() -> World {
 }
 
 fn system_move(world: &mut World) {
-    // 2-component fetch (Position, Velocity)
-    if let Some((positions, velocities)) = <(Position, Velocity) as FetchMut>::fetch(world) {
-        for (entity, pos) in positions.iter_mut() {
-            if let Some(vel) = velocities.get(entity) {
-                pos.x += vel.dx;
-                pos.y += vel.dy;
-            }
-        }
+    // Join on (Position, Velocity), walking whichever storage is smaller
+    for (_entity, (pos, vel)) in world.query::<(&mut Position, &Velocity)>() {
+        pos.x += vel.dx;
+        pos.y += vel.dy;
     }
 }
 
 fn system_apply_damage(world: &mut World) {
-    // 3-component fetch (Health, Damage, Armor)
-    let (healths, damages, armors) = <(Health, Damage, Armor) as FetchMut>::fetch(world).unwrap();
-    for (entity, hp) in healths.iter_mut() {
-        let dmg = damages.get(entity).map(|d| d.0).unwrap_or(0);
-        let armor = armors.get(entity).map(|a| a.0).unwrap_or(0);
+    // Damage and Armor are optional, so entities without them are still visited
+    for (_entity, (hp, damage, armor)) in
+        world.query::<(&mut Health, Option<&Damage>, Option<&Armor>)>()
+    {
+        let dmg = damage.map(|d| d.0).unwrap_or(0);
+        let armor = armor.map(|a| a.0).unwrap_or(0);
         let mitigated = dmg.saturating_sub(armor / 2);
         hp.0 = hp.0.saturating_sub(mitigated);
     }
@@ -117,19 +114,19 @@ fn main() {
 
     // Show state of first entity (which has all components)
     let first_entity = sparse_ecs::component::Entity(0);
-    if let Some(pos_set) = world.get::<Position>() {
-        if let Some(pos) = pos_set.get(first_entity) {
-            println!("Entity0 Position: ({:.1}, {:.1})", pos.x, pos.y);
-        }
+    if let Some(pos_set) = world.get::<Position>()
+        && let Some(pos) = pos_set.get(first_entity)
+    {
+        println!("Entity0 Position: ({:.1}, {:.1})", pos.x, pos.y);
     }
-    if let Some(hp_set) = world.get::<Health>() {
-        if let Some(h) = hp_set.get(first_entity) {
-            println!("Entity0 Health: {}", h.0);
-        }
+    if let Some(hp_set) = world.get::<Health>()
+        && let Some(h) = hp_set.get(first_entity)
+    {
+        println!("Entity0 Health: {}", h.0);
     }
-    if let Some(mana_set) = world.get::<Mana>() {
-        if let Some(m) = mana_set.get(first_entity) {
-            println!("Entity0 Mana: {}", m.0);
-        }
+    if let Some(mana_set) = world.get::<Mana>()
+        && let Some(m) = mana_set.get(first_entity)
+    {
+        println!("Entity0 Mana: {}", m.0);
     }
 }
@@ -1,14 +1,144 @@
 use proc_macro::TokenStream;
 
+/// Renders a type the way it's usually written, e.g. `Option<[f32; 2]>`.
+fn type_string(ty: &syn::Type) -> String {
+    let mut out = quote::quote!(#ty).to_string();
+    for (from, to) in [
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        (" :: ", "::"),
+        ("& ", "&"),
+        ("[ ", "["),
+        (" ]", "]"),
+        ("( ", "("),
+        (" )", ")"),
+    ] {
+        out = out.replace(from, to);
+    }
+    out
+}
+
+/// Generates the `fields()` schema description for a struct's fields.
+fn fields_fn(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
+    let syn::Data::Struct(data) = &ast.data else {
+        return quote::quote! {};
+    };
+    let fields = data.fields.iter().enumerate().map(|(i, field)| {
+        let name = field
+            .ident
+            .as_ref()
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| i.to_string());
+        let ty = type_string(&field.ty);
+        quote::quote! {
+            sparse_ecs::registry::Field { name: #name, ty: #ty }
+        }
+    });
+    quote::quote! {
+        fn fields() -> &'static [sparse_ecs::registry::Field] {
+            &[#(#fields),*]
+        }
+    }
+}
+
+/// Same 128-bit FNV-1a as `StableId::from_name`.
+fn fnv1a_128(name: &str) -> u128 {
+    let mut hash: u128 = 0x6c62272e07bb014262b821756295c58d;
+    for byte in name.bytes() {
+        hash ^= byte as u128;
+        hash = hash.wrapping_mul(0x0000000001000000000000000000013b);
+    }
+    hash
+}
+
+/// Options set with `#[component(...)]`.
+#[derive(Default)]
+struct ComponentAttrs {
+    stable_id: Option<u128>,
+    validate: Option<syn::Path>,
+}
+
+/// Reads `#[component(uuid = "...")]` or `#[component(name = "...")]` into a stable ID,
+/// and `#[component(validate = "...")]` into the validation function's path.
+fn component_attrs(ast: &syn::DeriveInput) -> syn::Result<ComponentAttrs> {
+    let mut attrs = ComponentAttrs::default();
+    for attr in ast.attrs.iter().filter(|a| a.path().is_ident("component")) {
+        attr.parse_nested_meta(|meta| {
+            let value: syn::LitStr = meta.value()?.parse()?;
+            if meta.path.is_ident("validate") {
+                if attrs.validate.is_some() {
+                    return Err(meta.error("validation function is already set"));
+                }
+                attrs.validate = Some(value.parse()?);
+                return Ok(());
+            }
+            if attrs.stable_id.is_some() {
+                return Err(meta.error("stable ID is already set"));
+            }
+            if meta.path.is_ident("uuid") {
+                let hex: String = value.value().chars().filter(|&c| c != '-').collect();
+                let parsed = u128::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 32)
+                    .ok_or_else(|| syn::Error::new(value.span(), "invalid UUID"))?;
+                attrs.stable_id = Some(parsed);
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                attrs.stable_id = Some(fnv1a_128(&value.value()));
+                Ok(())
+            } else {
+                Err(meta.error("expected `uuid`, `name` or `validate`"))
+            }
+        })?;
+    }
+    Ok(attrs)
+}
+
+/// Same 64-bit FNV-1a as `Label::from_name`.
+fn fnv1a_64(name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in name.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
 fn impl_component_trait(ast: syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
+    let fields = fields_fn(&ast);
+    let attrs = match component_attrs(&ast) {
+        Ok(attrs) => attrs,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let stable_id = attrs.stable_id.map(|id| {
+        quote::quote! {
+            fn stable_id() -> Option<sparse_ecs::registry::StableId> {
+                Some(sparse_ecs::registry::StableId(#id))
+            }
+        }
+    });
+    let validate = attrs.validate.map(|path| {
+        quote::quote! {
+            fn validate(&self) -> Result<(), String> {
+                #path(self).map_err(|err| err.to_string())
+            }
+        }
+    });
     quote::quote! {
-        impl sparse_ecs::world::Component for #name {}
+        impl sparse_ecs::world::Component for #name {
+            #fields
+            #stable_id
+            #validate
+        }
     }
     .into()
 }
 
-#[proc_macro_derive(Component)]
+#[proc_macro_derive(Component, attributes(component))]
 pub fn component_derive_macro(item: TokenStream) -> TokenStream {
     let ast = syn::parse(item).unwrap();
     impl_component_trait(ast)
@@ -16,8 +146,11 @@ pub fn component_derive_macro(item: TokenStream) -> TokenStream {
 
 fn impl_resource_trait(ast: syn::DeriveInput) -> TokenStream {
     let name = &ast.ident;
+    let fields = fields_fn(&ast);
     quote::quote! {
-        impl sparse_ecs::resource::Resource for #name {}
+        impl sparse_ecs::resource::Resource for #name {
+            #fields
+        }
     }
     .into()
 }
@@ -27,3 +160,199 @@ pub fn resource_derive_macro(item: TokenStream) -> TokenStream {
     let ast = syn::parse(item).unwrap();
     impl_resource_trait(ast)
 }
+
+fn impl_bundle_trait(ast: syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let syn::Data::Struct(data) = &ast.data else {
+        return syn::Error::new_spanned(name, "Bundle can only be derived for structs")
+            .to_compile_error()
+            .into();
+    };
+    let inserts = data.fields.iter().enumerate().map(|(i, field)| {
+        let member = match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(i.into()),
+        };
+        quote::quote! {
+            sparse_ecs::bundle::Bundle::insert(self.#member, world, entity);
+        }
+    });
+    quote::quote! {
+        impl sparse_ecs::bundle::Bundle for #name {
+            fn insert(
+                self,
+                world: &mut sparse_ecs::world::World,
+                entity: sparse_ecs::component::Entity,
+            ) {
+                #(#inserts)*
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `Bundle` by inserting each field, which must itself be a bundle (any component is).
+#[proc_macro_derive(Bundle)]
+pub fn bundle_derive_macro(item: TokenStream) -> TokenStream {
+    let ast = syn::parse(item).unwrap();
+    impl_bundle_trait(ast)
+}
+
+fn impl_query_data_trait(ast: syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let syn::Data::Struct(data) = &ast.data else {
+        return syn::Error::new_spanned(name, "QueryData can only be derived for structs")
+            .to_compile_error()
+            .into();
+    };
+    let params: Vec<_> = ast.generics.params.iter().collect();
+    let lifetime = match params.as_slice() {
+        [syn::GenericParam::Lifetime(param)] => &param.lifetime,
+        _ => {
+            return syn::Error::new_spanned(
+                &ast.generics,
+                "QueryData structs take exactly one lifetime, e.g. `struct MoveQuery<'a>`",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    let types: Vec<_> = data.fields.iter().map(|field| &field.ty).collect();
+    let indices = (0..types.len()).map(syn::Index::from);
+    let members = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(i.into()),
+        });
+    quote::quote! {
+        impl<#lifetime> sparse_ecs::query::QueryData for #name<#lifetime> {
+            type Item<'__w> = #name<'__w>;
+            type State = (#(<#types as sparse_ecs::query::QueryData>::State,)*);
+
+            fn access(access: &mut sparse_ecs::parallel::Access) {
+                #(<#types as sparse_ecs::query::QueryData>::access(access);)*
+            }
+
+            fn init_state(world: &sparse_ecs::query::QueryWorld<'_>) -> Self::State {
+                (#(<#types as sparse_ecs::query::QueryData>::init_state(world),)*)
+            }
+
+            unsafe fn fetch<'__w>(
+                state: Self::State,
+                entity: sparse_ecs::component::Entity,
+            ) -> Option<#name<'__w>> {
+                unsafe {
+                    Some(#name {
+                        #(#members: <#types as sparse_ecs::query::QueryData>::fetch(state.#indices, entity)?,)*
+                    })
+                }
+            }
+
+            fn driver(
+                world: &sparse_ecs::query::QueryWorld<'_>,
+            ) -> Option<sparse_ecs::query::Driver> {
+                sparse_ecs::query::Driver::smallest([
+                    #(<#types as sparse_ecs::query::QueryData>::driver(world)),*
+                ])
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `QueryData` for a struct of `&T`/`&mut T` fields with one lifetime, so
+/// queries return named fields instead of tuples.
+#[proc_macro_derive(QueryData)]
+pub fn query_data_derive_macro(item: TokenStream) -> TokenStream {
+    let ast = syn::parse(item).unwrap();
+    impl_query_data_trait(ast)
+}
+
+fn impl_diff_serialize_trait(ast: syn::DeriveInput) -> TokenStream {
+    let name = &ast.ident;
+    let fields = match &ast.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return syn::Error::new_spanned(
+                name,
+                "DiffSerialize can only be derived for structs with named fields",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+    if fields.len() > 64 {
+        return syn::Error::new_spanned(name, "DiffSerialize supports at most 64 fields")
+            .to_compile_error()
+            .into();
+    }
+    let idents: Vec<_> = fields
+        .iter()
+        .filter_map(|field| field.ident.as_ref())
+        .collect();
+    let names = idents.iter().map(|ident| ident.to_string());
+    let bits: Vec<_> = (0..idents.len() as u32).collect();
+    quote::quote! {
+        impl sparse_ecs::diff::DiffSerialize for #name {
+            const FIELDS: &'static [&'static str] = &[#(#names),*];
+
+            fn diff(&self, base: &Self) -> sparse_ecs::diff::FieldMask {
+                let mut mask = sparse_ecs::diff::FieldMask::NONE;
+                #(
+                    if self.#idents != base.#idents {
+                        mask.insert(#bits);
+                    }
+                )*
+                mask
+            }
+
+            fn apply_fields(&mut self, from: &Self, mask: sparse_ecs::diff::FieldMask) {
+                #(
+                    if mask.contains(#bits) {
+                        self.#idents = from.#idents;
+                    }
+                )*
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `DiffSerialize` for a struct with named fields, each `PartialEq`, so
+/// replication can send only the fields that changed.
+#[proc_macro_derive(DiffSerialize)]
+pub fn diff_serialize_derive_macro(item: TokenStream) -> TokenStream {
+    let ast = syn::parse(item).unwrap();
+    impl_diff_serialize_trait(ast)
+}
+
+/// Hashes a string literal into a `Label` at compile time: `label!("boss_door")`.
+/// Debug builds also register the name the first time each call site runs, panicking if
+/// another name hashes to the same label.
+#[proc_macro]
+pub fn label(item: TokenStream) -> TokenStream {
+    let name: syn::LitStr = match syn::parse(item) {
+        Ok(name) => name,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let hash = fnv1a_64(&name.value());
+    quote::quote! {
+        {
+            #[cfg(debug_assertions)]
+            {
+                static REGISTERED: ::std::sync::Once = ::std::sync::Once::new();
+                REGISTERED.call_once(|| {
+                    sparse_ecs::label::Label::register(#name);
+                });
+            }
+            sparse_ecs::label::Label(#hash)
+        }
+    }
+    .into()
+}
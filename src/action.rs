@@ -0,0 +1,156 @@
+// Per-entity action queues for scripted and turn-based sequences
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{component::Entity, resource::Resource, world::World};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ActionStatus {
+    /// Keep running this action next time the queue is processed.
+    Running,
+    /// Pop this action; the next one starts on the following run.
+    Done,
+}
+
+/// A step in an entity's behavior sequence.
+/// `update` is both the work and the completion predicate.
+pub trait Action: Send + Sync + 'static {
+    fn update(&mut self, world: &mut World, entity: Entity) -> ActionStatus;
+}
+
+impl<F> Action for F
+where
+    F: FnMut(&mut World, Entity) -> ActionStatus + Send + Sync + 'static,
+{
+    fn update(&mut self, world: &mut World, entity: Entity) -> ActionStatus {
+        self(world, entity)
+    }
+}
+
+/// Queues of boxed actions keyed by entity.
+/// Components must be `Copy`, so the queues live in a resource rather than a storage;
+/// queues for despawned entities are dropped on the next `run`.
+#[derive(Default)]
+pub struct ActionQueues {
+    queues: HashMap<Entity, VecDeque<Box<dyn Action>>>,
+}
+
+impl std::fmt::Debug for ActionQueues {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ActionQueues")
+            .field("entities", &self.queues.len())
+            .field(
+                "actions",
+                &self.queues.values().map(VecDeque::len).sum::<usize>(),
+            )
+            .finish()
+    }
+}
+
+impl Resource for ActionQueues {}
+
+impl ActionQueues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an action to the end of the entity's queue.
+    pub fn push(&mut self, entity: Entity, action: impl Action) {
+        self.queues
+            .entry(entity)
+            .or_default()
+            .push_back(Box::new(action));
+    }
+
+    /// Inserts an action in front of the entity's queue, interrupting the current one.
+    /// The interrupted action resumes once the new one is done.
+    pub fn push_front(&mut self, entity: Entity, action: impl Action) {
+        self.queues
+            .entry(entity)
+            .or_default()
+            .push_front(Box::new(action));
+    }
+
+    /// Drops all pending actions for the entity.
+    pub fn clear(&mut self, entity: Entity) {
+        self.queues.remove(&entity);
+    }
+
+    /// Returns the number of pending actions for the entity.
+    pub fn len(&self, entity: Entity) -> usize {
+        self.queues.get(&entity).map_or(0, |queue| queue.len())
+    }
+
+    /// Returns true if the entity has nothing queued.
+    pub fn is_idle(&self, entity: Entity) -> bool {
+        self.len(entity) == 0
+    }
+
+    /// Built-in system: updates the front action of every queue, popping it once done.
+    pub fn run(&mut self, world: &mut World) {
+        self.queues.retain(|&entity, queue| {
+            if world.is_dead(&entity) {
+                return false;
+            }
+            if let Some(action) = queue.front_mut()
+                && action.update(world, entity) == ActionStatus::Done
+            {
+                queue.pop_front();
+            }
+            !queue.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone)]
+    struct Steps(u32);
+    impl crate::world::Component for Steps {}
+
+    #[test]
+    fn actions_run_in_sequence() {
+        let mut world = World::new(4);
+        world.add::<Steps>();
+        let e = world.spawn();
+        world.get_mut::<Steps>().unwrap().add_entity(Steps(0), e);
+
+        let mut queues = ActionQueues::new();
+        // Walk until 2 steps have been taken
+        queues.push(e, |world: &mut World, e: Entity| {
+            let steps = world.get_mut::<Steps>().unwrap().get_mut(e).unwrap();
+            steps.0 += 1;
+            if steps.0 >= 2 {
+                ActionStatus::Done
+            } else {
+                ActionStatus::Running
+            }
+        });
+        queues.push(e, |world: &mut World, e: Entity| {
+            world.get_mut::<Steps>().unwrap().set(Steps(100), e);
+            ActionStatus::Done
+        });
+
+        queues.run(&mut world);
+        assert_eq!(queues.len(e), 2);
+        queues.run(&mut world);
+        assert_eq!(queues.len(e), 1);
+        assert_eq!(world.get::<Steps>().unwrap().get(e).unwrap().0, 2);
+        queues.run(&mut world);
+        assert!(queues.is_idle(e));
+        assert_eq!(world.get::<Steps>().unwrap().get(e).unwrap().0, 100);
+    }
+
+    #[test]
+    fn dead_entities_are_dropped() {
+        let mut world = World::new(4);
+        let e = world.spawn();
+        let mut queues = ActionQueues::new();
+        queues.push(e, |_: &mut World, _: Entity| ActionStatus::Running);
+        world.despawn(e);
+        queues.run(&mut world);
+        assert!(queues.is_idle(e));
+    }
+}
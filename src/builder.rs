@@ -0,0 +1,83 @@
+// Fluent entity spawning
+
+use crate::{
+    bundle::Bundle,
+    component::Entity,
+    world::{Component, World},
+};
+
+/// Builds up a freshly spawned entity, created with `World::spawn_entity`.
+/// Storages for missing component types are added with the default (sparse) backend.
+pub struct EntityBuilder<'w> {
+    world: &'w mut World,
+    entity: Entity,
+}
+
+impl<'w> EntityBuilder<'w> {
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        let entity = world.spawn();
+        Self { world, entity }
+    }
+
+    /// Inserts a component, replacing one of the same type added earlier in the chain.
+    pub fn with<T: Component>(self, component: T) -> Self {
+        self.world.insert(self.entity, component);
+        self
+    }
+
+    /// Inserts every component in the bundle.
+    pub fn with_bundle(self, bundle: impl Bundle) -> Self {
+        bundle.insert(self.world, self.entity);
+        self
+    }
+
+    pub fn tag(self, tag: &'static str) -> Self {
+        self.world.tags.add_tag(tag, self.entity);
+        self
+    }
+
+    /// The entity being built; it is already spawned.
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn build(self) -> Entity {
+        self.entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Position(i32, i32);
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Velocity(i32, i32);
+    impl Component for Velocity {}
+
+    #[test]
+    fn builds_with_components_and_tags() {
+        let mut world = World::new(8);
+        let player = world
+            .spawn_entity()
+            .with(Position(1, 2))
+            .with(Velocity(0, 1))
+            .with(Position(3, 4))
+            .tag("player")
+            .build();
+
+        assert!(world.is_alive(player));
+        assert_eq!(
+            world.get::<Position>().unwrap().get(player),
+            Some(&Position(3, 4))
+        );
+        assert_eq!(
+            world.get::<Velocity>().unwrap().get(player),
+            Some(&Velocity(0, 1))
+        );
+        assert!(world.tags.has_tag("player", &player));
+    }
+}
@@ -0,0 +1,94 @@
+// Groups of components inserted together
+
+use crate::{
+    component::Entity,
+    world::{Component, World},
+};
+
+/// A set of components inserted onto an entity in one call.
+/// Every component is a bundle of one, tuples of bundles are bundles, and
+/// `#[derive(Bundle)]` makes a struct whose fields are bundles into one.
+/// Storages for missing component types are added with the default (sparse) backend.
+pub trait Bundle: Send + Sync + 'static {
+    fn insert(self, world: &mut World, entity: Entity);
+}
+
+impl<T: Component> Bundle for T {
+    fn insert(self, world: &mut World, entity: Entity) {
+        world.insert(entity, self);
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($( $ty:ident ),+) => {
+        impl<$($ty: Bundle),+> Bundle for ($($ty,)+) {
+            #[allow(non_snake_case)]
+            fn insert(self, world: &mut World, entity: Entity) {
+                let ($($ty,)+) = self;
+                $( $ty.insert(world, entity); )+
+            }
+        }
+    };
+}
+
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use crate::{Bundle, Component, world::World};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Component)]
+    struct Position(i32, i32);
+
+    #[derive(Copy, Clone, Debug, PartialEq, Component)]
+    struct Velocity(i32, i32);
+
+    #[derive(Copy, Clone, Debug, PartialEq, Component)]
+    struct Health(u32);
+
+    #[derive(Bundle)]
+    struct Movement {
+        pos: Position,
+        vel: Velocity,
+    }
+
+    #[derive(Bundle)]
+    struct PlayerBundle {
+        movement: Movement,
+        hp: Health,
+    }
+
+    #[test]
+    fn derived_bundles_nest() {
+        let mut world = World::new(8);
+        let player = world.spawn_bundle(PlayerBundle {
+            movement: Movement {
+                pos: Position(1, 2),
+                vel: Velocity(3, 4),
+            },
+            hp: Health(10),
+        });
+        assert_eq!(
+            world.get::<Position>().unwrap().get(player),
+            Some(&Position(1, 2))
+        );
+        assert_eq!(
+            world.get::<Velocity>().unwrap().get(player),
+            Some(&Velocity(3, 4))
+        );
+        assert_eq!(
+            world.get::<Health>().unwrap().get(player),
+            Some(&Health(10))
+        );
+
+        let other = world.spawn();
+        world.insert_bundle(other, (Health(1), Position(0, 0)));
+        assert_eq!(world.get::<Health>().unwrap().get(other), Some(&Health(1)));
+    }
+}
@@ -0,0 +1,142 @@
+// Deferred structural changes
+
+use crate::{
+    bundle::Bundle,
+    component::Entity,
+    resource::Resource,
+    world::{Component, World},
+};
+
+type Command = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// Records spawns, despawns, inserts, removes and tag changes while the world is borrowed
+/// (e.g. mid-iteration), to be applied in order at a safe point with
+/// `World::apply_commands`.
+#[derive(Default)]
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+}
+
+impl std::fmt::Debug for CommandBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandBuffer")
+            .field("len", &self.commands.len())
+            .finish()
+    }
+}
+
+impl Resource for CommandBuffer {}
+
+impl CommandBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns an entity with the bundle's components.
+    pub fn spawn(&mut self, bundle: impl Bundle) {
+        self.push(move |world| {
+            world.spawn_bundle(bundle);
+        });
+    }
+
+    /// Spawns an entity with the bundle, then hands it to `then`, e.g. to link it up.
+    pub fn spawn_then(
+        &mut self,
+        bundle: impl Bundle,
+        then: impl FnOnce(&mut World, Entity) + Send + Sync + 'static,
+    ) {
+        self.push(move |world| {
+            let entity = world.spawn_bundle(bundle);
+            then(world, entity);
+        });
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.push(move |world| {
+            world.despawn(entity);
+        });
+    }
+
+    pub fn insert<T: Component>(&mut self, entity: Entity, value: T) {
+        self.push(move |world| world.insert(entity, value));
+    }
+
+    pub fn insert_bundle(&mut self, entity: Entity, bundle: impl Bundle) {
+        self.push(move |world| world.insert_bundle(entity, bundle));
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) {
+        self.push(move |world| {
+            world.remove::<T>(entity);
+        });
+    }
+
+    pub fn add_tag(&mut self, entity: Entity, tag: &'static str) {
+        self.push(move |world| world.tags.add_tag(tag, entity));
+    }
+
+    pub fn remove_tag(&mut self, entity: Entity, tag: &'static str) {
+        self.push(move |world| world.tags.remove_tag(tag, &entity));
+    }
+
+    /// Records an arbitrary change.
+    pub fn push(&mut self, command: impl FnOnce(&mut World) + Send + Sync + 'static) {
+        self.commands.push(Box::new(command));
+    }
+
+    /// Returns the number of recorded commands.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Applies and clears the recorded commands, in the order they were recorded.
+    pub fn apply(&mut self, world: &mut World) {
+        for command in self.commands.drain(..) {
+            command(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Corpse;
+    impl Component for Corpse {}
+
+    #[test]
+    fn applies_in_order() {
+        let mut world = World::new(8);
+        let alive = world.spawn_entity().with(Health(3)).build();
+        let dying = world.spawn_entity().with(Health(0)).build();
+
+        let mut commands = CommandBuffer::new();
+        for (entity, health) in world.iter::<Health>() {
+            if health.0 == 0 {
+                commands.despawn(entity);
+                commands.spawn_then(Corpse, |world, corpse| world.tags.add_tag("corpse", corpse));
+            } else {
+                commands.insert(entity, Health(health.0 - 1));
+                commands.add_tag(entity, "hurt");
+            }
+        }
+        assert_eq!(commands.len(), 4);
+
+        world.apply_commands(&mut commands);
+        assert!(commands.is_empty());
+        assert!(world.is_dead(&dying));
+        assert_eq!(world.component::<Health>(alive), Some(&Health(2)));
+        assert!(world.tags.has_tag("hurt", &alive));
+        let corpse = world.tags.expect_one("corpse");
+        assert!(world.has::<Corpse>(corpse));
+    }
+}
@@ -1,27 +1,74 @@
 // Sparse set component storage for the ecs
 
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet, VecDeque, hash_map::Entry},
+    ops::{Index, IndexMut},
+    ptr::NonNull,
+    str::FromStr,
+    sync::Arc,
+};
 
 use std::fmt::Debug;
 
-#[cfg(feature = "serde")]
-use serde::Serialize;
-
 /// Represents a unique entity in the ECS.
-/// Wraps a usize ID.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// Wraps a usize ID: the low half is the slot index, the high half a generation that
+/// `World::despawn` bumps, so handles kept past a despawn no longer match the slot's new
+/// occupant. `Entity(n)` is index `n` at generation 0. Each half is `usize::BITS / 2`
+/// wide, so on 32-bit targets indices and generations are limited to 16 bits.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Entity(pub usize);
 
+const GENERATION_SHIFT: u32 = usize::BITS / 2;
+const INDEX_MASK: usize = (1 << GENERATION_SHIFT) - 1;
+
 impl Entity {
+    /// Largest slot index that fits beside a generation.
+    pub const MAX_INDEX: usize = INDEX_MASK;
+    /// Largest generation that fits beside an index; the allocator wraps to 0 after it.
+    pub const MAX_GENERATION: u32 = (usize::MAX >> GENERATION_SHIFT) as u32;
+
+    /// Panics if either part is out of range, rather than truncating it into the other.
+    pub fn new(index: usize, generation: u32) -> Self {
+        assert!(
+            index <= Self::MAX_INDEX,
+            "entity index {index} out of range"
+        );
+        let entity = Entity(index | ((generation as usize) << GENERATION_SHIFT));
+        assert!(
+            entity.generation() == generation,
+            "entity generation {generation} out of range"
+        );
+        entity
+    }
+
+    /// The slot index, shared by every generation of the entity.
+    pub fn index(self) -> usize {
+        self.0 & INDEX_MASK
+    }
+
+    pub fn generation(self) -> u32 {
+        (self.0 >> GENERATION_SHIFT) as u32
+    }
+
     /// Szudzik pairing function to combine two entities into a single unique key.
+    /// Uses the slot indices, so it is unique among live entities.
     pub fn combine_key(self, other: Entity) -> usize {
-        let a = self.0;
-        let b = other.0;
+        let a = self.index();
+        let b = other.index();
         if a >= b { a * a + a + b } else { a + b * b }
     }
 }
 
+impl Debug for Entity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.generation() {
+            0 => write!(f, "Entity({})", self.index()),
+            generation => write!(f, "Entity({}v{})", self.index(), generation),
+        }
+    }
+}
+
 impl FromStr for Entity {
     type Err = &'static str;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -34,35 +81,461 @@ impl FromStr for Entity {
 #[derive(Clone)]
 enum SparseIndex {
     Vec(Vec<Option<usize>>),
+    Paged(PagedIndex),
     Map(HashMap<usize, usize>),
 }
 
+/// Slots per page of a `PagedIndex`.
+const PAGE_SIZE: usize = 4096;
+
+/// A sparse index split into fixed-size pages, each allocated the first time an entity
+/// in its range is inserted. Lookups cost one extra indirection over a flat vector, but a
+/// few entities scattered over a large id range only pay for the pages they touch.
+#[derive(Clone, Default)]
+struct PagedIndex {
+    pages: Vec<Option<Box<[Option<usize>]>>>,
+}
+
+impl PagedIndex {
+    fn get(&self, index: usize) -> Option<usize> {
+        self.pages.get(index / PAGE_SIZE)?.as_ref()?[index % PAGE_SIZE]
+    }
+
+    /// The index's slot, allocating its page if needed.
+    fn slot(&mut self, index: usize) -> &mut Option<usize> {
+        let page = index / PAGE_SIZE;
+        if page >= self.pages.len() {
+            self.pages.resize_with(page + 1, || None);
+        }
+        let page = self.pages[page].get_or_insert_with(|| vec![None; PAGE_SIZE].into());
+        &mut page[index % PAGE_SIZE]
+    }
+
+    fn page_count(&self) -> usize {
+        self.pages.iter().flatten().count()
+    }
+}
+
+/// The entity's slot in a sparse vector, growing the vector (at least doubling it) if the
+/// index is past the end, so inserts stay amortized O(1).
+fn grow_to(sparse: &mut Vec<Option<usize>>, index: usize) -> &mut Option<usize> {
+    if index >= sparse.len() {
+        sparse.resize((index + 1).max(sparse.len() * 2), None);
+    }
+    &mut sparse[index]
+}
+
+/// Dense component values: one growable Vec, or for pool storages fixed-capacity chunks
+/// that are never reallocated, so values keep their address.
+///
+/// Zero-sized values (tag-like components) take no memory in either layout: a `Vec` of
+/// them only counts its length, and unchecked access hands out a dangling pointer without
+/// reading any `Vec` header.
+#[derive(Clone)]
+enum Column<T> {
+    Vec(Vec<T>),
+    Chunks {
+        chunks: Vec<Vec<T>>,
+        chunk_size: usize,
+        len: usize,
+    },
+}
+
+impl<T> Column<T> {
+    const ZERO_SIZED: bool = size_of::<T>() == 0;
+
+    fn len(&self) -> usize {
+        match self {
+            Column::Vec(values) => values.len(),
+            Column::Chunks { len, .. } => *len,
+        }
+    }
+
+    fn push(&mut self, value: T) {
+        match self {
+            Column::Vec(values) => values.push(value),
+            Column::Chunks {
+                chunks,
+                chunk_size,
+                len,
+            } => {
+                if *len % *chunk_size == 0 {
+                    chunks.push(Vec::with_capacity(*chunk_size));
+                }
+                // Within capacity, so the chunk never reallocates
+                chunks.last_mut().expect("chunk pushed above").push(value);
+                *len += 1;
+            }
+        }
+    }
+
+    /// The values as contiguous runs: the whole Vec, or each chunk.
+    fn slices(&self) -> &[Vec<T>] {
+        match self {
+            Column::Vec(values) => std::slice::from_ref(values),
+            Column::Chunks { chunks, .. } => chunks,
+        }
+    }
+
+    fn slices_mut(&mut self) -> &mut [Vec<T>] {
+        match self {
+            Column::Vec(values) => std::slice::from_mut(values),
+            Column::Chunks { chunks, .. } => chunks,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slices().iter().flatten()
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slices_mut().iter_mut().flatten()
+    }
+
+    unsafe fn get_unchecked(&self, idx: usize) -> &T {
+        if Self::ZERO_SIZED {
+            // SAFETY: any aligned non-null pointer is a valid zero-sized value
+            return unsafe { NonNull::dangling().as_ref() };
+        }
+        match self {
+            Column::Vec(values) => unsafe { values.get_unchecked(idx) },
+            Column::Chunks {
+                chunks, chunk_size, ..
+            } => unsafe {
+                chunks
+                    .get_unchecked(idx / chunk_size)
+                    .get_unchecked(idx % chunk_size)
+            },
+        }
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, idx: usize) -> &mut T {
+        if Self::ZERO_SIZED {
+            // SAFETY: as in `get_unchecked`
+            return unsafe { NonNull::dangling().as_mut() };
+        }
+        match self {
+            Column::Vec(values) => unsafe { values.get_unchecked_mut(idx) },
+            Column::Chunks {
+                chunks, chunk_size, ..
+            } => unsafe {
+                chunks
+                    .get_unchecked_mut(idx / *chunk_size)
+                    .get_unchecked_mut(idx % *chunk_size)
+            },
+        }
+    }
+
+    /// Pointer to the value at `idx`. Only touches the `Vec` headers, never the values.
+    ///
+    /// # Safety
+    /// `idx` must be less than `len()`.
+    unsafe fn value_ptr(&mut self, idx: usize) -> *mut T {
+        if Self::ZERO_SIZED {
+            return NonNull::dangling().as_ptr();
+        }
+        match self {
+            Column::Vec(values) => unsafe { values.as_mut_ptr().add(idx) },
+            Column::Chunks {
+                chunks, chunk_size, ..
+            } => unsafe {
+                let chunk = &mut *chunks.as_mut_ptr().add(idx / *chunk_size);
+                chunk.as_mut_ptr().add(idx % *chunk_size)
+            },
+        }
+    }
+
+    /// Drops every value. Pool storages free their chunks.
+    fn clear(&mut self) {
+        match self {
+            Column::Vec(values) => values.clear(),
+            Column::Chunks { chunks, len, .. } => {
+                chunks.clear();
+                *len = 0;
+            }
+        }
+    }
+
+    /// The growable Vec, for operations that move values. Pool storages never do.
+    fn moving(&mut self) -> &mut Vec<T> {
+        match self {
+            Column::Vec(values) => values,
+            Column::Chunks { .. } => unreachable!("pool storage values never move"),
+        }
+    }
+}
+
+impl<T> Index<usize> for Column<T> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        match self {
+            Column::Vec(values) => &values[idx],
+            Column::Chunks {
+                chunks, chunk_size, ..
+            } => &chunks[idx / chunk_size][idx % chunk_size],
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Column<T> {
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        match self {
+            Column::Vec(values) => &mut values[idx],
+            Column::Chunks {
+                chunks, chunk_size, ..
+            } => &mut chunks[idx / *chunk_size][idx % *chunk_size],
+        }
+    }
+}
+
+/// One chunk of a storage's values, from `Storage::chunks`.
+/// Tombstoned slots keep their last value, so check `entity` before trusting `values[i]`.
+#[derive(Copy, Clone, Debug)]
+pub struct Chunk<'a, T> {
+    pub values: &'a [T],
+    entities: &'a [usize],
+}
+
+impl<'a, T> Chunk<'a, T> {
+    /// The entity owning `values[i]`, or `None` for an empty slot.
+    pub fn entity(&self, i: usize) -> Option<Entity> {
+        let id = *self.entities.get(i)?;
+        (id != TOMBSTONE).then_some(Entity(id))
+    }
+
+    /// Live `(entity, value)` pairs in this chunk.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &'a T)> + 'a {
+        let entities = self.entities;
+        self.values
+            .iter()
+            .enumerate()
+            .filter(move |&(i, _)| entities[i] != TOMBSTONE)
+            .map(move |(i, value)| (Entity(entities[i]), value))
+    }
+}
+
+/// Entity slot marking a removed entry in `RemovalMode::Tombstone`.
+const TOMBSTONE: usize = usize::MAX;
+
+/// How `Storage::remove_entity` treats the dense arrays.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RemovalMode {
+    /// Move the last entry into the hole. Compact, but reorders iteration.
+    SwapRemove,
+    /// Leave a tombstone in place, keeping iteration order stable and removal cheap.
+    /// Compacts automatically once tombstones make up more than `compact_ratio` of the
+    /// dense slots; use a ratio of 1.0 or more to only compact via `Storage::compact`.
+    Tombstone { compact_ratio: f32 },
+}
+
+/// What a storage with a quota does when inserting a new entity at the cap.
+#[derive(Copy, Clone, Debug)]
+pub enum QuotaPolicy<T> {
+    /// Refuse the insert: `try_set` returns `QuotaExceeded`, `set`/`add_entity` panic.
+    Reject,
+    /// Remove the existing instance with the lowest priority to make room.
+    EvictLowest(fn(&T) -> i64),
+    /// Ring behavior for short-lived entries (particles, decals): remove the instance that
+    /// was inserted longest ago. Amortized O(1).
+    ///
+    /// Evicting only removes the component; the entity stays alive. Evictions are listed
+    /// in `Storage::evicted`, and `World::despawn_evicted` despawns them.
+    EvictOldest,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Quota<T> {
+    max: usize,
+    policy: QuotaPolicy<T>,
+}
+
+/// Returned when inserting into a storage at its quota with `QuotaPolicy::Reject`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    pub max: usize,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "storage quota of {} reached", self.max)
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// When an entity's component was added and last changed, in world change ticks.
+/// See `World::change_tick`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u64,
+    pub changed: u64,
+}
+
+impl ComponentTicks {
+    /// True if the component was added after `since`.
+    pub fn is_added(&self, since: u64) -> bool {
+        self.added > since
+    }
+
+    /// True if the component was added or changed after `since`.
+    pub fn is_changed(&self, since: u64) -> bool {
+        self.changed > since
+    }
+}
+
+/// Everything that happened to a storage since the last `Storage::take_changes`, for
+/// consumers mirroring it elsewhere, e.g. a renderer or the network. Apply `removed`
+/// before `added`, since an entity can lose and regain the component in one batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageChanges<T> {
+    /// Entities that gained the component, with its current value.
+    pub added: Vec<(Entity, T)>,
+    pub removed: Vec<Entity>,
+    /// Dense relocations, if move tracking is on; see `Storage::moved`.
+    pub moved: Vec<(Entity, usize, usize)>,
+    /// Entities that already had the component and were written since the last take,
+    /// with the current value. Relies on change ticks, so a standalone storage only
+    /// reports these after its first take, or once given ticks with `set_change_tick`.
+    pub modified: Vec<(Entity, T)>,
+}
+
+impl<T> StorageChanges<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+/// Callback run when an entity gains or loses a component, see `Storage::on_insert`.
+type Hook<T> = Arc<dyn Fn(Entity, &T) + Send + Sync>;
+
+/// Checks a value before it's written, returning why it's invalid.
+pub type Validator<T> = fn(&T) -> Result<(), String>;
+
 /// Unified component storage that can use either a sparse vector index or a hashmap index.
 /// This allows a single concrete storage type to be used throughout the World API while
 /// still choosing an indexing strategy per component type.
+/// `Storage<T>` is `Send + Sync` for every `T` it accepts.
+/// Values are `Copy`, so none has a destructor to run: removed and tombstoned slots are
+/// simply overwritten later. Types owning resources (file handles, `Rc`s) can't be stored.
 #[derive(Clone)]
 pub struct Storage<T: Send + Sync + Copy + Clone> {
     pub added: Vec<Entity>,
     pub removed: Vec<Entity>,
+    /// `(entity, old dense index, new dense index)` for entries moved by swap-removal or
+    /// compaction. Only recorded once enabled with `set_move_tracking`.
+    pub moved: Vec<(Entity, usize, usize)>,
+    /// Entities whose entry a quota removed to make room. They stay alive, with any other
+    /// components, until despawned; see `World::despawn_evicted`.
+    pub evicted: Vec<Entity>,
     index: SparseIndex,
-    dense: Vec<T>,
+    dense: Column<T>,
     entities: Vec<usize>,
+    /// Change ticks of each dense slot, parallel to `entities`.
+    ticks: Vec<ComponentTicks>,
+    /// Tick stamped on writes, kept current by the world.
+    change_tick: u64,
+    /// `change_tick` as of the last `take_changes`, if any.
+    changes_taken: Option<u64>,
+    removal: RemovalMode,
+    tombstones: usize,
+    frozen: bool,
+    /// When false, inserts and removals aren't recorded in `added`/`removed`.
+    tracking: bool,
+    track_moves: bool,
+    quota: Option<Quota<T>>,
+    /// Insertion order for `QuotaPolicy::EvictOldest`; may hold ids removed since.
+    order: VecDeque<usize>,
+    /// Tombstoned slots a pool storage fills before growing.
+    free_slots: Vec<usize>,
+    on_insert: Vec<Hook<T>>,
+    on_remove: Vec<Hook<T>>,
+    /// Run on every written value in debug builds.
+    validator: Option<Validator<T>>,
 }
 
+// Checked for all `T` since the body of a generic fn is type-checked once.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    const fn storage_is_send_sync<T: Send + Sync + Copy>() {
+        assert_send_sync::<Storage<T>>();
+        assert_send_sync::<Entity>();
+    }
+    storage_is_send_sync::<u8>();
+};
+
+impl<T> Debug for Storage<T>
+where
+    T: Send + Sync + Copy + Clone,
+{
+    /// Summarizes the storage rather than dumping its contents.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let backend = match (&self.index, &self.dense) {
+            (SparseIndex::Vec(sparse), Column::Chunks { chunk_size, .. }) => {
+                format!("Pool({}, chunk {chunk_size})", sparse.len())
+            }
+            (SparseIndex::Vec(sparse), _) => format!("Sparse({})", sparse.len()),
+            (SparseIndex::Paged(paged), _) => format!("Paged({} pages)", paged.page_count()),
+            (SparseIndex::Map(_), _) => "HashMap".to_string(),
+        };
+        f.debug_struct("Storage")
+            .field("type", &std::any::type_name::<T>())
+            .field("backend", &backend)
+            .field("len", &self.len())
+            .field("tombstones", &self.tombstones)
+            .field("added", &self.added.len())
+            .field("removed", &self.removed.len())
+            .field("moved", &self.moved.len())
+            .field("frozen", &self.frozen)
+            .field("tracking", &self.tracking)
+            .field("quota", &self.quota.map(|quota| quota.max))
+            .field("hooks", &(self.on_insert.len() + self.on_remove.len()))
+            .finish()
+    }
+}
 
+impl<T> Default for Storage<T>
+where
+    T: Send + Sync + Copy + Clone,
+{
+    /// Hashmap-indexed, since it needs no capacity up front.
+    fn default() -> Self {
+        Self::new_hashmap()
+    }
+}
 
 impl<T> Storage<T>
 where
     T: Send + Sync + Sized + Copy + Clone,
 {
-    /// Create storage backed by a sparse vector of size `entity_count`.
+    /// Create storage backed by a sparse vector, sized for `entity_count` entities up front.
+    /// The vector grows, at least doubling, when a bigger entity id is inserted.
     pub fn new_sparse(entity_count: usize) -> Self {
         Self {
             added: Vec::new(),
             removed: Vec::new(),
+            moved: Vec::new(),
+            evicted: Vec::new(),
             index: SparseIndex::Vec(vec![None; entity_count]),
-            dense: Vec::new(),
+            dense: Column::Vec(Vec::new()),
             entities: Vec::new(),
+            ticks: Vec::new(),
+            change_tick: 0,
+            changes_taken: None,
+            removal: RemovalMode::SwapRemove,
+            tombstones: 0,
+            frozen: false,
+            tracking: true,
+            track_moves: false,
+            quota: None,
+            order: VecDeque::new(),
+            free_slots: Vec::new(),
+            on_insert: Vec::new(),
+            on_remove: Vec::new(),
+            validator: None,
         }
     }
 
@@ -71,150 +544,814 @@ where
         Self {
             added: Vec::new(),
             removed: Vec::new(),
+            moved: Vec::new(),
+            evicted: Vec::new(),
             index: SparseIndex::Map(HashMap::new()),
-            dense: Vec::new(),
+            dense: Column::Vec(Vec::new()),
             entities: Vec::new(),
+            ticks: Vec::new(),
+            change_tick: 0,
+            changes_taken: None,
+            removal: RemovalMode::SwapRemove,
+            tombstones: 0,
+            frozen: false,
+            tracking: true,
+            track_moves: false,
+            quota: None,
+            order: VecDeque::new(),
+            free_slots: Vec::new(),
+            on_insert: Vec::new(),
+            on_remove: Vec::new(),
+            validator: None,
+        }
+    }
+
+    /// Create a hashmap-indexed storage that iterates in insertion order, like an
+    /// `IndexMap`: removals leave tombstones and compaction keeps the remaining order, so
+    /// iteration never depends on hashing or on which entries were removed.
+    /// For determinism tests over low-density components.
+    pub fn new_ordered_hashmap() -> Self {
+        Self {
+            removal: RemovalMode::Tombstone { compact_ratio: 0.5 },
+            ..Self::new_hashmap()
         }
     }
 
+    /// Create storage whose sparse index is allocated in pages of 4096 entities as they're
+    /// first used. For components on a few entities spread over a large id range, where a
+    /// flat sparse vector would be mostly empty but lookups should stay cheaper than hashing.
+    pub fn new_paged() -> Self {
+        Self {
+            index: SparseIndex::Paged(PagedIndex::default()),
+            ..Self::new_sparse(0)
+        }
+    }
+
+    /// Create a sparse-indexed storage whose values never move, for consumers that hold
+    /// pointers to components between frames (e.g. across FFI). Values live in chunks of
+    /// `chunk_size` that are never reallocated; removal leaves a hole that a later insert
+    /// fills, so `compact` and `set_removal_mode` have no effect.
+    /// Zero-sized values have no address to keep, so they go in a single chunk whatever
+    /// `chunk_size` is.
+    pub fn new_pool(entity_count: usize, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "pool chunk size must be at least 1");
+        Self {
+            dense: Column::Chunks {
+                chunks: Vec::new(),
+                // A zero-sized Vec's capacity is unbounded and never allocates
+                chunk_size: if Column::<T>::ZERO_SIZED {
+                    usize::MAX
+                } else {
+                    chunk_size
+                },
+                len: 0,
+            },
+            ..Self::new_sparse(entity_count)
+        }
+    }
+
+    /// Returns true for storages made with `new_pool`.
+    pub fn is_pool(&self) -> bool {
+        matches!(self.dense, Column::Chunks { .. })
+    }
+
+    /// Dense slot the next new entity goes in.
+    fn next_slot(&self) -> usize {
+        self.free_slots
+            .last()
+            .copied()
+            .unwrap_or_else(|| self.dense.len())
+    }
+
     /// Sets the data for the given entity, replacing any existing data.
     /// If the entity does not exist, it will be added.
+    /// Probes the index once on either path; overwriting is treated as the hot path.
+    /// Panics if the slot belongs to another generation of the entity.
+    #[track_caller]
     pub fn set(&mut self, data: T, entity: Entity) {
-        match &mut self.index {
-            SparseIndex::Vec(sparse) => match sparse[entity.0] {
-                Some(idx) => self.dense[idx] = data,
-                None => self.add_entity(data, entity),
-            },
-            SparseIndex::Map(index) => {
-                if let Some(&idx) = index.get(&entity.0) {
-                    self.dense[idx] = data;
+        self.check_frozen();
+        #[cfg(debug_assertions)]
+        self.check_valid(&data, entity);
+        if self.quota.is_some() && !self.has(entity) {
+            self.make_room().unwrap_or_else(|err| panic!("{err}"));
+        }
+        let next = self.next_slot();
+        let existing = match &mut self.index {
+            SparseIndex::Vec(sparse) => {
+                let slot = grow_to(sparse, entity.index());
+                if slot.is_none() {
+                    *slot = Some(next);
+                    None
                 } else {
-                    self.add_entity(data, entity);
+                    *slot
+                }
+            }
+            SparseIndex::Paged(paged) => {
+                let slot = paged.slot(entity.index());
+                if slot.is_none() {
+                    *slot = Some(next);
+                    None
+                } else {
+                    *slot
+                }
+            }
+            SparseIndex::Map(index) => match index.entry(entity.index()) {
+                Entry::Occupied(occupied) => Some(*occupied.get()),
+                Entry::Vacant(vacant) => {
+                    vacant.insert(next);
+                    None
                 }
+            },
+        };
+        match existing {
+            Some(idx) => {
+                assert_eq!(
+                    self.entities[idx], entity.0,
+                    "stale entity handle {entity:?}"
+                );
+                self.dense[idx] = data;
+                self.mark_changed(idx);
             }
+            None => self.push_dense(data, entity),
         }
     }
 
     /// Adds a new entity with the given component data. Panics if the entity already exists.
+    #[track_caller]
     pub fn add_entity(&mut self, data: T, entity: Entity) {
-        let idx = self.dense.len();
+        self.check_frozen();
+        #[cfg(debug_assertions)]
+        self.check_valid(&data, entity);
+        if self.quota.is_some() {
+            self.make_room().unwrap_or_else(|err| panic!("{err}"));
+        }
+        let idx = self.next_slot();
         match &mut self.index {
             SparseIndex::Vec(sparse) => {
-                assert_eq!(sparse[entity.0], None);
-                sparse[entity.0] = Some(idx);
+                let slot = grow_to(sparse, entity.index());
+                assert_eq!(*slot, None);
+                *slot = Some(idx);
+            }
+            SparseIndex::Paged(paged) => {
+                let slot = paged.slot(entity.index());
+                assert_eq!(*slot, None);
+                *slot = Some(idx);
             }
             SparseIndex::Map(index) => {
-                assert!(!index.contains_key(&entity.0));
-                index.insert(entity.0, idx);
+                assert!(!index.contains_key(&entity.index()));
+                index.insert(entity.index(), idx);
+            }
+        }
+        self.push_dense(data, entity);
+    }
+
+    /// Inserts the value, returning the entity's previous one if it had any, like
+    /// `HashMap::insert`. Unlike `add_entity` it doesn't panic when the entity already has
+    /// the component, and unlike `set` it hands the replaced value back.
+    /// Panics on a stale entity handle, like `set`.
+    #[track_caller]
+    pub fn insert(&mut self, entity: Entity, data: T) -> Option<T> {
+        let previous = self.get(entity).copied();
+        self.set(data, entity);
+        previous
+    }
+
+    /// Like `set`, but reports a `QuotaPolicy::Reject` quota instead of panicking.
+    /// Returns the entity evicted to make room, if any.
+    #[track_caller]
+    pub fn try_set(&mut self, data: T, entity: Entity) -> Result<Option<Entity>, QuotaExceeded> {
+        let evicted = if self.quota.is_some() && !self.has(entity) {
+            self.make_room()?
+        } else {
+            None
+        };
+        self.set(data, entity);
+        Ok(evicted)
+    }
+
+    /// Caps the number of entities with this component. Existing entries over the cap are
+    /// kept; the quota applies to later inserts.
+    pub fn set_quota(&mut self, max: usize, policy: QuotaPolicy<T>) {
+        self.quota = Some(Quota { max, policy });
+        self.order.clear();
+        if let QuotaPolicy::EvictOldest = policy {
+            // Treat existing entries as oldest in their current dense order
+            let live = self.entities.iter().filter(|&&id| id != TOMBSTONE);
+            self.order.extend(live);
+        }
+    }
+
+    pub fn clear_quota(&mut self) {
+        self.quota = None;
+        self.order.clear();
+    }
+
+    pub fn quota(&self) -> Option<usize> {
+        self.quota.map(|quota| quota.max)
+    }
+
+    /// Frees a slot if the storage is at its quota, returning the evicted entity.
+    #[track_caller]
+    fn make_room(&mut self) -> Result<Option<Entity>, QuotaExceeded> {
+        let Some(Quota { max, policy }) = self.quota else {
+            return Ok(None);
+        };
+        if self.len() < max {
+            return Ok(None);
+        }
+        let victim = match policy {
+            QuotaPolicy::Reject => None,
+            QuotaPolicy::EvictLowest(priority) => self
+                .iter()
+                .min_by_key(|(_, data)| priority(data))
+                .map(|(entity, _)| entity),
+            QuotaPolicy::EvictOldest => {
+                // Skip ids that were removed by hand since they were inserted
+                let (index, entities) = (&self.index, &self.entities);
+                std::iter::from_fn(|| self.order.pop_front())
+                    .map(Entity)
+                    .find(|&entity| Self::lookup(index, entities, entity).is_some())
+            }
+        }
+        .ok_or(QuotaExceeded { max })?;
+        self.remove_entity(victim);
+        if self.tracking {
+            self.evicted.push(victim);
+        }
+        Ok(Some(victim))
+    }
+
+    /// Copies components for every entity in `other` accepted by `filter`, overwriting
+    /// existing entries. Walks `other`'s dense arrays directly and reserves up front, so it
+    /// suits bulk extract/replication between worlds. Returns the number copied.
+    #[track_caller]
+    pub fn copy_from(
+        &mut self,
+        other: &Storage<T>,
+        mut filter: impl FnMut(Entity) -> bool,
+    ) -> usize {
+        self.check_frozen();
+        if let Column::Vec(values) = &mut self.dense {
+            values.reserve(other.len());
+        }
+        self.entities.reserve(other.len());
+        self.ticks.reserve(other.len());
+        let mut copied = 0;
+        for (&id, data) in other.entities.iter().zip(other.dense.iter()) {
+            if id == TOMBSTONE || !filter(Entity(id)) {
+                continue;
+            }
+            self.set(*data, Entity(id));
+            copied += 1;
+        }
+        copied
+    }
+
+    /// Fills `next_slot`, appending to the dense arrays unless a pool hole is reused;
+    /// the index must already point at that slot.
+    /// Cold so `set`'s overwrite path is laid out as the fall-through.
+    #[cold]
+    fn push_dense(&mut self, data: T, entity: Entity) {
+        // Untracked inserts never count as added
+        let tick = if self.tracking { self.change_tick } else { 0 };
+        let ticks = ComponentTicks {
+            added: tick,
+            changed: tick,
+        };
+        let slot = if let Some(slot) = self.free_slots.pop() {
+            self.dense[slot] = data;
+            self.entities[slot] = entity.0;
+            self.ticks[slot] = ticks;
+            self.tombstones -= 1;
+            slot
+        } else {
+            self.dense.push(data);
+            self.entities.push(entity.0);
+            self.ticks.push(ticks);
+            self.dense.len() - 1
+        };
+        if self.tracking {
+            self.added.push(entity);
+        }
+        for hook in &self.on_insert {
+            hook(entity, &self.dense[slot]);
+        }
+        if let Some(Quota {
+            max,
+            policy: QuotaPolicy::EvictOldest,
+        }) = self.quota
+        {
+            self.order.push_back(entity.0);
+            // Drop ids removed by hand so the queue stays bounded
+            if self.order.len() > max.max(1) * 2 {
+                let (index, entities) = (&self.index, &self.entities);
+                self.order
+                    .retain(|&id| Self::lookup(index, entities, Entity(id)).is_some());
+            }
+        }
+    }
+
+    /// Removes an entity and returns its component data, if present.
+    #[track_caller]
+    pub fn remove_entity(&mut self, entity: Entity) -> Option<T> {
+        self.check_frozen();
+        let idx = self.dense_index_of(entity)?;
+        match &mut self.index {
+            SparseIndex::Vec(sparse) => sparse[entity.index()] = None,
+            SparseIndex::Paged(paged) => *paged.slot(entity.index()) = None,
+            SparseIndex::Map(index) => {
+                index.remove(&entity.index());
+            }
+        }
+
+        if self.is_pool() {
+            self.entities[idx] = TOMBSTONE;
+            self.tombstones += 1;
+            self.free_slots.push(idx);
+            let removed = self.dense[idx];
+            self.track_removed(entity, &removed);
+            return Some(removed);
+        }
+
+        if let RemovalMode::Tombstone { compact_ratio } = self.removal {
+            self.entities[idx] = TOMBSTONE;
+            self.tombstones += 1;
+            let removed = self.dense[idx];
+            self.track_removed(entity, &removed);
+            if self.tombstones as f32 > self.dense.len() as f32 * compact_ratio {
+                self.compact();
+            }
+            return Some(removed);
+        }
+
+        let last = self.dense.len() - 1;
+        self.entities.swap_remove(idx);
+        self.ticks.swap_remove(idx);
+        let removed = self.dense.moving().swap_remove(idx);
+        if idx != last {
+            // Update index for the entity that was moved
+            let moved_entity = Entity(self.entities[idx]).index();
+            match &mut self.index {
+                SparseIndex::Vec(sparse) => {
+                    sparse[moved_entity] = Some(idx);
+                }
+                SparseIndex::Paged(paged) => {
+                    *paged.slot(moved_entity) = Some(idx);
+                }
+                SparseIndex::Map(index) => {
+                    index.insert(moved_entity, idx);
+                }
+            }
+            if self.track_moves {
+                self.moved.push((Entity(self.entities[idx]), last, idx));
+            }
+        }
+        self.track_removed(entity, &removed);
+        Some(removed)
+    }
+
+    /// Removes every entity at once, recording each in `removed` and running the remove
+    /// hooks. The backend, quota and hooks are kept.
+    #[track_caller]
+    pub fn clear(&mut self) {
+        self.drain();
+    }
+
+    /// Empties the storage like `clear`, returning the removed `(entity, value)` pairs in
+    /// storage order, e.g. to hand a frame's one-shot components to a consumer.
+    #[track_caller]
+    pub fn drain(&mut self) -> std::vec::IntoIter<(Entity, T)> {
+        self.check_frozen();
+        let drained: Vec<(Entity, T)> = self.iter().map(|(entity, &data)| (entity, data)).collect();
+        match &mut self.index {
+            SparseIndex::Vec(sparse) => sparse.fill(None),
+            SparseIndex::Paged(paged) => paged.pages.clear(),
+            SparseIndex::Map(index) => index.clear(),
+        }
+        self.dense.clear();
+        self.entities.clear();
+        self.ticks.clear();
+        self.tombstones = 0;
+        self.free_slots.clear();
+        self.order.clear();
+        for (entity, data) in &drained {
+            self.track_removed(*entity, data);
+        }
+        drained.into_iter()
+    }
+
+    /// Records a removal and runs the remove hooks.
+    fn track_removed(&mut self, entity: Entity, removed: &T) {
+        if self.tracking {
+            self.removed.push(entity);
+        }
+        for hook in &self.on_remove {
+            hook(entity, removed);
+        }
+    }
+
+    /// Registers a hook run whenever an entity gains this component, after it's stored.
+    /// Overwriting an existing value doesn't count. Hooks run on every insert path,
+    /// including untracked writes and quota evictions, and are shared with forked worlds.
+    pub fn on_insert(&mut self, hook: impl Fn(Entity, &T) + Send + Sync + 'static) {
+        self.on_insert.push(Arc::new(hook));
+    }
+
+    /// Sets the check run on values passed to `set` and `add_entity` in debug builds,
+    /// panicking at the write site if it fails. Writes through `get_mut` aren't checked.
+    /// The world sets `Component::validate` on the storages it creates.
+    pub fn set_validator(&mut self, validator: Validator<T>) {
+        self.validator = Some(validator);
+    }
+
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    fn check_valid(&self, data: &T, entity: Entity) {
+        if let Some(validate) = self.validator
+            && let Err(reason) = validate(data)
+        {
+            panic!(
+                "invalid {} for {entity:?}: {reason}",
+                std::any::type_name::<T>()
+            );
+        }
+    }
+
+    /// Registers a hook run with the removed value whenever an entity loses this
+    /// component, whether by `remove_entity`, despawn or quota eviction.
+    pub fn on_remove(&mut self, hook: impl Fn(Entity, &T) + Send + Sync + 'static) {
+        self.on_remove.push(Arc::new(hook));
+    }
+
+    /// Drops every insert and remove hook.
+    pub fn clear_hooks(&mut self) {
+        self.on_insert.clear();
+        self.on_remove.clear();
+    }
+
+    /// Turns recording of inserts and removals in `added`/`removed` on or off, e.g. while
+    /// applying authoritative server state that shouldn't echo back as local changes.
+    /// Covers every write path, including queries, until turned back on.
+    pub fn set_tracking(&mut self, enabled: bool) {
+        self.tracking = enabled;
+    }
+
+    pub fn is_tracking(&self) -> bool {
+        self.tracking
+    }
+
+    /// Turns recording of dense index moves in `moved` on or off, so mirrored external
+    /// buffers can be patched in place rather than rebuilt. Off by default. Moves are
+    /// recorded even while `set_tracking(false)`, since a mirror needs every one.
+    pub fn set_move_tracking(&mut self, enabled: bool) {
+        self.track_moves = enabled;
+    }
+
+    pub fn is_tracking_moves(&self) -> bool {
+        self.track_moves
+    }
+
+    /// Like `set`, but a new entity isn't recorded in `added`.
+    #[track_caller]
+    pub fn set_untracked(&mut self, data: T, entity: Entity) {
+        let tracking = std::mem::replace(&mut self.tracking, false);
+        self.set(data, entity);
+        self.tracking = tracking;
+    }
+
+    /// Like `remove_entity`, but the removal isn't recorded in `removed`.
+    #[track_caller]
+    pub fn remove_untracked(&mut self, entity: Entity) -> Option<T> {
+        let tracking = std::mem::replace(&mut self.tracking, false);
+        let removed = self.remove_entity(entity);
+        self.tracking = tracking;
+        removed
+    }
+
+    /// Empties `added`, `removed` and `moved`.
+    pub fn clear_tracked(&mut self) {
+        self.added.clear();
+        self.removed.clear();
+        self.moved.clear();
+        self.evicted.clear();
+    }
+
+    /// Drains `added`, `removed` and `moved` into one batch, along with every entry
+    /// written since the previous call, so a mirror can catch up in a single pass.
+    /// Writes later in the same tick are stamped with the next one, so the next call
+    /// still sees them even if the world's tick hasn't moved.
+    pub fn take_changes(&mut self) -> StorageChanges<T> {
+        let since = self.changes_taken.unwrap_or(0);
+        self.changes_taken = Some(self.change_tick);
+        self.change_tick += 1;
+        let mut seen = HashSet::new();
+        let added = std::mem::take(&mut self.added)
+            .into_iter()
+            .filter(|&entity| seen.insert(entity))
+            .filter_map(|entity| Some((entity, *self.get(entity)?)))
+            .collect();
+        let modified = self
+            .entities
+            .iter()
+            .zip(&self.ticks)
+            .enumerate()
+            .filter(|&(_, (&id, ticks))| {
+                id != TOMBSTONE && ticks.is_changed(since) && !ticks.is_added(since)
+            })
+            .map(|(idx, (&id, _))| (Entity(id), self.dense[idx]))
+            .collect();
+        StorageChanges {
+            added,
+            removed: std::mem::take(&mut self.removed),
+            moved: std::mem::take(&mut self.moved),
+            modified,
+        }
+    }
+
+    /// Sets how removals treat the dense arrays. Switching back to `SwapRemove` compacts.
+    pub fn set_removal_mode(&mut self, mode: RemovalMode) {
+        self.removal = mode;
+        if mode == RemovalMode::SwapRemove {
+            self.compact();
+        }
+    }
+
+    pub fn removal_mode(&self) -> RemovalMode {
+        self.removal
+    }
+
+    /// Returns the number of tombstoned slots waiting for `compact`.
+    pub fn tombstones(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Removes tombstones, shifting live entries down while keeping their order.
+    #[track_caller]
+    pub fn compact(&mut self) {
+        self.check_frozen();
+        if self.tombstones == 0 || self.is_pool() {
+            return;
+        }
+        let mut write = 0;
+        for read in 0..self.entities.len() {
+            let id = self.entities[read];
+            if id == TOMBSTONE {
+                continue;
+            }
+            if read != write {
+                self.entities[write] = id;
+                self.ticks[write] = self.ticks[read];
+                self.dense[write] = self.dense[read];
+                match &mut self.index {
+                    SparseIndex::Vec(sparse) => sparse[Entity(id).index()] = Some(write),
+                    SparseIndex::Paged(paged) => *paged.slot(Entity(id).index()) = Some(write),
+                    SparseIndex::Map(index) => {
+                        index.insert(Entity(id).index(), write);
+                    }
+                }
+                if self.track_moves {
+                    self.moved.push((Entity(id), read, write));
+                }
+            }
+            write += 1;
+        }
+        self.entities.truncate(write);
+        self.ticks.truncate(write);
+        self.dense.moving().truncate(write);
+        self.tombstones = 0;
+    }
+
+    /// Gets a reference to the component data for the given entity.
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        Some(&self.dense[self.dense_index_of(entity)?])
+    }
+
+    /// Gets a mutable reference to the component data for the given entity, marking it
+    /// changed.
+    #[track_caller]
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.check_frozen();
+        let idx = self.dense_index_of(entity)?;
+        self.mark_changed(idx);
+        Some(&mut self.dense[idx])
+    }
+
+    /// The entity's value, inserting `default()` first if it has none. Lets a system
+    /// attach a component lazily without checking `has` first.
+    #[track_caller]
+    pub fn get_or_insert_with(&mut self, entity: Entity, default: impl FnOnce() -> T) -> &mut T {
+        self.entry(entity).or_insert_with(default)
+    }
+
+    /// The entity's slot, for inserting or updating in place with a single lookup:
+    /// `velocities.entry(e).and_modify(|v| v.x += 1.0).or_insert(Velocity::default())`.
+    #[track_caller]
+    pub fn entry(&mut self, entity: Entity) -> StorageEntry<'_, T> {
+        self.check_frozen();
+        match self.dense_index_of(entity) {
+            Some(idx) => StorageEntry::Occupied(OccupiedEntry {
+                storage: self,
+                entity,
+                idx,
+            }),
+            None => StorageEntry::Vacant(VacantEntry {
+                storage: self,
+                entity,
+            }),
+        }
+    }
+
+    /// Like `get_mut`, for writes that must never count as local changes: the change tick
+    /// is left alone.
+    #[track_caller]
+    pub fn get_mut_untracked(&mut self, entity: Entity) -> Option<&mut T> {
+        self.check_frozen();
+        let idx = self.dense_index_of(entity)?;
+        Some(&mut self.dense[idx])
+    }
+
+    /// The entity's change ticks, if it has this component.
+    pub fn ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+        Some(self.ticks[self.dense_index_of(entity)?])
+    }
+
+    /// Sets the tick later writes are stamped with. `World` does this whenever it hands
+    /// out the storage mutably, so only standalone storages need it. Ticks no later than
+    /// the last `take_changes` stamp the one after it, so writes aren't hidden from the next.
+    pub fn set_change_tick(&mut self, tick: u64) {
+        self.change_tick = match self.changes_taken {
+            Some(taken) if tick <= taken => taken + 1,
+            _ => tick,
+        };
+    }
+
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick
+    }
+
+    fn mark_changed(&mut self, idx: usize) {
+        if self.tracking {
+            self.ticks[idx].changed = self.change_tick;
+        }
+    }
+
+    /// Marks every entry changed, for iterators that hand out `&mut` to all of them.
+    fn mark_all_changed(&mut self) {
+        if self.tracking {
+            for ticks in &mut self.ticks {
+                ticks.changed = self.change_tick;
+            }
+        }
+    }
+
+    /// Points at the entity's value without creating a reference to the storage's values,
+    /// so values already handed out stay valid. For queries.
+    ///
+    /// # Safety
+    /// `this` must be valid and not borrowed mutably elsewhere.
+    #[track_caller]
+    pub(crate) unsafe fn value_ptr(this: *mut Self, entity: Entity) -> Option<*mut T> {
+        let idx = unsafe {
+            (*this).check_frozen();
+            (*this).dense_index_of(entity)?
+        };
+        // SAFETY: handed-out values live in `dense`, so borrowing `ticks` aliases nothing
+        unsafe {
+            if (*this).tracking {
+                (&mut (*this).ticks)[idx].changed = (*this).change_tick;
             }
+            Some((*this).dense.value_ptr(idx))
         }
-        self.dense.push(data);
-        self.entities.push(entity.0);
-        self.added.push(entity);
     }
 
-    /// Removes an entity and returns its component data, if present.
-    pub fn remove_entity(&mut self, entity: Entity) -> Option<T> {
-        let idx_opt = match &mut self.index {
-            SparseIndex::Vec(sparse) => {
-                let idx = sparse[entity.0]?;
-                sparse[entity.0] = None;
-                Some(idx)
-            }
-            SparseIndex::Map(index) => index.remove(&entity.0),
-        };
+    /// Returns the entity's position in the dense array, if it has this component.
+    /// Pair with `get_dense_unchecked` to hoist the lookup out of hot loops.
+    /// Only valid until the next structural change (add/remove), which can move entries.
+    /// Stale handles (an older generation of the slot's entity) find nothing.
+    pub fn dense_index_of(&self, entity: Entity) -> Option<usize> {
+        Self::lookup(&self.index, &self.entities, entity)
+    }
 
-        let idx = idx_opt?;
+    fn lookup(index: &SparseIndex, entities: &[usize], entity: Entity) -> Option<usize> {
+        let idx = match index {
+            // Ids past the end come from bigger worlds or the network; they just aren't here
+            SparseIndex::Vec(sparse) => *sparse.get(entity.index())?,
+            SparseIndex::Paged(paged) => paged.get(entity.index()),
+            SparseIndex::Map(index) => index.get(&entity.index()).copied(),
+        }?;
+        (entities[idx] == entity.0).then_some(idx)
+    }
 
-        let last = self.dense.len() - 1;
-        self.entities.swap_remove(idx);
-        let removed = self.dense.swap_remove(idx);
-        if idx != last {
-            // Update index for the entity that was moved
-            let moved_entity = self.entities[idx];
-            match &mut self.index {
-                SparseIndex::Vec(sparse) => {
-                    sparse[moved_entity] = Some(idx);
-                }
-                SparseIndex::Map(index) => {
-                    index.insert(moved_entity, idx);
-                }
-            }
-        }
-        self.removed.push(entity);
-        Some(removed)
+    /// Gets the component data at a dense index without bounds checking.
+    ///
+    /// # Safety
+    /// `idx` must be less than `len()`, e.g. from `dense_index_of` with no structural change since.
+    pub unsafe fn get_dense_unchecked(&self, idx: usize) -> &T {
+        debug_assert!(idx < self.dense.len());
+        unsafe { self.dense.get_unchecked(idx) }
     }
 
-    /// Gets a reference to the component data for the given entity.
-    pub fn get(&self, entity: Entity) -> Option<&T> {
-        match &self.index {
-            SparseIndex::Vec(sparse) => Some(&self.dense[sparse[entity.0]?]),
-            SparseIndex::Map(index) => Some(&self.dense[*index.get(&entity.0)?]),
-        }
+    /// Mutable variant of `get_dense_unchecked`.
+    ///
+    /// # Safety
+    /// `idx` must be less than `len()`, e.g. from `dense_index_of` with no structural change since.
+    #[track_caller]
+    pub unsafe fn get_dense_unchecked_mut(&mut self, idx: usize) -> &mut T {
+        self.check_frozen();
+        debug_assert!(idx < self.dense.len());
+        self.mark_changed(idx);
+        unsafe { self.dense.get_unchecked_mut(idx) }
     }
 
-    /// Gets a mutable reference to the component data for the given entity.
-    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
-        let idx = match &self.index {
-            SparseIndex::Vec(sparse) => {
-                 sparse[entity.0]?
-            }
-            SparseIndex::Map(index) => {
-                 *index.get(&entity.0)?
-            }
-        };
-        self.dense.get_mut(idx)
+    /// Makes any write or structural change panic (with a backtrace) until `unfreeze`.
+    /// Useful for finding which system is unexpectedly mutating a component type.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
     }
 
+    pub fn unfreeze(&mut self) {
+        self.frozen = false;
+    }
 
-    /// Gets a mutable reference to the component data for the given entity. Unsafe/unchecked.
-    pub fn get_mut_unchecked(&mut self, entity: Entity) -> Option<&mut T> {
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
 
-        let idx = match &self.index {
-            SparseIndex::Vec(sparse) => {
-                 sparse[entity.0]?
-            }
-            SparseIndex::Map(index) => {
-                 *index.get(&entity.0)?
-            }
-        };
-        // Safety: index was checked above
-        unsafe { Some(self.dense.get_unchecked_mut(idx)) }
+    #[track_caller]
+    fn check_frozen(&self) {
+        if self.frozen {
+            panic!(
+                "Attempted to mutate frozen Storage<{}>\n{}",
+                std::any::type_name::<T>(),
+                std::backtrace::Backtrace::force_capture()
+            );
+        }
     }
 
     /// Returns true if the component contains data for the given entity.
     pub fn has(&self, entity: Entity) -> bool {
-        match &self.index {
-            SparseIndex::Vec(sparse) => sparse[entity.0].is_some(),
-            SparseIndex::Map(index) => index.contains_key(&entity.0),
-        }
+        self.dense_index_of(entity).is_some()
     }
 
     /// Returns the number of entities with this component.
     pub fn len(&self) -> usize {
-        self.dense.len()
+        self.dense.len() - self.tombstones
+    }
+
+    /// Returns true if no entities have this component.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
     /// Uses unsafe to iterate the ECS a bit faster.
     pub fn iter_unchecked(&self) -> impl Iterator<Item = (Entity, &T)> {
         debug_assert_eq!(self.entities.len(), self.dense.len());
-        unsafe {
-            let entities_ptr = self.entities.as_ptr();
-            let dense_ptr = self.dense.as_ptr();
-            let len = self.entities.len();
-            (0..len).map(move |i| (Entity(*entities_ptr.add(i)), &*dense_ptr.add(i)))
-        }
+        let entities_ptr = self.entities.as_ptr();
+        let mut start = 0;
+        self.dense.slices().iter().flat_map(move |chunk| unsafe {
+            let entities_ptr = entities_ptr.add(start);
+            start += chunk.len();
+            let dense_ptr = chunk.as_ptr();
+            (0..chunk.len())
+                .filter(move |&i| *entities_ptr.add(i) != TOMBSTONE)
+                .map(move |i| (Entity(*entities_ptr.add(i)), &*dense_ptr.add(i)))
+        })
     }
 
     /// Uses unsafe to iterate the ECS a bit faster (mutable ref to the component data).
+    #[track_caller]
     pub fn iter_mut_unchecked(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.check_frozen();
+        self.mark_all_changed();
         debug_assert_eq!(self.entities.len(), self.dense.len());
-        unsafe {
-            let entities_ptr = self.entities.as_ptr();
-            let dense_ptr = self.dense.as_mut_ptr();
-            let len = self.entities.len();
-            (0..len).map(move |i| (Entity(*entities_ptr.add(i)), &mut *dense_ptr.add(i)))
-        }
+        let entities_ptr = self.entities.as_ptr();
+        let mut start = 0;
+        self.dense
+            .slices_mut()
+            .iter_mut()
+            .flat_map(move |chunk| unsafe {
+                let entities_ptr = entities_ptr.add(start);
+                start += chunk.len();
+                let dense_ptr = chunk.as_mut_ptr();
+                (0..chunk.len())
+                    .filter(move |&i| *entities_ptr.add(i) != TOMBSTONE)
+                    .map(move |i| (Entity(*entities_ptr.add(i)), &mut *dense_ptr.add(i)))
+            })
+    }
+
+    /// Iterates the values as contiguous chunks: one per pool chunk, or a single chunk of
+    /// everything for other storages. Pool chunk addresses are stable, so they can be
+    /// handed to external code once and read in place each frame.
+    pub fn chunks(&self) -> impl Iterator<Item = Chunk<'_, T>> {
+        let mut start = 0;
+        self.dense.slices().iter().map(move |chunk| {
+            let entities = &self.entities[start..start + chunk.len()];
+            start += chunk.len();
+            Chunk {
+                values: chunk,
+                entities,
+            }
+        })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
@@ -222,22 +1359,193 @@ where
             .iter()
             .copied()
             .zip(self.dense.iter())
+            .filter(|&(id, _)| id != TOMBSTONE)
             .map(|(id, data)| (Entity(id), data))
     }
 
+    #[track_caller]
     pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.check_frozen();
+        self.mark_all_changed();
         self.entities
             .iter()
             .copied()
             .zip(self.dense.iter_mut())
+            .filter(|&(id, _)| id != TOMBSTONE)
             .map(|(id, data)| (Entity(id), data))
     }
 
+    /// Iterates `(entity, dense index, value)`, for keeping external arrays (GPU buffers,
+    /// physics bodies) laid out in lockstep with the storage. Indices are the ones
+    /// `dense_index_of` returns and stay valid until the next structural change; enable
+    /// `set_move_tracking` to learn which entries a removal relocated.
+    pub fn iter_indexed(&self) -> impl Iterator<Item = (Entity, usize, &T)> {
+        self.entities
+            .iter()
+            .copied()
+            .zip(self.dense.iter())
+            .enumerate()
+            .filter(|&(_, (id, _))| id != TOMBSTONE)
+            .map(|(idx, (id, data))| (Entity(id), idx, data))
+    }
+
+    #[track_caller]
+    pub fn iter_indexed_mut(&mut self) -> impl Iterator<Item = (Entity, usize, &mut T)> {
+        self.check_frozen();
+        self.mark_all_changed();
+        self.entities
+            .iter()
+            .copied()
+            .zip(self.dense.iter_mut())
+            .enumerate()
+            .filter(|&(_, (id, _))| id != TOMBSTONE)
+            .map(|(idx, (id, data))| (Entity(id), idx, data))
+    }
+
     pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
-        self.entities.iter().map(|&id| Entity(id))
+        self.entities
+            .iter()
+            .filter(|&&id| id != TOMBSTONE)
+            .map(|&id| Entity(id))
+    }
+
+    /// Appends every `(entity, component)` pair to `out`, reserving for all of them first.
+    /// Clear and reuse the same `Vec` each frame to avoid reallocating.
+    pub fn collect_into(&self, out: &mut Vec<(Entity, T)>) {
+        out.reserve(self.len());
+        out.extend(self.iter().map(|(entity, data)| (entity, *data)));
+    }
+
+    /// Collects the storage into an owned map, presized to the storage length.
+    pub fn collect_map(&self) -> HashMap<Entity, T> {
+        let mut map = HashMap::with_capacity(self.len());
+        map.extend(self.iter().map(|(entity, data)| (entity, *data)));
+        map
+    }
+}
+
+/// One entity's slot in a storage, from `Storage::entry`.
+pub enum StorageEntry<'a, T: Send + Sync + Copy> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>),
+}
+
+/// A slot holding a value.
+pub struct OccupiedEntry<'a, T: Send + Sync + Copy> {
+    storage: &'a mut Storage<T>,
+    entity: Entity,
+    idx: usize,
+}
+
+/// A slot without a value.
+pub struct VacantEntry<'a, T: Send + Sync + Copy> {
+    storage: &'a mut Storage<T>,
+    entity: Entity,
+}
+
+impl<'a, T: Send + Sync + Copy> StorageEntry<'a, T> {
+    pub fn entity(&self) -> Entity {
+        match self {
+            StorageEntry::Occupied(entry) => entry.entity,
+            StorageEntry::Vacant(entry) => entry.entity,
+        }
+    }
+
+    /// The value, inserting `value` if there isn't one.
+    #[track_caller]
+    pub fn or_insert(self, value: T) -> &'a mut T {
+        self.or_insert_with(|| value)
+    }
+
+    /// The value, inserting `default()` if there isn't one.
+    #[track_caller]
+    pub fn or_insert_with(self, default: impl FnOnce() -> T) -> &'a mut T {
+        match self {
+            StorageEntry::Occupied(entry) => entry.into_mut(),
+            StorageEntry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    #[track_caller]
+    pub fn or_default(self) -> &'a mut T
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+
+    /// Runs `f` on the value if there is one.
+    #[track_caller]
+    pub fn and_modify(mut self, f: impl FnOnce(&mut T)) -> Self {
+        if let StorageEntry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, T: Send + Sync + Copy> OccupiedEntry<'a, T> {
+    pub fn get(&self) -> &T {
+        &self.storage.dense[self.idx]
+    }
+
+    /// The value, marking it changed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.storage.mark_changed(self.idx);
+        &mut self.storage.dense[self.idx]
+    }
+
+    /// Like `get_mut`, but borrowing for as long as the entry did.
+    pub fn into_mut(self) -> &'a mut T {
+        self.storage.mark_changed(self.idx);
+        &mut self.storage.dense[self.idx]
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: T) -> T {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    #[track_caller]
+    pub fn remove(self) -> T {
+        self.storage
+            .remove_entity(self.entity)
+            .expect("occupied entry has a value")
+    }
+}
+
+impl<'a, T: Send + Sync + Copy> VacantEntry<'a, T> {
+    /// Inserts the value like `Storage::set`, returning it.
+    #[track_caller]
+    pub fn insert(self, value: T) -> &'a mut T {
+        self.storage.set(value, self.entity);
+        self.storage
+            .get_mut(self.entity)
+            .expect("value was just inserted")
+    }
+}
+
+/// Adapters for iterators of `(entity, value)` pairs, like `Storage::iter`, that keep each
+/// value's entity attached through a pipeline.
+pub trait EntityIterExt<V>: Iterator<Item = (Entity, V)> + Sized {
+    /// Transforms each value, keeping its entity.
+    fn map_components<U>(self, mut f: impl FnMut(V) -> U) -> impl Iterator<Item = (Entity, U)> {
+        self.map(move |(entity, value)| (entity, f(value)))
+    }
+
+    /// Pairs each value with the same entity's value in `other`, if it has one:
+    /// `positions.iter().zip_storage(&velocities)` yields
+    /// `(Entity, &Position, Option<&Velocity>)`.
+    fn zip_storage<B>(self, other: &Storage<B>) -> impl Iterator<Item = (Entity, V, Option<&B>)>
+    where
+        B: Send + Sync + Copy,
+    {
+        self.map(move |(entity, value)| (entity, value, other.get(entity)))
     }
 }
 
+impl<V, I: Iterator<Item = (Entity, V)>> EntityIterExt<V> for I {}
+
 /// Attempts to get a reference to a component. If not found, executes the fallback block.
 /// Usage: and!(components, entity, comp, { continue; });
 #[macro_export]
@@ -263,6 +1571,48 @@ macro_rules! ecs_has {
     };
 }
 
+/// Runs a block for every entity with all the listed components, walking the smallest of
+/// their storages. Each binding is `name: Type` for a shared reference, `name: mut Type` for
+/// a mutable one, or `_: has Type` / `_: not Type` to filter without borrowing. Name the
+/// entity by passing an identifier before the bindings:
+/// `ecs_join!(world, entity, (pos: mut Position, vel: Velocity, _: has Collider) => { ... })`.
+/// Expands to a `World::query_filtered` loop, so `continue` and `break` work as usual.
+#[macro_export]
+macro_rules! ecs_join {
+    ($world:expr, ($($binds:tt)*) => $body:block) => {
+        $crate::ecs_join!(@munch $world, _, [] [] [] ($($binds)*) $body)
+    };
+    ($world:expr, $entity:ident, ($($binds:tt)*) => $body:block) => {
+        $crate::ecs_join!(@munch $world, $entity, [] [] [] ($($binds)*) $body)
+    };
+    (@munch $world:expr, $entity:tt, [$($name:tt)*] [$($data:ty,)*] [$($filter:ty,)*]
+        (_ : has $ty:ty $(, $($rest:tt)*)?) $body:block) => {
+        $crate::ecs_join!(@munch $world, $entity, [$($name)*] [$($data,)*]
+            [$($filter,)* $crate::query::With<$ty>,] ($($($rest)*)?) $body)
+    };
+    (@munch $world:expr, $entity:tt, [$($name:tt)*] [$($data:ty,)*] [$($filter:ty,)*]
+        (_ : not $ty:ty $(, $($rest:tt)*)?) $body:block) => {
+        $crate::ecs_join!(@munch $world, $entity, [$($name)*] [$($data,)*]
+            [$($filter,)* $crate::query::Without<$ty>,] ($($($rest)*)?) $body)
+    };
+    (@munch $world:expr, $entity:tt, [$($name:tt)*] [$($data:ty,)*] [$($filter:ty,)*]
+        ($var:tt : mut $ty:ty $(, $($rest:tt)*)?) $body:block) => {
+        $crate::ecs_join!(@munch $world, $entity, [$($name)* $var] [$($data,)* &mut $ty,]
+            [$($filter,)*] ($($($rest)*)?) $body)
+    };
+    (@munch $world:expr, $entity:tt, [$($name:tt)*] [$($data:ty,)*] [$($filter:ty,)*]
+        ($var:tt : $ty:ty $(, $($rest:tt)*)?) $body:block) => {
+        $crate::ecs_join!(@munch $world, $entity, [$($name)* $var] [$($data,)* &$ty,]
+            [$($filter,)*] ($($($rest)*)?) $body)
+    };
+    (@munch $world:expr, $entity:tt, [$($name:tt)*] [$($data:ty,)*] [$($filter:ty,)*]
+        () $body:block) => {
+        for ($entity, ($($name,)*)) in $world.query_filtered::<($($data,)*), ($($filter,)*)>() {
+            $body
+        }
+    };
+}
+
 #[allow(unused)]
 #[cfg(test)]
 mod tests {
@@ -309,6 +1659,78 @@ mod tests {
         assert_eq!(found[0].0, 6);
     }
 
+    #[test]
+    fn join_macro() {
+        use crate::world::{Component, World};
+
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct Position(i32);
+        impl Component for Position {}
+        #[derive(Copy, Clone)]
+        struct Velocity(i32);
+        impl Component for Velocity {}
+        #[derive(Copy, Clone)]
+        struct Collider;
+        impl Component for Collider {}
+        #[derive(Copy, Clone)]
+        struct Frozen;
+        impl Component for Frozen {}
+
+        let mut world = World::new(8);
+        let moving = world.spawn();
+        let frozen = world.spawn();
+        let ghost = world.spawn();
+        for entity in [moving, frozen, ghost] {
+            world.insert(entity, Position(0));
+            world.insert(entity, Velocity(2));
+        }
+        world.insert(moving, Collider);
+        world.insert(frozen, Collider);
+        world.insert(frozen, Frozen);
+
+        let mut visited = Vec::new();
+        ecs_join!(world, entity, (pos: mut Position, vel: Velocity, _: has Collider, _: not Frozen) => {
+            pos.0 += vel.0;
+            visited.push(entity);
+        });
+        assert_eq!(visited, vec![moving]);
+        assert_eq!(world.component(moving), Some(&Position(2)));
+        assert_eq!(world.component(frozen), Some(&Position(0)));
+
+        let mut total = 0;
+        ecs_join!(world, (pos: Position) => {
+            total += pos.0;
+        });
+        assert_eq!(total, 2);
+    }
+
+    #[derive(Copy, Clone, Debug, PartialEq, crate::Component)]
+    #[component(validate = "check_health")]
+    struct Health(i32);
+
+    fn check_health(health: &Health) -> Result<(), &'static str> {
+        if (0..=100).contains(&health.0) {
+            Ok(())
+        } else {
+            Err("out of range")
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Health for Entity(1): out of range")]
+    fn validation_runs_on_writes() {
+        use crate::world::World;
+
+        let mut world = World::new(4);
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, Health(100));
+        world.get_mut::<Health>().unwrap().set(Health(50), a);
+        assert_eq!(world.component(a), Some(&Health(50)));
+        world.insert(b, Health(101));
+    }
+
     #[test]
     fn test_iter() {
         let mut component = Storage::<u32>::new_sparse(5);
@@ -359,6 +1781,154 @@ mod tests {
         println!("iteration: {:?}", i.elapsed());
     }
 
+    #[test]
+    fn clear_empties_every_backend() {
+        for mut storage in [
+            Storage::<u32>::new_sparse(8),
+            Storage::new_hashmap(),
+            Storage::new_pool(8, 2),
+        ] {
+            for i in 0..5 {
+                storage.add_entity(i, Entity(i as usize));
+            }
+            storage.remove_entity(Entity(1));
+            storage.clear_tracked();
+
+            storage.clear();
+            assert!(storage.is_empty());
+            assert_eq!(storage.iter().count(), 0);
+            assert!(!storage.has(Entity(0)));
+            assert_eq!(storage.removed.len(), 4);
+
+            storage.add_entity(7, Entity(3));
+            assert_eq!(storage.get(Entity(3)), Some(&7));
+            assert_eq!(storage.len(), 1);
+        }
+    }
+
+    #[test]
+    fn entries_insert_or_update_in_place() {
+        let mut hits = Storage::<u32>::new_sparse(4);
+        for entity in [Entity(1), Entity(2), Entity(1)] {
+            hits.entry(entity)
+                .and_modify(|hits| *hits += 1)
+                .or_insert(1);
+        }
+        assert_eq!(hits.get(Entity(1)), Some(&2));
+        assert_eq!(hits.get(Entity(2)), Some(&1));
+
+        *hits.get_or_insert_with(Entity(3), || 10) += 1;
+        assert_eq!(*hits.get_or_insert_with(Entity(3), || 0), 11);
+        assert_eq!(*hits.entry(Entity(0)).or_default(), 0);
+
+        let StorageEntry::Occupied(mut entry) = hits.entry(Entity(2)) else {
+            panic!("entity 2 has hits");
+        };
+        assert_eq!(entry.insert(5), 1);
+        assert_eq!(entry.remove(), 5);
+        assert!(matches!(hits.entry(Entity(2)), StorageEntry::Vacant(_)));
+        assert_eq!(hits.len(), 3);
+    }
+
+    #[test]
+    fn insert_returns_the_replaced_value() {
+        let mut scores = Storage::<u32>::new_sparse(4);
+        assert_eq!(scores.insert(Entity(1), 10), None);
+        assert_eq!(scores.insert(Entity(1), 15), Some(10));
+        assert_eq!(scores.get(Entity(1)), Some(&15));
+        assert_eq!(scores.added, vec![Entity(1)]);
+
+        scores.remove_entity(Entity(1));
+        assert_eq!(scores.insert(Entity(1), 3), None);
+        assert_eq!(scores.len(), 1);
+    }
+
+    #[test]
+    fn out_of_range_entities_are_absent() {
+        let mut scores = Storage::<u32>::new_sparse(4);
+        scores.add_entity(1, Entity(3));
+        let far = Entity(1000);
+        assert!(!scores.has(far));
+        assert_eq!(scores.get(far), None);
+        assert_eq!(scores.get_mut(far), None);
+        assert_eq!(scores.remove_entity(far), None);
+        assert_eq!(scores.dense_index_of(Entity::new(4, 0)), None);
+    }
+
+    #[test]
+    fn sparse_index_grows_past_its_initial_size() {
+        let mut scores = Storage::<u32>::new_sparse(2);
+        scores.add_entity(1, Entity(5));
+        scores.set(2, Entity(40));
+        assert_eq!(scores.get(Entity(5)), Some(&1));
+        assert_eq!(scores.get(Entity(40)), Some(&2));
+        assert!(!scores.has(Entity(41)));
+
+        use crate::world::{Component, World};
+
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct Score(u32);
+        impl Component for Score {}
+        let mut world = World::new(2);
+        let spawned: Vec<Entity> = (0..20)
+            .map(|i| world.spawn_entity().with(Score(i)).build())
+            .collect();
+        assert_eq!(
+            world.get::<Score>().unwrap().get(spawned[19]),
+            Some(&Score(19))
+        );
+    }
+
+    #[test]
+    fn paged_index_allocates_pages_on_demand() {
+        let mut scores = Storage::<u32>::new_paged();
+        let far = Entity(1_000_000);
+        scores.add_entity(1, Entity(3));
+        scores.set(2, far);
+        scores.set(3, Entity(4));
+        assert_eq!(scores.get(far), Some(&2));
+        assert!(!scores.has(Entity(5000)));
+        assert!(format!("{scores:?}").contains("Paged(2 pages)"));
+
+        // Swap-removal re-points the moved entity's slot
+        assert_eq!(scores.remove_entity(Entity(3)), Some(1));
+        assert_eq!(scores.get(Entity(4)), Some(&3));
+        assert_eq!(scores.get(far), Some(&2));
+        scores.clear();
+        assert!(format!("{scores:?}").contains("Paged(0 pages)"));
+    }
+
+    #[test]
+    fn drain_hands_over_owned_values() {
+        let mut damage = Storage::<u32>::new_sparse(4);
+        damage.add_entity(5, Entity(2));
+        damage.add_entity(7, Entity(0));
+        damage.clear_tracked();
+
+        let total: u32 = damage.drain().map(|(_, amount)| amount).sum();
+        assert_eq!(total, 12);
+        assert!(damage.is_empty());
+        assert_eq!(damage.removed, vec![Entity(2), Entity(0)]);
+        assert_eq!(damage.drain().len(), 0);
+    }
+
+    #[test]
+    fn adapters_keep_entities_attached() {
+        let mut positions = Storage::<i32>::new_sparse(4);
+        let mut velocities = Storage::<i32>::new_sparse(4);
+        positions.add_entity(10, Entity(0));
+        positions.add_entity(20, Entity(1));
+        velocities.add_entity(2, Entity(1));
+
+        let moved: Vec<(Entity, i32)> = positions
+            .iter()
+            .zip_storage(&velocities)
+            .map(|(entity, pos, vel)| (entity, (pos, vel.copied().unwrap_or(0))))
+            .map_components(|(pos, vel)| pos + vel)
+            .collect();
+        assert_eq!(moved, vec![(Entity(0), 10), (Entity(1), 22)]);
+    }
+
     #[test]
     fn test_add_remove() {
         let mut component = Storage::<usize>::new_sparse(3);
@@ -619,6 +2189,201 @@ mod tests {
         assert_eq!(sparse.len(), map.len());
     }
 
+    #[test]
+    fn set_inserts_then_overwrites() {
+        for mut component in [Storage::<u32>::new_sparse(4), Storage::<u32>::new_hashmap()] {
+            component.set(1, Entity(2));
+            component.set(2, Entity(3));
+            component.set(3, Entity(2));
+            assert_eq!(component.len(), 2);
+            assert_eq!(component.get(Entity(2)), Some(&3));
+            assert_eq!(component.get(Entity(3)), Some(&2));
+            assert_eq!(component.added, vec![Entity(2), Entity(3)]);
+        }
+    }
+
+    #[test]
+    fn tombstone_keeps_order() {
+        let mut component = Storage::<u32>::new_sparse(8);
+        component.set_removal_mode(RemovalMode::Tombstone { compact_ratio: 1.0 });
+        for i in 0..5 {
+            component.add_entity(i as u32, Entity(i));
+        }
+        assert_eq!(component.remove_entity(Entity(1)), Some(1));
+        assert_eq!(component.remove_entity(Entity(3)), Some(3));
+        assert_eq!(component.remove_entity(Entity(3)), None);
+        assert_eq!(component.len(), 3);
+        assert_eq!(component.tombstones(), 2);
+        let order: Vec<_> = component.entities().collect();
+        assert_eq!(order, vec![Entity(0), Entity(2), Entity(4)]);
+        assert_eq!(component.iter_unchecked().count(), 3);
+
+        component.add_entity(5, Entity(5));
+        component.compact();
+        assert_eq!(component.tombstones(), 0);
+        let values: Vec<_> = component.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, vec![0, 2, 4, 5]);
+        assert_eq!(component.get(Entity(5)), Some(&5));
+        assert_eq!(component.get(Entity(1)), None);
+    }
+
+    #[test]
+    fn tombstone_auto_compacts() {
+        let mut component = Storage::<u32>::new_hashmap();
+        component.set_removal_mode(RemovalMode::Tombstone { compact_ratio: 0.5 });
+        for i in 0..4 {
+            component.add_entity(i as u32, Entity(i));
+        }
+        component.remove_entity(Entity(0));
+        component.remove_entity(Entity(1));
+        assert_eq!(component.tombstones(), 2);
+        component.remove_entity(Entity(2));
+        assert_eq!(component.tombstones(), 0);
+        assert_eq!(component.get(Entity(3)), Some(&3));
+        assert_eq!(component.dense_index_of(Entity(3)), Some(0));
+    }
+
+    #[test]
+    fn dense_index_access() {
+        let mut component = Storage::<u32>::new_sparse(4);
+        component.add_entity(10, Entity(2));
+        component.add_entity(20, Entity(3));
+        assert_eq!(component.dense_index_of(Entity(0)), None);
+        let idx = component.dense_index_of(Entity(3)).unwrap();
+        unsafe {
+            *component.get_dense_unchecked_mut(idx) += 1;
+            assert_eq!(*component.get_dense_unchecked(idx), 21);
+        }
+
+        component.set_removal_mode(RemovalMode::Tombstone { compact_ratio: 1.0 });
+        component.add_entity(30, Entity(1));
+        component.remove_entity(Entity(2));
+        for (_, _, value) in component.iter_indexed_mut() {
+            *value += 1;
+        }
+        let indexed: Vec<_> = component.iter_indexed().collect();
+        assert_eq!(indexed, vec![(Entity(3), 1, &22), (Entity(1), 2, &31)]);
+        for (entity, idx, _) in indexed {
+            assert_eq!(component.dense_index_of(entity), Some(idx));
+        }
+    }
+
+    #[test]
+    fn move_tracking_records_relocations() {
+        let mut component = Storage::<u32>::new_sparse(8);
+        component.add_entity(10, Entity(0));
+        component.remove_entity(Entity(0));
+        assert!(component.moved.is_empty(), "off by default");
+
+        component.set_move_tracking(true);
+        for id in 1..4 {
+            component.add_entity(id as u32, Entity(id));
+        }
+        component.remove_entity(Entity(1));
+        assert_eq!(component.moved, vec![(Entity(3), 2, 0)]);
+        // Removing the last entry moves nothing
+        component.remove_entity(Entity(2));
+        assert_eq!(component.moved.len(), 1);
+
+        component.clear_tracked();
+        component.set_removal_mode(RemovalMode::Tombstone { compact_ratio: 1.0 });
+        component.add_entity(4, Entity(4));
+        component.add_entity(5, Entity(5));
+        component.remove_entity(Entity(3));
+        assert!(component.moved.is_empty());
+        component.compact();
+        assert_eq!(component.moved, vec![(Entity(4), 1, 0), (Entity(5), 2, 1)]);
+        for &(entity, _, to) in &component.moved {
+            assert_eq!(component.dense_index_of(entity), Some(to));
+        }
+    }
+
+    #[test]
+    fn take_changes_batches_one_frame() {
+        let mut component = Storage::<u32>::new_sparse(8);
+        component.set_move_tracking(true);
+        component.set_change_tick(1);
+        for id in 0..4 {
+            component.add_entity(id as u32, Entity(id));
+        }
+        let first = component.take_changes();
+        assert_eq!(first.added.len(), 4);
+        assert!(first.modified.is_empty());
+        assert!(component.take_changes().is_empty());
+
+        component.set_change_tick(2);
+        *component.get_mut(Entity(1)).unwrap() = 11;
+        component.remove_entity(Entity(0));
+        component.add_entity(5, Entity(5));
+        let changes = component.take_changes();
+        assert_eq!(changes.added, vec![(Entity(5), 5)]);
+        assert_eq!(changes.removed, vec![Entity(0)]);
+        assert_eq!(changes.moved, vec![(Entity(3), 3, 0)]);
+        assert_eq!(changes.modified, vec![(Entity(1), 11)]);
+        assert!(component.added.is_empty() && component.moved.is_empty());
+    }
+
+    #[test]
+    fn writes_after_a_take_are_not_lost() {
+        let mut component = Storage::<u32>::new_sparse(4);
+        component.add_entity(1, Entity(0));
+        component.take_changes();
+        for value in [2, 3] {
+            *component.get_mut(Entity(0)).unwrap() = value;
+            assert_eq!(component.take_changes().modified, vec![(Entity(0), value)]);
+        }
+        assert!(component.take_changes().is_empty());
+
+        // Same in a world whose tick never moves outside a schedule
+        use crate::world::World;
+        let mut world = World::new(4);
+        let e = world.spawn();
+        world.insert(e, Health(1));
+        world.get_mut::<Health>().unwrap().take_changes();
+        for hp in [2, 3] {
+            world.get_mut::<Health>().unwrap().set(Health(hp), e);
+            let changes = world.get_mut::<Health>().unwrap().take_changes();
+            assert_eq!(changes.modified, vec![(e, Health(hp))]);
+        }
+    }
+
+    #[test]
+    fn freeze_allows_reads() {
+        let mut component = Storage::<u32>::new_sparse(4);
+        component.add_entity(1, Entity(0));
+        component.freeze();
+        assert!(component.is_frozen());
+        assert_eq!(component.get(Entity(0)), Some(&1));
+        assert_eq!(component.iter().count(), 1);
+        component.unfreeze();
+        component.set(2, Entity(0));
+        assert_eq!(component.get(Entity(0)), Some(&2));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn entity_index_overflow_panics() {
+        Entity::new(Entity::MAX_INDEX + 1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen Storage<u32>")]
+    fn freeze_panics_on_write() {
+        let mut component = Storage::<u32>::new_hashmap();
+        component.add_entity(1, Entity(0));
+        component.freeze();
+        component.get_mut(Entity(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen Storage<u32>")]
+    fn freeze_panics_on_entry() {
+        let mut component = Storage::<u32>::new_hashmap();
+        component.add_entity(1, Entity(0));
+        component.freeze();
+        component.entry(Entity(0)).and_modify(|value| *value = 7);
+    }
+
     #[test]
     fn trait_object_usage() {
         // Manipulate either backend via unified Storage type.
@@ -639,4 +2404,190 @@ mod tests {
         assert_eq!(s_store.get(Entity(0)), Some(&6));
         assert_eq!(m_store.get(Entity(1)), Some(&7));
     }
+
+    #[test]
+    fn copy_from_filters_and_overwrites() {
+        let mut source = Storage::<u32>::new_sparse(8);
+        source.set_removal_mode(RemovalMode::Tombstone { compact_ratio: 1.0 });
+        for id in 0..4 {
+            source.add_entity(id as u32 * 10, Entity(id));
+        }
+        source.remove_entity(Entity(1));
+
+        let mut target = Storage::<u32>::new_hashmap();
+        target.add_entity(99, Entity(2));
+        target.add_entity(5, Entity(7));
+
+        let copied = target.copy_from(&source, |e| e != Entity(3));
+        assert_eq!(copied, 2);
+        assert_eq!(target.get(Entity(0)), Some(&0));
+        assert_eq!(target.get(Entity(1)), None);
+        assert_eq!(target.get(Entity(2)), Some(&20));
+        assert_eq!(target.get(Entity(3)), None);
+        assert_eq!(target.get(Entity(7)), Some(&5));
+    }
+
+    #[test]
+    fn collect_helpers() {
+        let mut storage = Storage::<u32>::new_sparse(8);
+        storage.add_entity(1, Entity(3));
+        storage.add_entity(2, Entity(5));
+
+        let mut out = Vec::new();
+        storage.collect_into(&mut out);
+        out.sort_by_key(|&(entity, _)| entity.0);
+        assert_eq!(out, vec![(Entity(3), 1), (Entity(5), 2)]);
+
+        let map = storage.collect_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&Entity(5)], 2);
+    }
+
+    #[test]
+    fn entity_generation_packing() {
+        let e = Entity::new(7, 3);
+        assert_eq!(e.index(), 7);
+        assert_eq!(e.generation(), 3);
+        assert_eq!(Entity(7), Entity::new(7, 0));
+        assert_eq!(format!("{e:?}"), "Entity(7v3)");
+
+        let mut storage = Storage::<u32>::new_hashmap();
+        storage.add_entity(1, Entity(7));
+        assert!(!storage.has(e));
+        assert_eq!(storage.remove_entity(e), None);
+        assert_eq!(storage.get(Entity(7)), Some(&1));
+    }
+
+    #[test]
+    fn quota_rejects_or_evicts() {
+        let mut storage = Storage::<u32>::new_sparse(8);
+        storage.set_quota(2, QuotaPolicy::Reject);
+        storage.add_entity(5, Entity(0));
+        storage.add_entity(1, Entity(1));
+        assert_eq!(storage.try_set(9, Entity(2)), Err(QuotaExceeded { max: 2 }));
+        // Overwrites don't count against the quota
+        assert_eq!(storage.try_set(3, Entity(1)), Ok(None));
+
+        storage.set_quota(2, QuotaPolicy::EvictLowest(|&brightness| brightness as i64));
+        assert_eq!(storage.try_set(9, Entity(2)), Ok(Some(Entity(1))));
+        assert!(!storage.has(Entity(1)));
+        assert_eq!(storage.removed, vec![Entity(1)]);
+        storage.set(7, Entity(3));
+        assert!(!storage.has(Entity(0)));
+        assert_eq!(storage.len(), 2);
+    }
+
+    #[test]
+    fn ring_quota_evicts_oldest() {
+        let mut storage = Storage::<u32>::new_sparse(16);
+        storage.set_quota(3, QuotaPolicy::EvictOldest);
+        for id in 0..3 {
+            storage.set(id as u32, Entity(id));
+        }
+        // Removed by hand, so it is skipped rather than evicted
+        storage.remove_entity(Entity(0));
+        storage.set(3, Entity(3));
+        assert_eq!(storage.len(), 3);
+
+        storage.set(4, Entity(4));
+        assert!(!storage.has(Entity(1)));
+        storage.set(5, Entity(5));
+        assert!(!storage.has(Entity(2)));
+        let mut live: Vec<_> = storage.entities().map(|e| e.0).collect();
+        live.sort();
+        assert_eq!(live, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn pool_values_never_move() {
+        let mut storage = Storage::<u32>::new_pool(64, 4);
+        for id in 0..6 {
+            storage.set(id as u32, Entity(id));
+        }
+        let pinned = storage.get(Entity(1)).unwrap() as *const u32;
+
+        storage.remove_entity(Entity(0));
+        storage.remove_entity(Entity(4));
+        storage.compact();
+        for id in 6..40 {
+            storage.set(id as u32, Entity(id));
+        }
+        assert_eq!(storage.get(Entity(1)).unwrap() as *const u32, pinned);
+        assert_eq!(storage.len(), 38);
+        assert_eq!(storage.tombstones(), 0);
+
+        // Holes were refilled before growing: 38 slots in chunks of 4
+        let chunks: Vec<_> = storage.chunks().collect();
+        assert_eq!(chunks.len(), 10);
+        assert!(chunks[..9].iter().all(|chunk| chunk.values.len() == 4));
+        assert_eq!(chunks[9].values.len(), 2);
+        let live: usize = chunks.iter().map(|chunk| chunk.iter().count()).sum();
+        assert_eq!(live, 38);
+        assert_eq!(storage.iter_unchecked().count(), 38);
+        for (entity, value) in storage.iter_mut_unchecked() {
+            *value += entity.0 as u32;
+        }
+        assert_eq!(storage.get(Entity(39)), Some(&78));
+
+        storage.remove_entity(Entity(2));
+        let first = storage.chunks().next().unwrap();
+        assert_eq!(first.entity(2), None);
+        assert_eq!(first.entity(1), Some(Entity(1)));
+    }
+
+    #[test]
+    fn zero_sized_values_take_no_chunks() {
+        #[derive(Copy, Clone, Debug, PartialEq)]
+        struct Enemy;
+
+        let mut storage = Storage::<Enemy>::new_pool(8, 4);
+        for id in 0..100 {
+            storage.set(Enemy, Entity(id));
+        }
+        storage.remove_entity(Entity(7));
+        assert_eq!(storage.chunks().count(), 1);
+        assert_eq!(storage.len(), 99);
+        assert_eq!(storage.get(Entity(99)), Some(&Enemy));
+        assert_eq!(storage.get(Entity(7)), None);
+
+        let idx = storage.dense_index_of(Entity(42)).unwrap();
+        // SAFETY: idx was just looked up
+        assert_eq!(unsafe { *storage.get_dense_unchecked(idx) }, Enemy);
+        assert_eq!(storage.iter_mut_unchecked().count(), 99);
+    }
+
+    #[test]
+    fn untracked_writes_skip_trackers() {
+        let mut storage = Storage::<u32>::new_sparse(8);
+        storage.set(1, Entity(0));
+        storage.set_untracked(2, Entity(1));
+        storage.remove_untracked(Entity(0));
+        assert_eq!(storage.added, vec![Entity(0)]);
+        assert!(storage.removed.is_empty());
+        assert_eq!(storage.get(Entity(1)), Some(&2));
+
+        storage.set_tracking(false);
+        storage.set(3, Entity(2));
+        *storage.get_mut_untracked(Entity(2)).unwrap() += 1;
+        storage.remove_entity(Entity(1));
+        storage.set_tracking(true);
+        storage.remove_entity(Entity(2));
+        assert_eq!(storage.added, vec![Entity(0)]);
+        assert_eq!(storage.removed, vec![Entity(2)]);
+    }
+
+    #[test]
+    fn ordered_hashmap_keeps_insertion_order() {
+        let mut storage = Storage::<u32>::new_ordered_hashmap();
+        for id in [40, 3, 17, 8, 25] {
+            storage.set(id as u32, Entity(id));
+        }
+        storage.remove_entity(Entity(3));
+        storage.remove_entity(Entity(8));
+        storage.remove_entity(Entity(17));
+        storage.set(1, Entity(1));
+        let order: Vec<_> = storage.entities().map(|e| e.0).collect();
+        assert_eq!(order, vec![40, 25, 1]);
+        assert_eq!(storage.tombstones(), 0);
+    }
 }
@@ -0,0 +1,231 @@
+// Typed config resources loaded from JSON files and reloaded when the file changes
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    resource::{Resource, Resources},
+    world::World,
+};
+
+/// Why a config file couldn't be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "can't read {path:?}: {source}"),
+            ConfigError::Parse { path, source } => write!(f, "can't parse {path:?}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Sent by `reload_config` after a `Config<T>` picks up a new value.
+pub struct ConfigChanged<T> {
+    pub value: Arc<T>,
+}
+
+/// A value of `T` read from a JSON file, kept in `Resources` and reloaded by the
+/// `reload_config::<T>` system when the file's modified time or length changes.
+///
+/// The file is polled with `std::fs::metadata` at most once per `interval` (half a second by
+/// default) rather than watched, so nothing extra is linked in. A reload swaps in a fresh
+/// `Arc<T>` whole, so a `snapshot` never sees half of an old value and half of a new one. A
+/// file that fails to parse leaves the previous value in place.
+pub struct Config<T> {
+    path: PathBuf,
+    value: Arc<T>,
+    stamp: Option<(SystemTime, u64)>,
+    interval: Duration,
+    checked: Option<Instant>,
+}
+
+impl<T: DeserializeOwned + Send + Sync + 'static> Resource for Config<T> {}
+
+impl<T> fmt::Debug for Config<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("type", &std::any::type_name::<T>())
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl<T: DeserializeOwned> Config<T> {
+    /// Reads and parses the file now.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let path = path.into();
+        let stamp = stamp_of(&path);
+        let value = Arc::new(read(&path)?);
+        Ok(Self {
+            path,
+            value,
+            stamp,
+            interval: Duration::from_millis(500),
+            checked: None,
+        })
+    }
+
+    /// Sets how often `poll` looks at the file; zero checks on every call.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// A shared handle to the current value, unaffected by later reloads.
+    pub fn snapshot(&self) -> Arc<T> {
+        Arc::clone(&self.value)
+    }
+
+    /// Reloads the file if it changed since the last load. Returns the new value, or `None`
+    /// if the file is unchanged or the interval hasn't passed yet.
+    pub fn poll(&mut self) -> Result<Option<Arc<T>>, ConfigError> {
+        let now = Instant::now();
+        if self
+            .checked
+            .is_some_and(|checked| now.duration_since(checked) < self.interval)
+        {
+            return Ok(None);
+        }
+        self.checked = Some(now);
+        self.reload_if_changed()
+    }
+
+    /// Like `poll`, ignoring the interval.
+    pub fn reload_if_changed(&mut self) -> Result<Option<Arc<T>>, ConfigError> {
+        let stamp = stamp_of(&self.path);
+        if stamp == self.stamp {
+            return Ok(None);
+        }
+        // Record the stamp first, so a broken file is reported once rather than every poll
+        self.stamp = stamp;
+        self.value = Arc::new(read(&self.path)?);
+        Ok(Some(self.snapshot()))
+    }
+}
+
+fn stamp_of(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+fn read<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let text = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_str(&text).map_err(|source| ConfigError::Parse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// System that polls the `Config<T>` resource, if any, and sends `ConfigChanged<T>` when
+/// it reloads. Load errors are printed and the old value kept.
+/// Add it with `schedule.add_system(reload_config::<GameSettings>)`.
+pub fn reload_config<T: DeserializeOwned + Send + Sync + 'static>(
+    world: &mut World,
+    resources: &Resources,
+) {
+    let Some(mut config) = resources.get_mut::<Config<T>>() else {
+        return;
+    };
+    match config.poll() {
+        Ok(Some(value)) => world.send_event(ConfigChanged { value }),
+        Ok(None) => {}
+        Err(err) => eprintln!("sparse_ecs: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventReader;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Settings {
+        volume: f32,
+        fullscreen: bool,
+    }
+
+    #[test]
+    fn edits_reload_and_send_an_event() {
+        let path =
+            std::env::temp_dir().join(format!("sparse_ecs_config_{}.json", std::process::id()));
+        fs::write(&path, r#"{ "volume": 0.5, "fullscreen": false }"#).unwrap();
+
+        let mut resources = Resources::new();
+        let config = Config::<Settings>::load(&path)
+            .unwrap()
+            .with_interval(Duration::ZERO);
+        let before = config.snapshot();
+        resources.add(config);
+        let mut world = World::new(4);
+        let mut reader = EventReader::<ConfigChanged<Settings>>::new();
+
+        reload_config::<Settings>(&mut world, &resources);
+        assert_eq!(reader.read(world.events_mut()).count(), 0);
+
+        // Lengths differ too, in case both writes land in the same mtime tick
+        fs::write(&path, r#"{"volume": 0.25, "fullscreen": true}"#).unwrap();
+        reload_config::<Settings>(&mut world, &resources);
+        let changed: Vec<_> = reader
+            .read(world.events_mut())
+            .map(|event| event.value.volume)
+            .collect();
+        assert_eq!(changed, vec![0.25]);
+        assert!(
+            resources
+                .get::<Config<Settings>>()
+                .unwrap()
+                .get()
+                .fullscreen
+        );
+        assert_eq!(before.volume, 0.5);
+
+        // A broken edit keeps the last good value
+        fs::write(&path, "{ \"volume\": ").unwrap();
+        let mut config = resources.get_mut::<Config<Settings>>().unwrap();
+        assert!(matches!(
+            config.reload_if_changed(),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert_eq!(config.get().volume, 0.25);
+        drop(config);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+// Debug console commands typed in at runtime
+
+use std::{collections::VecDeque, fmt, str::FromStr, sync::Arc};
+
+use parking_lot::Mutex;
+
+use crate::{resource::Resource, world::World};
+
+type Handler = Box<dyn FnMut(&mut World, &[&str]) -> Result<String, ConsoleError> + Send + Sync>;
+
+/// Why a console line didn't run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConsoleError {
+    /// The line was blank.
+    Empty,
+    UnknownCommand(String),
+    /// The arguments didn't parse; holds the command's usage.
+    BadArgs {
+        usage: &'static str,
+        reason: String,
+    },
+    /// The handler ran and reported a failure.
+    Failed(String),
+}
+
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsoleError::Empty => write!(f, "empty command"),
+            ConsoleError::UnknownCommand(name) => write!(f, "unknown command {name:?}"),
+            ConsoleError::BadArgs { usage, reason } => write!(f, "{reason}; usage: {usage}"),
+            ConsoleError::Failed(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConsoleError {}
+
+/// Arguments a command takes, parsed from the words after its name.
+/// Implemented for `()`, `Vec<T>` (any number of `T`) and tuples of up to four `FromStr`
+/// values, which need exactly that many words.
+pub trait FromArgs: Sized {
+    fn from_args(args: &[&str]) -> Result<Self, String>;
+}
+
+impl FromArgs for () {
+    fn from_args(args: &[&str]) -> Result<Self, String> {
+        if args.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("expected no arguments, got {}", args.len()))
+        }
+    }
+}
+
+impl<T: FromStr> FromArgs for Vec<T> {
+    fn from_args(args: &[&str]) -> Result<Self, String> {
+        args.iter().map(|arg| parse_arg(arg)).collect()
+    }
+}
+
+fn parse_arg<T: FromStr>(arg: &str) -> Result<T, String> {
+    arg.parse()
+        .map_err(|_| format!("can't read {arg:?} as {}", std::any::type_name::<T>()))
+}
+
+macro_rules! impl_from_args_tuple {
+    ($count:literal: $($ty:ident),+) => {
+        impl<$($ty: FromStr),+> FromArgs for ($($ty,)+) {
+            #[allow(non_snake_case)]
+            fn from_args(args: &[&str]) -> Result<Self, String> {
+                let [$($ty),+] = args else {
+                    return Err(format!("expected {} arguments, got {}", $count, args.len()));
+                };
+                Ok(($(parse_arg::<$ty>($ty)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_args_tuple!(1: A);
+impl_from_args_tuple!(2: A, B);
+impl_from_args_tuple!(3: A, B, C);
+impl_from_args_tuple!(4: A, B, C, D);
+
+/// Queue of console lines waiting for `Console::execute`. Clones share the queue, so keep
+/// one in `Resources` and hand others to a UI or network thread.
+#[derive(Clone, Default)]
+pub struct ConsoleInput {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Resource for ConsoleInput {}
+
+impl fmt::Debug for ConsoleInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConsoleInput")
+            .field("pending", &self.len())
+            .finish()
+    }
+}
+
+impl ConsoleInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a line, e.g. `"spawn_enemies 3"`.
+    pub fn push(&self, line: impl Into<String>) {
+        self.lines.lock().push_back(line.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.lock().is_empty()
+    }
+}
+
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    handler: Handler,
+}
+
+/// Registered console commands. A line is a command name followed by whitespace-separated
+/// arguments. Lines run at a sync point, via `execute` or `run`, with the world borrowed
+/// mutably.
+#[derive(Default)]
+pub struct Console {
+    commands: Vec<Command>,
+}
+
+impl fmt::Debug for Console {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.commands.iter().map(|command| command.name))
+            .finish()
+    }
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a command, replacing any with the same name. `usage` is shown by `help`
+    /// and in argument errors, e.g. `"spawn_enemies <count>"`. The handler's output or
+    /// error message is returned to whoever ran the line.
+    pub fn register<A, F>(&mut self, name: &'static str, usage: &'static str, mut handler: F)
+    where
+        A: FromArgs,
+        F: FnMut(&mut World, A) -> Result<String, String> + Send + Sync + 'static,
+    {
+        let handler: Handler = Box::new(move |world, args| {
+            let args =
+                A::from_args(args).map_err(|reason| ConsoleError::BadArgs { usage, reason })?;
+            handler(world, args).map_err(ConsoleError::Failed)
+        });
+        let command = Command {
+            name,
+            usage,
+            handler,
+        };
+        match self.commands.iter_mut().find(|known| known.name == name) {
+            Some(known) => *known = command,
+            None => self.commands.push(command),
+        }
+    }
+
+    /// Usage lines of every command, in the order registered.
+    pub fn help(&self) -> Vec<&'static str> {
+        self.commands.iter().map(|command| command.usage).collect()
+    }
+
+    /// Runs one line now.
+    pub fn run(&mut self, world: &mut World, line: &str) -> Result<String, ConsoleError> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&name, args)) = words.split_first() else {
+            return Err(ConsoleError::Empty);
+        };
+        let command = self
+            .commands
+            .iter_mut()
+            .find(|command| command.name == name)
+            .ok_or_else(|| ConsoleError::UnknownCommand(name.to_string()))?;
+        (command.handler)(world, args)
+    }
+
+    /// Runs every queued line in order, emptying the queue. Returns each line with its
+    /// result, for echoing back to the console.
+    pub fn execute(
+        &mut self,
+        world: &mut World,
+        input: &ConsoleInput,
+    ) -> Vec<(String, Result<String, ConsoleError>)> {
+        let lines = std::mem::take(&mut *input.lines.lock());
+        lines
+            .into_iter()
+            .map(|line| {
+                let result = self.run(world, &line);
+                (line, result)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Component;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    fn console() -> Console {
+        let mut console = Console::new();
+        console.register(
+            "spawn",
+            "spawn <count> <health>",
+            |world, (count, hp): (usize, u32)| {
+                for _ in 0..count {
+                    world.spawn_entity().with(Health(hp)).build();
+                }
+                Ok(format!("spawned {count}"))
+            },
+        );
+        console.register("count", "count", |world, ()| {
+            Ok(world.entity_count().to_string())
+        });
+        console.register("fail", "fail <why...>", |_, why: Vec<String>| {
+            Err(why.join(" "))
+        });
+        console
+    }
+
+    #[test]
+    fn queued_lines_run_at_execute() {
+        let mut world = World::new(8);
+        let mut console = console();
+        let input = ConsoleInput::new();
+        let from_ui = input.clone();
+        std::thread::spawn(move || from_ui.push("spawn 2 10"))
+            .join()
+            .unwrap();
+        input.push("count");
+        assert_eq!(world.entity_count(), 0);
+
+        let results = console.execute(&mut world, &input);
+        assert!(input.is_empty());
+        assert_eq!(
+            results,
+            vec![
+                ("spawn 2 10".to_string(), Ok("spawned 2".to_string())),
+                ("count".to_string(), Ok("2".to_string())),
+            ]
+        );
+        assert_eq!(
+            console.help(),
+            vec!["spawn <count> <health>", "count", "fail <why...>"]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_problem() {
+        let mut world = World::new(8);
+        let mut console = console();
+        assert_eq!(console.run(&mut world, "  "), Err(ConsoleError::Empty));
+        assert_eq!(
+            console.run(&mut world, "nope"),
+            Err(ConsoleError::UnknownCommand("nope".to_string()))
+        );
+        let err = console.run(&mut world, "spawn two 10").unwrap_err();
+        assert!(matches!(
+            err,
+            ConsoleError::BadArgs {
+                usage: "spawn <count> <health>",
+                ..
+            }
+        ));
+        assert!(console.run(&mut world, "count extra").is_err());
+        assert_eq!(
+            console.run(&mut world, "fail out of  mana"),
+            Err(ConsoleError::Failed("out of mana".to_string()))
+        );
+    }
+}
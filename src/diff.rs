@@ -0,0 +1,268 @@
+// Per-field component diffs, so replication only sends the fields that changed
+
+use std::collections::HashMap;
+
+use crate::{
+    component::Entity,
+    world::{Component, World},
+};
+
+/// Which fields of a component changed: bit `i` is `DiffSerialize::FIELDS[i]`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FieldMask(pub u64);
+
+impl FieldMask {
+    pub const NONE: FieldMask = FieldMask(0);
+
+    /// The first `count` fields.
+    pub fn all(count: usize) -> Self {
+        match count {
+            64.. => FieldMask(u64::MAX),
+            _ => FieldMask((1 << count) - 1),
+        }
+    }
+
+    pub fn insert(&mut self, field: u32) {
+        self.0 |= 1 << field;
+    }
+
+    pub fn contains(self, field: u32) -> bool {
+        self.0 & (1 << field) != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Number of fields set.
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Indices of the fields set, lowest first.
+    pub fn iter(self) -> impl Iterator<Item = u32> {
+        (0..64).filter(move |&field| self.contains(field))
+    }
+}
+
+/// A component whose fields can be compared and copied one at a time, so a large
+/// component (say a 20-field `PlayerState`) can be replicated by sending only the fields
+/// that changed. Derive it with `#[derive(DiffSerialize)]` on a struct with up to 64 named
+/// fields, each `PartialEq`.
+pub trait DiffSerialize: Component {
+    /// Field names, in declaration order.
+    const FIELDS: &'static [&'static str];
+
+    /// The fields where `self` differs from `base`.
+    fn diff(&self, base: &Self) -> FieldMask;
+
+    /// Copies the fields in `mask` from `from`, leaving the rest as they are.
+    fn apply_fields(&mut self, from: &Self, mask: FieldMask);
+}
+
+/// One entity's changed fields. `value` holds the whole current component; only the
+/// fields in `mask` need to go over the wire.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FieldDiff<T> {
+    pub entity: Entity,
+    pub mask: FieldMask,
+    pub value: T,
+}
+
+impl<T: DiffSerialize> FieldDiff<T> {
+    /// Copies the changed fields onto the receiving side's copy.
+    pub fn apply(&self, target: &mut T) {
+        target.apply_fields(&self.value, self.mask);
+    }
+
+    /// Names of the changed fields.
+    pub fn fields(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.mask
+            .iter()
+            .filter_map(|field| T::FIELDS.get(field as usize).copied())
+    }
+}
+
+/// What changed in one component type since the last `DiffTracker::collect`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diffs<T> {
+    /// Entities that gained the component (every field set) or changed some fields.
+    pub changed: Vec<FieldDiff<T>>,
+    /// Entities that lost the component or were despawned.
+    pub removed: Vec<Entity>,
+}
+
+/// Remembers the last value sent for each entity's `T`, to diff the next frame against.
+/// Unlike `sync::Mirror`, which goes by change ticks, it compares against its own copies,
+/// so a write that leaves a field as it was sends nothing. Keep one per connection, or one
+/// shared by every client that has received the same frames.
+pub struct DiffTracker<T> {
+    sent: HashMap<Entity, T>,
+}
+
+impl<T> Default for DiffTracker<T> {
+    fn default() -> Self {
+        Self {
+            sent: HashMap::new(),
+        }
+    }
+}
+
+impl<T: DiffSerialize> DiffTracker<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Diffs every `T` in the world against what was last collected and remembers the new
+    /// values. Entities seen for the first time report every field.
+    pub fn collect(&mut self, world: &World) -> Diffs<T> {
+        let mut changed = Vec::new();
+        for (entity, value) in world.iter::<T>() {
+            let mask = match self.sent.get_mut(&entity) {
+                Some(sent) => {
+                    let mask = value.diff(sent);
+                    *sent = *value;
+                    mask
+                }
+                None => {
+                    self.sent.insert(entity, *value);
+                    FieldMask::all(T::FIELDS.len())
+                }
+            };
+            if !mask.is_empty() {
+                changed.push(FieldDiff {
+                    entity,
+                    mask,
+                    value: *value,
+                });
+            }
+        }
+        let mut removed = Vec::new();
+        self.sent.retain(|&entity, _| {
+            let kept = world.has::<T>(entity);
+            if !kept {
+                removed.push(entity);
+            }
+            kept
+        });
+        Diffs { changed, removed }
+    }
+
+    /// Forgets what was sent, so the next `collect` reports every field again, e.g. when
+    /// a client reconnects.
+    pub fn reset(&mut self) {
+        self.sent.clear();
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: DiffSerialize + serde::Serialize> FieldDiff<T> {
+    /// The changed fields as a JSON object, e.g. `{"hp": 90}`. Keys are the Rust field
+    /// names, so serde renames on the component aren't supported.
+    pub fn to_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        let Ok(serde_json::Value::Object(mut all)) = serde_json::to_value(self.value) else {
+            return serde_json::Map::new();
+        };
+        self.fields()
+            .filter_map(|name| Some((name.to_string(), all.remove(name)?)))
+            .collect()
+    }
+}
+
+/// Applies fields from `FieldDiff::to_json` onto the receiving side's copy. Keys that
+/// aren't fields of `T` are ignored.
+#[cfg(feature = "json")]
+pub fn apply_json<T>(
+    target: &mut T,
+    fields: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), serde_json::Error>
+where
+    T: DiffSerialize + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let mut value = serde_json::to_value(*target)?;
+    if let serde_json::Value::Object(current) = &mut value {
+        for (name, field) in fields {
+            if T::FIELDS.contains(&name.as_str()) {
+                current.insert(name.clone(), field.clone());
+            }
+        }
+    }
+    *target = serde_json::from_value(value)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffSerialize;
+
+    #[derive(Copy, Clone, Debug, PartialEq, DiffSerialize)]
+    #[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+    struct PlayerState {
+        hp: u32,
+        ammo: u16,
+        pos: (f32, f32),
+        crouched: bool,
+    }
+    impl Component for PlayerState {}
+
+    const SPAWNED: PlayerState = PlayerState {
+        hp: 100,
+        ammo: 30,
+        pos: (0.0, 0.0),
+        crouched: false,
+    };
+
+    #[test]
+    fn only_changed_fields_are_reported() {
+        let mut world = World::new(4);
+        let player = world.spawn_entity().with(SPAWNED).build();
+        let mut tracker = DiffTracker::<PlayerState>::new();
+
+        let first = tracker.collect(&world);
+        assert_eq!(first.changed[0].mask, FieldMask::all(4));
+        assert!(tracker.collect(&world).changed.is_empty());
+
+        let state = world
+            .get_mut::<PlayerState>()
+            .unwrap()
+            .get_mut(player)
+            .unwrap();
+        state.hp = 90;
+        state.crouched = true;
+        let diffs = tracker.collect(&world);
+        let [diff] = diffs.changed.as_slice() else {
+            panic!("one player changed");
+        };
+        assert_eq!(diff.fields().collect::<Vec<_>>(), vec!["hp", "crouched"]);
+
+        let mut remote = SPAWNED;
+        remote.ammo = 5;
+        diff.apply(&mut remote);
+        assert_eq!((remote.hp, remote.ammo, remote.crouched), (90, 5, true));
+
+        world.despawn(player);
+        assert_eq!(tracker.collect(&world).removed, vec![player]);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn json_diffs_carry_only_changed_fields() {
+        let mut moved = SPAWNED;
+        moved.pos = (1.5, 2.0);
+        let diff = FieldDiff {
+            entity: Entity(0),
+            mask: moved.diff(&SPAWNED),
+            value: moved,
+        };
+        let fields = diff.to_json();
+        assert_eq!(
+            serde_json::Value::Object(fields.clone()),
+            serde_json::json!({ "pos": [1.5, 2.0] })
+        );
+
+        let mut remote = SPAWNED;
+        apply_json(&mut remote, &fields).unwrap();
+        assert_eq!(remote, moved);
+    }
+}
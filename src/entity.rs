@@ -0,0 +1,134 @@
+// Per-entity views over the world
+
+use crate::{
+    bundle::Bundle,
+    component::Entity,
+    world::{Component, World},
+};
+
+/// Read access to one live entity, from `World::entity`.
+#[derive(Copy, Clone)]
+pub struct EntityRef<'w> {
+    world: &'w World,
+    entity: Entity,
+}
+
+impl<'w> EntityRef<'w> {
+    pub(crate) fn new(world: &'w World, entity: Entity) -> Self {
+        Self { world, entity }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&'w T> {
+        self.world.component::<T>(self.entity)
+    }
+
+    pub fn has<T: Component>(&self) -> bool {
+        self.world.has::<T>(self.entity)
+    }
+
+    /// Iterates the entity's tags, in no particular order.
+    pub fn tags(&self) -> impl Iterator<Item = &'static str> + 'w {
+        self.world.tags.tags_of(self.entity)
+    }
+}
+
+/// Read-write access to one live entity, from `World::entity_mut`.
+pub struct EntityMut<'w> {
+    world: &'w mut World,
+    entity: Entity,
+}
+
+impl<'w> EntityMut<'w> {
+    pub(crate) fn new(world: &'w mut World, entity: Entity) -> Self {
+        Self { world, entity }
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.world.component::<T>(self.entity)
+    }
+
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.world.component_mut::<T>(self.entity)
+    }
+
+    pub fn has<T: Component>(&self) -> bool {
+        self.world.has::<T>(self.entity)
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.world.tags.tags_of(self.entity)
+    }
+
+    pub fn insert<T: Component>(&mut self, value: T) -> &mut Self {
+        self.world.insert(self.entity, value);
+        self
+    }
+
+    pub fn insert_bundle(&mut self, bundle: impl Bundle) -> &mut Self {
+        self.world.insert_bundle(self.entity, bundle);
+        self
+    }
+
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.world.remove::<T>(self.entity)
+    }
+
+    pub fn add_tag(&mut self, tag: &'static str) -> &mut Self {
+        self.world.tags.add_tag(tag, self.entity);
+        self
+    }
+
+    pub fn remove_tag(&mut self, tag: &'static str) -> &mut Self {
+        self.world.tags.remove_tag(tag, &self.entity);
+        self
+    }
+
+    pub fn despawn(self) {
+        self.world.despawn(self.entity);
+    }
+
+    /// Downgrades to a read-only view.
+    pub fn as_ref(&self) -> EntityRef<'_> {
+        EntityRef::new(self.world, self.entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Shield;
+    impl Component for Shield {}
+
+    #[test]
+    fn views_read_and_write() {
+        let mut world = World::new(4);
+        let e = world.spawn();
+
+        let mut view = world.entity_mut(e).unwrap();
+        view.insert(Health(3)).insert(Shield).add_tag("player");
+        view.get_mut::<Health>().unwrap().0 += 1;
+        assert_eq!(view.remove::<Shield>(), Some(Shield));
+
+        let view = world.entity(e).unwrap();
+        assert_eq!(view.get::<Health>(), Some(&Health(4)));
+        assert!(!view.has::<Shield>());
+        assert_eq!(view.tags().collect::<Vec<_>>(), vec!["player"]);
+
+        world.entity_mut(e).unwrap().despawn();
+        assert!(world.entity(e).is_none());
+    }
+}
@@ -0,0 +1,226 @@
+// Double-buffered gameplay events
+
+use std::{any::Any, marker::PhantomData};
+
+use crate::resource::Resource;
+
+/// Queue of events of one type, kept for two updates so every system sees them once
+/// whether it runs before or after the sender. Call `update` once per frame;
+/// `World::update_events` does this for the world's queues and `Schedule::run` calls it.
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    /// Id of `previous[0]`. Ids count every event ever sent, so readers can keep a cursor.
+    previous_start: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Events<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Events")
+            .field("type", &std::any::type_name::<T>())
+            .field("previous", &self.previous.len())
+            .field("current", &self.current.len())
+            .finish()
+    }
+}
+
+impl<T: Send + Sync + 'static> Resource for Events<T> {}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.current.extend(events);
+    }
+
+    /// A writer borrowing this queue, for handing to code that should only send.
+    pub fn writer(&mut self) -> EventWriter<'_, T> {
+        EventWriter { events: self }
+    }
+
+    /// A reader that skips everything already sent, seeing only later events.
+    pub fn reader(&self) -> EventReader<T> {
+        EventReader {
+            next: self.end(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Swaps buffers: events from two updates ago are dropped, this update's are kept
+    /// for one more.
+    pub fn update(&mut self) {
+        self.previous_start += self.previous.len();
+        self.previous.clear();
+        std::mem::swap(&mut self.previous, &mut self.current);
+    }
+
+    /// Drops every held event. Readers carry on from the next one sent.
+    pub fn clear(&mut self) {
+        self.previous_start = self.end();
+        self.previous.clear();
+        self.current.clear();
+    }
+
+    /// Number of events held across both buffers.
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every held event, oldest first, regardless of readers.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(&self.current)
+    }
+
+    /// Id the next sent event gets.
+    fn end(&self) -> usize {
+        self.previous_start + self.len()
+    }
+}
+
+/// Sends events into a borrowed `Events` queue.
+pub struct EventWriter<'a, T> {
+    events: &'a mut Events<T>,
+}
+
+impl<T> EventWriter<'_, T> {
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.send_batch(events);
+    }
+}
+
+/// A cursor into an `Events` queue, owned by the consuming system so each reader sees
+/// every event once. Events dropped by `update` before being read are missed.
+pub struct EventReader<T> {
+    next: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventReader<T> {
+    /// Starts at the oldest held event.
+    fn default() -> Self {
+        Self {
+            next: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for EventReader<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventReader")
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+impl<T> EventReader<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events sent since this reader last read, oldest first.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> + use<'a, T> {
+        let skip = self.next.saturating_sub(events.previous_start);
+        self.next = events.end();
+        events.iter().skip(skip)
+    }
+
+    /// Returns true if `read` would return nothing.
+    pub fn is_caught_up(&self, events: &Events<T>) -> bool {
+        self.next >= events.end()
+    }
+}
+
+/// Type-erased queue so the world can update every event type together.
+pub(crate) trait AnyEvents: Send + Sync {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Send + Sync + 'static> AnyEvents for Events<T> {
+    fn update(&mut self) {
+        Events::update(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    #[derive(Debug, PartialEq)]
+    struct Damage(u32);
+
+    #[test]
+    fn readers_see_each_event_once_for_two_updates() {
+        let mut events = Events::new();
+        let mut early = EventReader::new();
+        events.writer().send(Damage(1));
+
+        let mut late = events.reader();
+        events.send(Damage(2));
+        assert_eq!(late.read(&events).collect::<Vec<_>>(), vec![&Damage(2)]);
+        events.update();
+
+        // Still readable one update later
+        events.send(Damage(3));
+        assert_eq!(
+            early.read(&events).collect::<Vec<_>>(),
+            vec![&Damage(1), &Damage(2), &Damage(3)]
+        );
+        assert_eq!(late.read(&events).collect::<Vec<_>>(), vec![&Damage(3)]);
+        assert!(early.is_caught_up(&events));
+
+        // Dropped after the second update
+        events.update();
+        events.update();
+        assert!(events.is_empty());
+        events.send(Damage(4));
+        assert_eq!(early.read(&events).collect::<Vec<_>>(), vec![&Damage(4)]);
+    }
+
+    #[test]
+    fn world_updates_its_queues() {
+        let mut world = World::new(4);
+        let mut reader = EventReader::<Damage>::new();
+        world.send_event(Damage(7));
+        world.update_events();
+        assert_eq!(reader.read(world.events::<Damage>().unwrap()).count(), 1);
+        world.update_events();
+        assert!(world.events::<Damage>().unwrap().is_empty());
+        assert!(world.events::<u8>().is_none());
+    }
+}
@@ -0,0 +1,243 @@
+// Component columns written out as CSV, for offline analysis of simulation runs
+
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use serde_json::Value;
+
+use crate::world::World;
+
+/// Why an export couldn't be set up.
+#[derive(Debug)]
+pub enum ExportError {
+    /// No component is registered with this name.
+    UnknownComponent(String),
+    /// The component is registered, but not with `register_json`.
+    NotJson(&'static str),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::UnknownComponent(name) => write!(f, "unknown component {name:?}"),
+            ExportError::NotJson(name) => {
+                write!(f, "component {name:?} isn't registered with register_json")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// Writes selected components as CSV, one row per entity per `record` call, with a `tick`
+/// and `entity` column followed by one column per component field.
+///
+/// Components are read through the registry's JSON support, so each one must be registered
+/// with `register_json`. Struct fields become `Name.field` columns (nested ones
+/// `Name.field.inner`) and plain values a single `Name` column. The columns are fixed by the
+/// first `record`, from the first entity carrying each component, so record after the
+/// world is populated; an entity without a component leaves its cells empty.
+pub struct CsvExport<W: Write> {
+    out: W,
+    components: Vec<&'static str>,
+    columns: Option<Vec<String>>,
+}
+
+impl<W: Write> CsvExport<W> {
+    /// Exports the named components, in the order given.
+    pub fn new(out: W, world: &World, components: &[&str]) -> Result<Self, ExportError> {
+        let components = components
+            .iter()
+            .map(|&name| {
+                let info = world
+                    .registry
+                    .get(name)
+                    .ok_or_else(|| ExportError::UnknownComponent(name.to_string()))?;
+                if !world.registry.has_json(name) {
+                    return Err(ExportError::NotJson(info.name));
+                }
+                Ok(info.name)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            out,
+            components,
+            columns: None,
+        })
+    }
+
+    /// The component field columns, once the header is written.
+    pub fn columns(&self) -> Option<&[String]> {
+        self.columns.as_deref()
+    }
+
+    /// Appends a row for every living entity with at least one of the components, writing
+    /// the header first on the first call. Returns the number of rows written.
+    pub fn record(&mut self, world: &World, tick: u64) -> io::Result<usize> {
+        if self.columns.is_none() {
+            let columns = self.discover(world);
+            write!(self.out, "tick,entity")?;
+            for column in &columns {
+                write!(self.out, ",")?;
+                write_cell(&mut self.out, column)?;
+            }
+            writeln!(self.out)?;
+            self.columns = Some(columns);
+        }
+        let columns = self.columns.as_deref().unwrap_or_default();
+
+        let mut rows = 0;
+        let mut cells: Vec<(String, Value)> = Vec::new();
+        for entity in world.entities() {
+            cells.clear();
+            for &name in &self.components {
+                if let Some(value) = world.registry.read_json(world, name, entity) {
+                    flatten(name.to_string(), value, &mut cells);
+                }
+            }
+            if cells.is_empty() {
+                continue;
+            }
+            write!(self.out, "{tick},{}", entity.0)?;
+            for column in columns {
+                write!(self.out, ",")?;
+                match cells.iter().find(|(path, _)| path == column) {
+                    Some((_, Value::String(text))) => write_cell(&mut self.out, text)?,
+                    Some((_, Value::Null)) | None => {}
+                    Some((_, value)) => write!(self.out, "{value}")?,
+                }
+            }
+            writeln!(self.out)?;
+            rows += 1;
+        }
+        self.out.flush()?;
+        Ok(rows)
+    }
+
+    /// Flattens one sample of each component into column names.
+    fn discover(&self, world: &World) -> Vec<String> {
+        let mut columns = Vec::new();
+        for &name in &self.components {
+            let sample = world
+                .entities()
+                .find_map(|entity| world.registry.read_json(world, name, entity));
+            match sample {
+                Some(value) => {
+                    let mut cells = Vec::new();
+                    flatten(name.to_string(), value, &mut cells);
+                    columns.extend(cells.into_iter().map(|(path, _)| path));
+                }
+                None => columns.push(name.to_string()),
+            }
+        }
+        columns
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Splits objects and arrays into `path.key` leaves.
+fn flatten(path: String, value: Value, out: &mut Vec<(String, Value)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                flatten(format!("{path}.{key}"), value, out);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, value) in items.into_iter().enumerate() {
+                flatten(format!("{path}.{i}"), value, out);
+            }
+        }
+        value => out.push((path, value)),
+    }
+}
+
+/// Writes text, quoted if it holds a comma, quote or line break.
+fn write_cell(out: &mut impl Write, text: &str) -> io::Result<()> {
+    if text.contains([',', '"', '\n', '\r']) {
+        write!(out, "\"{}\"", text.replace('"', "\"\""))
+    } else {
+        write!(out, "{text}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Component;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Copy, Clone, Debug)]
+    struct Frozen;
+    impl Component for Frozen {}
+
+    #[test]
+    fn rows_per_entity_per_tick() {
+        let mut world = World::new(8);
+        world.registry.register_json::<Position>("Position");
+        world.registry.register_json::<Health>("Health");
+        world.registry.register::<Frozen>("Frozen");
+        let a = world
+            .spawn_entity()
+            .with(Position { x: 1.0, y: 2.0 })
+            .with(Health(10))
+            .build();
+        world
+            .spawn_entity()
+            .with(Position { x: 3.5, y: 0.0 })
+            .build();
+        world.spawn();
+
+        assert!(matches!(
+            CsvExport::new(Vec::new(), &world, &["Velocity"]),
+            Err(ExportError::UnknownComponent(_))
+        ));
+        assert!(matches!(
+            CsvExport::new(Vec::new(), &world, &["Frozen"]),
+            Err(ExportError::NotJson("Frozen"))
+        ));
+
+        let mut export = CsvExport::new(Vec::new(), &world, &["Position", "Health"]).unwrap();
+        assert_eq!(export.record(&world, 0).unwrap(), 2);
+        world.get_mut::<Health>().unwrap().set(Health(7), a);
+        assert_eq!(export.record(&world, 1).unwrap(), 2);
+        assert_eq!(
+            export.columns().unwrap(),
+            ["Position.x", "Position.y", "Health"]
+        );
+
+        let csv = String::from_utf8(export.into_inner()).unwrap();
+        assert_eq!(
+            csv,
+            "tick,entity,Position.x,Position.y,Health\n\
+             0,0,1.0,2.0,10\n\
+             0,1,3.5,0.0,\n\
+             1,0,1.0,2.0,7\n\
+             1,1,3.5,0.0,\n"
+        );
+    }
+
+    #[test]
+    fn cells_with_commas_are_quoted() {
+        let mut out = Vec::new();
+        write_cell(&mut out, "plain").unwrap();
+        write_cell(&mut out, "a,\"b\"").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "plain\"a,\"\"b\"\"\"");
+    }
+}
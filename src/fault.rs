@@ -0,0 +1,48 @@
+// Handling of recoverable misuse, like despawning an entity twice
+
+use std::fmt;
+
+use crate::component::Entity;
+
+/// How the world reacts to a recoverable fault.
+/// Defaults to `Panic` in debug builds and `LogAndSkip` in release builds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FaultPolicy {
+    Panic,
+    /// Print the fault to stderr and skip the operation.
+    LogAndSkip,
+    /// Skip the operation and keep the fault for `World::take_faults`.
+    CollectErrors,
+}
+
+impl Default for FaultPolicy {
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            FaultPolicy::Panic
+        } else {
+            FaultPolicy::LogAndSkip
+        }
+    }
+}
+
+/// A recoverable fault. The operation that hit it was skipped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// `op` was given an entity that is despawned, stale, or was never spawned.
+    DeadEntity { op: &'static str, entity: Entity },
+    /// A despawn was refused because `holder` links to `entity` under `LinkPolicy::Restrict`.
+    Linked { entity: Entity, holder: Entity },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::DeadEntity { op, entity } => write!(f, "{op}: {entity:?} is not alive"),
+            Fault::Linked { entity, holder } => {
+                write!(f, "despawn: {entity:?} is still linked from {holder:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
@@ -0,0 +1,184 @@
+// Ring buffer of past world states, for rollback and replay scrubbing
+
+use std::collections::VecDeque;
+
+use crate::world::World;
+
+/// The last few ticks of a world, recorded with `record` once per tick.
+///
+/// Snapshots are `World` clones, so recording is cheap: storages are shared until the live
+/// world next writes to them, and only those storages are copied. The oldest tick is
+/// dropped once `capacity` ticks are held.
+///
+/// Debug tooling can scrub through the recording read-only with `view_at`, or by moving a
+/// cursor with `step_back`/`step_forward`, while the live world carries on; `rollback_to`
+/// is the only call that touches the live world.
+#[derive(Debug)]
+pub struct History {
+    capacity: usize,
+    ticks: VecDeque<(u64, World)>,
+    cursor: Option<u64>,
+}
+
+impl History {
+    /// Keeps up to `capacity` ticks. Panics if it's zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "history capacity must be at least 1");
+        Self {
+            capacity,
+            ticks: VecDeque::with_capacity(capacity),
+            cursor: None,
+        }
+    }
+
+    /// Snapshots the world as of `tick`. Recording a tick that's already held replaces it.
+    /// Panics if `tick` is older than the newest recorded tick.
+    pub fn record(&mut self, tick: u64, world: &World) {
+        if let Some(&(newest, _)) = self.ticks.back() {
+            assert!(
+                tick >= newest,
+                "tick {tick} recorded after newer tick {newest}"
+            );
+            if tick == newest {
+                self.ticks.pop_back();
+            }
+        }
+        if self.ticks.len() == self.capacity {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back((tick, world.clone()));
+    }
+
+    /// The world as it was at `tick`, if that tick is still held.
+    pub fn view_at(&self, tick: u64) -> Option<&World> {
+        let idx = self.position(tick)?;
+        Some(&self.ticks[idx].1)
+    }
+
+    /// Recorded ticks, oldest first.
+    pub fn ticks(&self) -> impl DoubleEndedIterator<Item = u64> + '_ {
+        self.ticks.iter().map(|(tick, _)| *tick)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ticks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    /// The tick the scrubber is on: the newest one, unless moved. Moves to the oldest
+    /// remaining tick if the one it was on has been dropped.
+    pub fn cursor(&self) -> Option<u64> {
+        let oldest = self.ticks.front()?.0;
+        match self.cursor {
+            Some(tick) if self.position(tick).is_some() => Some(tick),
+            Some(tick) if tick < oldest => Some(oldest),
+            _ => self.ticks.back().map(|(tick, _)| *tick),
+        }
+    }
+
+    /// Moves the scrubber to `tick`. Returns false, leaving it where it was, if the tick
+    /// isn't held.
+    pub fn seek(&mut self, tick: u64) -> bool {
+        let held = self.position(tick).is_some();
+        if held {
+            self.cursor = Some(tick);
+        }
+        held
+    }
+
+    /// Moves the scrubber one recorded tick back and returns that tick's world, or `None`
+    /// at the oldest tick.
+    pub fn step_back(&mut self) -> Option<(u64, &World)> {
+        let idx = self.position(self.cursor()?)?.checked_sub(1)?;
+        self.step_to(idx)
+    }
+
+    /// Moves the scrubber one recorded tick forward, or returns `None` at the newest tick.
+    pub fn step_forward(&mut self) -> Option<(u64, &World)> {
+        let idx = self.position(self.cursor()?)? + 1;
+        if idx == self.ticks.len() {
+            return None;
+        }
+        self.step_to(idx)
+    }
+
+    /// Returns the scrubber to the newest tick.
+    pub fn follow_latest(&mut self) {
+        self.cursor = None;
+    }
+
+    /// Replaces the live world with the one recorded at `tick`, dropping newer ticks so
+    /// recording can resume from there. Returns false, leaving everything as it was, if
+    /// the tick isn't held. Event queues start empty, as with any `World` clone.
+    pub fn rollback_to(&mut self, tick: u64, world: &mut World) -> bool {
+        let Some(idx) = self.position(tick) else {
+            return false;
+        };
+        self.ticks.truncate(idx + 1);
+        *world = self.ticks[idx].1.clone();
+        self.cursor = None;
+        true
+    }
+
+    fn step_to(&mut self, idx: usize) -> Option<(u64, &World)> {
+        let (tick, world) = &self.ticks[idx];
+        self.cursor = Some(*tick);
+        Some((*tick, world))
+    }
+
+    fn position(&self, tick: u64) -> Option<usize> {
+        self.ticks
+            .binary_search_by_key(&tick, |(recorded, _)| *recorded)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Component;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[test]
+    fn scrubbing_leaves_the_live_world_alone() {
+        let mut world = World::new(4);
+        let ball = world.spawn_entity().with(Position(0)).build();
+        let mut history = History::new(3);
+        for tick in 1..=4 {
+            world
+                .get_mut::<Position>()
+                .unwrap()
+                .set(Position(tick), ball);
+            history.record(tick as u64, &world);
+        }
+        assert_eq!(history.ticks().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert!(history.view_at(1).is_none());
+        let at = |view: &World| view.get::<Position>().unwrap().get(ball).copied();
+        assert_eq!(at(history.view_at(2).unwrap()), Some(Position(2)));
+
+        assert_eq!(history.cursor(), Some(4));
+        let (tick, view) = history.step_back().unwrap();
+        assert_eq!((tick, at(view)), (3, Some(Position(3))));
+        history.step_back();
+        assert!(history.step_back().is_none());
+        assert_eq!(history.cursor(), Some(2));
+        assert_eq!(history.step_forward().unwrap().0, 3);
+        assert_eq!(at(&world), Some(Position(4)));
+
+        // Dropping the scrubbed-to tick moves the cursor to the oldest one left
+        history.record(5, &world);
+        history.record(6, &world);
+        assert_eq!(history.cursor(), Some(4));
+
+        assert!(history.rollback_to(5, &mut world));
+        assert_eq!(history.ticks().collect::<Vec<_>>(), vec![4, 5]);
+        assert_eq!(at(&world), Some(Position(4)));
+        assert!(!history.rollback_to(1, &mut world));
+    }
+}
@@ -0,0 +1,578 @@
+// Input resources for the ECS
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::resource::{Resource, Resources};
+
+/// Platform key code. Backend agnostic, so map your windowing library's key codes
+/// (e.g. winit's `KeyCode as u32`) into it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KeyCode(pub u32);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+/// Raw input event fed to `ingest`.
+/// Mirrors the subset of windowing events that nearly every game cares about.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    Key {
+        code: KeyCode,
+        pressed: bool,
+    },
+    MouseMoved {
+        x: f32,
+        y: f32,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    MouseWheel {
+        dx: f32,
+        dy: f32,
+    },
+    GamepadButton {
+        pad: usize,
+        button: u32,
+        pressed: bool,
+    },
+    GamepadAxis {
+        pad: usize,
+        axis: u32,
+        value: f32,
+    },
+    GamepadDisconnected {
+        pad: usize,
+    },
+}
+
+/// Pressed state for a set of buttons, tracking per-frame transitions.
+#[derive(Debug, Clone)]
+pub struct Buttons<K: Copy + Eq + Hash> {
+    pressed: HashSet<K>,
+    just_pressed: HashSet<K>,
+    just_released: HashSet<K>,
+}
+
+impl<K: Copy + Eq + Hash> Default for Buttons<K> {
+    fn default() -> Self {
+        Self {
+            pressed: HashSet::new(),
+            just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> Buttons<K> {
+    /// Marks the button as pressed. Repeated presses (key repeat) don't count as new.
+    pub fn press(&mut self, button: K) {
+        if self.pressed.insert(button) {
+            self.just_pressed.insert(button);
+        }
+    }
+
+    /// Marks the button as released.
+    pub fn release(&mut self, button: K) {
+        if self.pressed.remove(&button) {
+            self.just_released.insert(button);
+        }
+    }
+
+    /// Returns true while the button is held down.
+    pub fn pressed(&self, button: K) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// Returns true if the button went down this frame.
+    pub fn just_pressed(&self, button: K) -> bool {
+        self.just_pressed.contains(&button)
+    }
+
+    /// Returns true if the button went up this frame.
+    pub fn just_released(&self, button: K) -> bool {
+        self.just_released.contains(&button)
+    }
+
+    /// Iterates all currently held buttons.
+    pub fn iter_pressed(&self) -> impl Iterator<Item = K> + '_ {
+        self.pressed.iter().copied()
+    }
+
+    /// Clears the per-frame transitions, keeping held state.
+    pub fn clear_frame(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+    }
+}
+
+/// Keyboard state resource.
+#[derive(Debug, Clone, Default)]
+pub struct Keyboard {
+    pub keys: Buttons<KeyCode>,
+}
+
+impl Resource for Keyboard {}
+
+impl Keyboard {
+    pub fn pressed(&self, key: KeyCode) -> bool {
+        self.keys.pressed(key)
+    }
+
+    pub fn just_pressed(&self, key: KeyCode) -> bool {
+        self.keys.just_pressed(key)
+    }
+
+    pub fn just_released(&self, key: KeyCode) -> bool {
+        self.keys.just_released(key)
+    }
+}
+
+/// Mouse state resource. `delta` and `wheel` accumulate over a frame.
+#[derive(Debug, Clone, Default)]
+pub struct Mouse {
+    pub buttons: Buttons<MouseButton>,
+    pub position: (f32, f32),
+    pub delta: (f32, f32),
+    pub wheel: (f32, f32),
+    has_position: bool,
+}
+
+impl Resource for Mouse {}
+
+impl Mouse {
+    pub fn pressed(&self, button: MouseButton) -> bool {
+        self.buttons.pressed(button)
+    }
+
+    pub fn just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons.just_pressed(button)
+    }
+
+    pub fn just_released(&self, button: MouseButton) -> bool {
+        self.buttons.just_released(button)
+    }
+}
+
+/// A single connected gamepad.
+#[derive(Debug, Clone, Default)]
+pub struct Gamepad {
+    pub buttons: Buttons<u32>,
+    pub axes: HashMap<u32, f32>,
+}
+
+impl Gamepad {
+    /// Returns the axis value, or 0 if it has never been reported.
+    pub fn axis(&self, axis: u32) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or(0.0)
+    }
+}
+
+/// Gamepad state resource, keyed by pad ID.
+#[derive(Debug, Clone, Default)]
+pub struct GamepadState {
+    pub pads: HashMap<usize, Gamepad>,
+}
+
+impl Resource for GamepadState {}
+
+impl GamepadState {
+    pub fn get(&self, pad: usize) -> Option<&Gamepad> {
+        self.pads.get(&pad)
+    }
+}
+
+/// A button an action can be bound to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// The button on any connected pad.
+    GamepadButton(u32),
+}
+
+/// An input an axis action reads a value from.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AxisBinding {
+    /// -1 while `negative` is held, +1 while `positive` is, 0 for both or neither.
+    Keys {
+        negative: KeyCode,
+        positive: KeyCode,
+    },
+    /// The axis on any connected pad, ignoring values within `dead_zone` of 0.
+    GamepadAxis { axis: u32, dead_zone: f32 },
+    /// Vertical wheel movement this frame.
+    MouseWheel,
+}
+
+/// Named actions bound to raw inputs, e.g. `"jump"` to space and the A button, so systems
+/// check `actions.just_pressed("jump")` instead of every key and pad button. Bindings can
+/// be changed at runtime for rebinding menus.
+///
+/// Recomputed from `Keyboard`, `Mouse` and `GamepadState` by `update_actions`, which should
+/// run after the frame's events are ingested and before systems read it.
+#[derive(Debug, Clone, Default)]
+pub struct ActionMap {
+    buttons: HashMap<&'static str, Vec<Binding>>,
+    axes: HashMap<&'static str, Vec<AxisBinding>>,
+    state: Buttons<&'static str>,
+    values: HashMap<&'static str, f32>,
+}
+
+impl Resource for ActionMap {}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a binding for a button action; any bound button held counts as the action held.
+    pub fn bind(&mut self, action: &'static str, binding: Binding) -> &mut Self {
+        let bindings = self.buttons.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self
+    }
+
+    /// Adds an input to an axis action; the action's value is their sum, clamped to -1..=1.
+    pub fn bind_axis(&mut self, action: &'static str, binding: AxisBinding) -> &mut Self {
+        self.axes.entry(action).or_default().push(binding);
+        self
+    }
+
+    /// Removes every binding of the action, e.g. before rebinding it.
+    pub fn unbind(&mut self, action: &str) {
+        self.buttons.remove(action);
+        self.axes.remove(action);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.buttons.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns true while any of the action's buttons is held.
+    pub fn pressed(&self, action: &'static str) -> bool {
+        self.state.pressed(action)
+    }
+
+    /// Returns true on the frame the action started being held.
+    pub fn just_pressed(&self, action: &'static str) -> bool {
+        self.state.just_pressed(action)
+    }
+
+    /// Returns true on the frame the action stopped being held.
+    pub fn just_released(&self, action: &'static str) -> bool {
+        self.state.just_released(action)
+    }
+
+    /// The axis action's value this frame, or 0 if it has none.
+    pub fn axis(&self, action: &str) -> f32 {
+        self.values.get(action).copied().unwrap_or(0.0)
+    }
+
+    fn update(&mut self, keyboard: &Keyboard, mouse: &Mouse, gamepads: &GamepadState) {
+        // A press and release within one frame still counts, for a frame
+        let held = |binding: &Binding| match *binding {
+            Binding::Key(code) => keyboard.pressed(code) || keyboard.just_pressed(code),
+            Binding::Mouse(button) => mouse.pressed(button) || mouse.just_pressed(button),
+            Binding::GamepadButton(button) => gamepads
+                .pads
+                .values()
+                .any(|pad| pad.buttons.pressed(button) || pad.buttons.just_pressed(button)),
+        };
+        for (&action, bindings) in &self.buttons {
+            if bindings.iter().any(held) {
+                self.state.press(action);
+            } else {
+                self.state.release(action);
+            }
+        }
+
+        self.values.clear();
+        for (&action, bindings) in &self.axes {
+            let value: f32 = bindings
+                .iter()
+                .map(|binding| match *binding {
+                    AxisBinding::Keys { negative, positive } => {
+                        keyboard.pressed(positive) as i8 as f32
+                            - keyboard.pressed(negative) as i8 as f32
+                    }
+                    AxisBinding::GamepadAxis { axis, dead_zone } => gamepads
+                        .pads
+                        .values()
+                        .map(|pad| pad.axis(axis))
+                        .filter(|value| value.abs() > dead_zone)
+                        .sum(),
+                    AxisBinding::MouseWheel => mouse.wheel.1,
+                })
+                .sum();
+            self.values.insert(action, value.clamp(-1.0, 1.0));
+        }
+    }
+}
+
+/// Adds default `Keyboard`, `Mouse`, `GamepadState` and `ActionMap` resources.
+pub fn add_input_resources(resources: &mut Resources) {
+    resources.add(Keyboard::default());
+    resources.add(Mouse::default());
+    resources.add(GamepadState::default());
+    resources.add(ActionMap::default());
+}
+
+/// Recomputes the `ActionMap` from the raw input resources. Does nothing if any of them
+/// is missing (or currently locked).
+pub fn update_actions(resources: &Resources) {
+    let (Some(mut actions), Some(keyboard), Some(mouse), Some(gamepads)) = (
+        resources.get_mut::<ActionMap>(),
+        resources.get::<Keyboard>(),
+        resources.get::<Mouse>(),
+        resources.get::<GamepadState>(),
+    ) else {
+        return;
+    };
+    actions.update(&keyboard, &mouse, &gamepads);
+}
+
+/// Applies a raw input event to the input resources.
+/// Events for resources that are missing (or currently locked) are dropped.
+pub fn ingest(resources: &Resources, event: InputEvent) {
+    match event {
+        InputEvent::Key { code, pressed } => {
+            if let Some(mut keyboard) = resources.get_mut::<Keyboard>() {
+                if pressed {
+                    keyboard.keys.press(code);
+                } else {
+                    keyboard.keys.release(code);
+                }
+            }
+        }
+        InputEvent::MouseMoved { x, y } => {
+            if let Some(mut mouse) = resources.get_mut::<Mouse>() {
+                if mouse.has_position {
+                    mouse.delta.0 += x - mouse.position.0;
+                    mouse.delta.1 += y - mouse.position.1;
+                }
+                mouse.position = (x, y);
+                mouse.has_position = true;
+            }
+        }
+        InputEvent::MouseButton { button, pressed } => {
+            if let Some(mut mouse) = resources.get_mut::<Mouse>() {
+                if pressed {
+                    mouse.buttons.press(button);
+                } else {
+                    mouse.buttons.release(button);
+                }
+            }
+        }
+        InputEvent::MouseWheel { dx, dy } => {
+            if let Some(mut mouse) = resources.get_mut::<Mouse>() {
+                mouse.wheel.0 += dx;
+                mouse.wheel.1 += dy;
+            }
+        }
+        InputEvent::GamepadButton {
+            pad,
+            button,
+            pressed,
+        } => {
+            if let Some(mut gamepads) = resources.get_mut::<GamepadState>() {
+                let gamepad = gamepads.pads.entry(pad).or_default();
+                if pressed {
+                    gamepad.buttons.press(button);
+                } else {
+                    gamepad.buttons.release(button);
+                }
+            }
+        }
+        InputEvent::GamepadAxis { pad, axis, value } => {
+            if let Some(mut gamepads) = resources.get_mut::<GamepadState>() {
+                gamepads
+                    .pads
+                    .entry(pad)
+                    .or_default()
+                    .axes
+                    .insert(axis, value);
+            }
+        }
+        InputEvent::GamepadDisconnected { pad } => {
+            if let Some(mut gamepads) = resources.get_mut::<GamepadState>() {
+                gamepads.pads.remove(&pad);
+            }
+        }
+    }
+}
+
+/// Clears per-frame input state (just pressed/released, mouse delta and wheel).
+/// Call once at the end of every frame, after systems have run.
+pub fn end_frame(resources: &Resources) {
+    if let Some(mut keyboard) = resources.get_mut::<Keyboard>() {
+        keyboard.keys.clear_frame();
+    }
+    if let Some(mut mouse) = resources.get_mut::<Mouse>() {
+        mouse.buttons.clear_frame();
+        mouse.delta = (0.0, 0.0);
+        mouse.wheel = (0.0, 0.0);
+    }
+    if let Some(mut gamepads) = resources.get_mut::<GamepadState>() {
+        for gamepad in gamepads.pads.values_mut() {
+            gamepad.buttons.clear_frame();
+        }
+    }
+    if let Some(mut actions) = resources.get_mut::<ActionMap>() {
+        actions.state.clear_frame();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACE: KeyCode = KeyCode(32);
+
+    #[test]
+    fn key_transitions() {
+        let mut resources = Resources::new();
+        add_input_resources(&mut resources);
+
+        ingest(
+            &resources,
+            InputEvent::Key {
+                code: SPACE,
+                pressed: true,
+            },
+        );
+        {
+            let keyboard = resources.get::<Keyboard>().unwrap();
+            assert!(keyboard.pressed(SPACE));
+            assert!(keyboard.just_pressed(SPACE));
+        }
+
+        end_frame(&resources);
+        // Key repeat shouldn't count as a fresh press
+        ingest(
+            &resources,
+            InputEvent::Key {
+                code: SPACE,
+                pressed: true,
+            },
+        );
+        {
+            let keyboard = resources.get::<Keyboard>().unwrap();
+            assert!(keyboard.pressed(SPACE));
+            assert!(!keyboard.just_pressed(SPACE));
+        }
+
+        ingest(
+            &resources,
+            InputEvent::Key {
+                code: SPACE,
+                pressed: false,
+            },
+        );
+        let keyboard = resources.get::<Keyboard>().unwrap();
+        assert!(!keyboard.pressed(SPACE));
+        assert!(keyboard.just_released(SPACE));
+    }
+
+    #[test]
+    fn mouse_delta_and_gamepad() {
+        let mut resources = Resources::new();
+        add_input_resources(&mut resources);
+
+        ingest(&resources, InputEvent::MouseMoved { x: 10.0, y: 10.0 });
+        ingest(&resources, InputEvent::MouseMoved { x: 15.0, y: 8.0 });
+        ingest(
+            &resources,
+            InputEvent::GamepadAxis {
+                pad: 0,
+                axis: 1,
+                value: 0.5,
+            },
+        );
+        {
+            let mouse = resources.get::<Mouse>().unwrap();
+            assert_eq!(mouse.position, (15.0, 8.0));
+            assert_eq!(mouse.delta, (5.0, -2.0));
+            let gamepads = resources.get::<GamepadState>().unwrap();
+            assert_eq!(gamepads.get(0).unwrap().axis(1), 0.5);
+        }
+
+        end_frame(&resources);
+        assert_eq!(resources.get::<Mouse>().unwrap().delta, (0.0, 0.0));
+    }
+
+    #[test]
+    fn actions_follow_their_bindings() {
+        let mut resources = Resources::new();
+        add_input_resources(&mut resources);
+        resources
+            .get_mut::<ActionMap>()
+            .unwrap()
+            .bind("jump", Binding::Key(SPACE))
+            .bind("jump", Binding::GamepadButton(0))
+            .bind_axis(
+                "move_x",
+                AxisBinding::Keys {
+                    negative: KeyCode(65),
+                    positive: KeyCode(68),
+                },
+            )
+            .bind_axis(
+                "move_x",
+                AxisBinding::GamepadAxis {
+                    axis: 0,
+                    dead_zone: 0.1,
+                },
+            );
+        let frame = |events: &[InputEvent]| {
+            end_frame(&resources);
+            for &event in events {
+                ingest(&resources, event);
+            }
+            update_actions(&resources);
+        };
+        let key = |code, pressed| InputEvent::Key { code, pressed };
+
+        frame(&[key(SPACE, true), key(KeyCode(68), true)]);
+        {
+            let actions = resources.get::<ActionMap>().unwrap();
+            assert!(actions.just_pressed("jump") && actions.pressed("jump"));
+            assert_eq!(actions.axis("move_x"), 1.0);
+        }
+
+        // A second binding going down while the first is held isn't a new press
+        let pad = |pressed| InputEvent::GamepadButton {
+            pad: 0,
+            button: 0,
+            pressed,
+        };
+        let stick = |value| InputEvent::GamepadAxis {
+            pad: 0,
+            axis: 0,
+            value,
+        };
+        frame(&[pad(true), key(SPACE, false), stick(-0.05)]);
+        {
+            let actions = resources.get::<ActionMap>().unwrap();
+            assert!(actions.pressed("jump") && !actions.just_pressed("jump"));
+            assert_eq!(actions.axis("move_x"), 1.0);
+        }
+
+        frame(&[pad(false), key(KeyCode(68), false), stick(-0.5)]);
+        let actions = resources.get::<ActionMap>().unwrap();
+        assert!(actions.just_released("jump"));
+        assert_eq!(actions.axis("move_x"), -0.5);
+        assert_eq!(actions.axis("unbound"), 0.0);
+    }
+}
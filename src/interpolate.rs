@@ -0,0 +1,104 @@
+// Previous/current buffering and blending for smooth rendering between fixed ticks
+
+use crate::{
+    component::Entity,
+    world::{Component, World},
+};
+
+/// Linear blend between two values. `t` of 0 gives `self`, 1 gives `to`, and values past 1
+/// extrapolate.
+pub trait Lerp: Copy {
+    fn lerp(self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for f64 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t as f64
+    }
+}
+
+impl<T: Lerp, const N: usize> Lerp for [T; N] {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        std::array::from_fn(|i| self[i].lerp(to[i], t))
+    }
+}
+
+/// The value a component had at the last `save_previous`: the back buffer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Previous<T>(pub T);
+
+impl<T: Component> Component for Previous<T> {}
+
+/// Maintenance system: copies every `T` into its `Previous<T>`. Run it at the start of each
+/// fixed tick, before the simulation writes new values.
+pub fn save_previous<T: Component>(world: &mut World) {
+    world.add::<Previous<T>>();
+    let (Some(current), Some(previous)) = world.get_two_mut::<T, Previous<T>>() else {
+        return;
+    };
+    for (entity, value) in current.iter() {
+        previous.set(Previous(*value), entity);
+    }
+}
+
+/// Iterates every `T` blended from its previous value by `alpha`, the fraction of a tick
+/// elapsed since the last one. Entities without a previous value yield their current one.
+pub fn interpolated<T: Component + Lerp>(
+    world: &World,
+    alpha: f32,
+) -> impl Iterator<Item = (Entity, T)> + '_ {
+    let previous = world.get::<Previous<T>>();
+    world.iter::<T>().map(move |(entity, current)| {
+        let blended = previous
+            .and_then(|set| set.get(entity))
+            .map_or(*current, |prev| prev.0.lerp(*current, alpha));
+        (entity, blended)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Position([f32; 2]);
+    impl Component for Position {}
+
+    impl Lerp for Position {
+        fn lerp(self, to: Self, t: f32) -> Self {
+            Position(self.0.lerp(to.0, t))
+        }
+    }
+
+    #[test]
+    fn blends_between_ticks() {
+        let mut world = World::new(4);
+        let e = world.spawn_entity().with(Position([0.0, 10.0])).build();
+        let fresh = world.spawn_entity().with(Position([1.0, 1.0])).build();
+        world.get_mut::<Position>().unwrap().remove_entity(fresh);
+
+        save_previous::<Position>(&mut world);
+        world
+            .get_mut::<Position>()
+            .unwrap()
+            .set(Position([4.0, 20.0]), e);
+        world
+            .get_mut::<Position>()
+            .unwrap()
+            .set(Position([1.0, 1.0]), fresh);
+
+        let frame: Vec<_> = interpolated::<Position>(&world, 0.25).collect();
+        assert!(frame.contains(&(e, Position([1.0, 12.5]))));
+        assert!(frame.contains(&(fresh, Position([1.0, 1.0]))));
+
+        // Extrapolates past the current tick
+        let ahead: Vec<_> = interpolated::<Position>(&world, 1.5).collect();
+        assert!(ahead.contains(&(e, Position([6.0, 25.0]))));
+    }
+}
@@ -0,0 +1,201 @@
+// Hashed entity labels, a cheaper alternative to string tags
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::{BuildHasherDefault, Hasher},
+    sync::LazyLock,
+};
+
+use parking_lot::Mutex;
+
+use crate::component::Entity;
+
+/// Names seen by `Label::register`, by hash.
+static NAMES: LazyLock<Mutex<HashMap<u64, &'static str>>> = LazyLock::new(Mutex::default);
+
+/// A label hashed from a name, compared and looked up as a plain `u64`.
+/// Make one with `label!("boss_door")`, which hashes at compile time and, in debug builds,
+/// registers the name so two names hashing to the same label panic instead of matching.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Label(pub u64);
+
+impl Label {
+    /// Hashes a name with 64-bit FNV-1a, the same hash `label!` uses.
+    pub const fn from_name(name: &str) -> Self {
+        const OFFSET: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+        let bytes = name.as_bytes();
+        let mut hash = OFFSET;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u64;
+            hash = hash.wrapping_mul(PRIME);
+            i += 1;
+        }
+        Label(hash)
+    }
+
+    /// Hashes a name and records it, so `name` and `Debug` can show it.
+    /// Panics if a different name already hashed to the same label.
+    pub fn register(name: &'static str) -> Self {
+        let label = Self::from_name(name);
+        let mut names = NAMES.lock();
+        let known = *names.entry(label.0).or_insert(name);
+        assert!(
+            known == name,
+            "labels {known:?} and {name:?} hash to the same value {:#018x}",
+            label.0
+        );
+        label
+    }
+
+    /// The registered name, if any.
+    pub fn name(self) -> Option<&'static str> {
+        NAMES.lock().get(&self.0).copied()
+    }
+}
+
+impl fmt::Debug for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "Label({name:?})"),
+            None => write!(f, "Label({:#018x})", self.0),
+        }
+    }
+}
+
+/// Passes a label's hash straight through, since it's already well mixed.
+#[derive(Default)]
+pub struct LabelHasher(u64);
+
+impl Hasher for LabelHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 << 8) | byte as u64;
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
+    }
+}
+
+type LabelMap<V> = HashMap<Label, V, BuildHasherDefault<LabelHasher>>;
+
+/// Labels attached to entities, each with the set of entities carrying it.
+/// Like `EntityTags`, but keyed by `Label` so hot paths skip hashing strings.
+#[derive(Default, Clone)]
+pub struct EntityLabels {
+    labels: LabelMap<HashSet<Entity>>,
+}
+
+impl fmt::Debug for EntityLabels {
+    /// Shows each label with its entity count.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut counts: Vec<_> = self
+            .labels
+            .iter()
+            .map(|(label, entities)| (*label, entities.len()))
+            .collect();
+        counts.sort();
+        f.debug_map().entries(counts).finish()
+    }
+}
+
+impl EntityLabels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Labels the entity. Returns false if it already had the label.
+    pub fn add(&mut self, label: Label, entity: Entity) -> bool {
+        self.labels.entry(label).or_default().insert(entity)
+    }
+
+    /// Returns false if the entity didn't have the label.
+    pub fn remove(&mut self, label: Label, entity: Entity) -> bool {
+        self.labels
+            .get_mut(&label)
+            .is_some_and(|entities| entities.remove(&entity))
+    }
+
+    pub fn clear(&mut self) {
+        self.labels.clear();
+    }
+
+    pub fn remove_all(&mut self, entity: Entity) {
+        for entities in self.labels.values_mut() {
+            entities.remove(&entity);
+        }
+    }
+
+    pub fn has(&self, label: Label, entity: Entity) -> bool {
+        self.labels
+            .get(&label)
+            .is_some_and(|entities| entities.contains(&entity))
+    }
+
+    /// Iterates the entities with the label, in no particular order.
+    pub fn entities(&self, label: Label) -> impl Iterator<Item = Entity> + '_ {
+        self.labels.get(&label).into_iter().flatten().copied()
+    }
+
+    pub fn count(&self, label: Label) -> usize {
+        self.labels.get(&label).map_or(0, HashSet::len)
+    }
+
+    /// Iterates the entity's labels, in no particular order.
+    pub fn labels_of(&self, entity: Entity) -> impl Iterator<Item = Label> + '_ {
+        self.labels
+            .iter()
+            .filter(move |(_, entities)| entities.contains(&entity))
+            .map(|(label, _)| *label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    #[test]
+    fn labels_hash_at_compile_time() {
+        const DOOR: Label = Label::from_name("boss_door");
+        assert_eq!(crate::label!("boss_door"), DOOR);
+        assert_ne!(crate::label!("boss_key"), DOOR);
+        // `label!` only records names in debug builds
+        #[cfg(debug_assertions)]
+        {
+            assert_eq!(DOOR.name(), Some("boss_door"));
+            assert_eq!(format!("{DOOR:?}"), "Label(\"boss_door\")");
+        }
+        #[cfg(not(debug_assertions))]
+        assert_eq!(DOOR.name(), None);
+
+        let mut world = World::new(4);
+        let door = world.spawn();
+        let other = world.spawn();
+        assert!(world.labels.add(DOOR, door));
+        assert!(!world.labels.add(DOOR, door));
+        assert!(world.labels.has(DOOR, door));
+        assert!(!world.labels.has(DOOR, other));
+        assert_eq!(world.labels.entities(DOOR).collect::<Vec<_>>(), vec![door]);
+
+        world.despawn(door);
+        assert_eq!(world.labels.count(DOOR), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "hash to the same value")]
+    fn colliding_names_panic() {
+        // Hand-built collision: record a name under another name's hash
+        let label = Label::from_name("collides_a");
+        NAMES.lock().insert(label.0, "collides_b");
+        Label::register("collides_a");
+    }
+}
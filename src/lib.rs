@@ -1,7 +1,46 @@
+pub mod action;
+pub mod builder;
+pub mod bundle;
+pub mod commands;
 pub mod component;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod console;
+pub mod diff;
+pub mod entity;
+pub mod events;
+#[cfg(feature = "json")]
+pub mod export;
+pub mod fault;
+pub mod history;
+#[cfg(feature = "input")]
+pub mod input;
+pub mod interpolate;
+pub mod label;
+pub mod mailbox;
+pub mod marker;
+pub mod metrics;
+pub mod parallel;
+#[cfg(feature = "json")]
+pub mod persist;
+pub mod pool;
+pub mod query;
+pub mod registry;
+#[cfg(feature = "remote")]
+pub mod remote;
 pub mod resource;
+pub mod schedule;
+pub mod shared;
+pub mod sync;
 pub mod tags;
+pub mod template;
+pub mod timer;
+mod traits;
+pub mod watch;
 pub mod world;
 
+// Lets the derive macros' `sparse_ecs::` paths resolve inside this crate too.
+extern crate self as sparse_ecs;
+
 #[cfg(feature = "macros")]
-pub use sparse_ecs_macros::{Component, Resource};
+pub use sparse_ecs_macros::{Bundle, Component, DiffSerialize, QueryData, Resource, label};
@@ -0,0 +1,226 @@
+// Lock-free single producer, single consumer mailbox
+
+use std::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use crate::resource::Resource;
+
+struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Next slot to read, only written by the receiver.
+    head: AtomicUsize,
+    /// Next slot to write, only written by the sender.
+    tail: AtomicUsize,
+}
+
+// Safety: a slot is only ever accessed by one side at a time, handed over through
+// the release/acquire pairs on `head` and `tail`.
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn slot(&self, pos: usize) -> *mut MaybeUninit<T> {
+        self.slots[pos % self.slots.len()].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        for i in 0..tail.wrapping_sub(head) {
+            // Safety: everything between head and tail was written and never read.
+            unsafe { (*self.slot(head.wrapping_add(i))).assume_init_drop() };
+        }
+    }
+}
+
+/// Sending half of a fixed-capacity mailbox, meant to live in `Resources` so systems can
+/// post commands to a real-time thread (audio, haptics).
+/// Pushing never allocates or locks, so the receiving thread never waits on the ECS.
+pub struct Mailbox<T: Send + 'static> {
+    ring: Arc<Ring<T>>,
+}
+
+/// Receiving half of a mailbox, owned by the real-time thread.
+/// Popping never allocates or locks.
+pub struct MailboxReceiver<T: Send + 'static> {
+    ring: Arc<Ring<T>>,
+}
+
+impl<T: Send + 'static> Resource for Mailbox<T> {}
+
+impl<T: Send + 'static> std::fmt::Debug for Mailbox<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mailbox")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<T: Send + 'static> std::fmt::Debug for MailboxReceiver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MailboxReceiver")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<T: Send + 'static> Mailbox<T> {
+    /// Creates a mailbox holding at most `capacity` pending messages.
+    /// All allocation happens here.
+    pub fn new(capacity: usize) -> (Mailbox<T>, MailboxReceiver<T>) {
+        assert!(capacity > 0, "Mailbox capacity must be non-zero");
+        let slots = (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+        let ring = Arc::new(Ring {
+            slots,
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        });
+        (Mailbox { ring: ring.clone() }, MailboxReceiver { ring })
+    }
+
+    /// Posts a message. Returns it back if the mailbox is full.
+    pub fn push(&mut self, message: T) -> Result<(), T> {
+        let tail = self.ring.tail.load(Ordering::Relaxed);
+        let head = self.ring.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) == self.ring.slots.len() {
+            return Err(message);
+        }
+        // Safety: the slot is outside head..tail so the receiver won't touch it.
+        unsafe { (*self.ring.slot(tail)).write(message) };
+        self.ring
+            .tail
+            .store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Returns the number of messages waiting to be received.
+    pub fn len(&self) -> usize {
+        pending(&self.ring)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of pending messages.
+    pub fn capacity(&self) -> usize {
+        self.ring.slots.len()
+    }
+}
+
+impl<T: Send + 'static> MailboxReceiver<T> {
+    /// Takes the oldest message, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        let head = self.ring.head.load(Ordering::Relaxed);
+        let tail = self.ring.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        // Safety: the slot is inside head..tail, so it was written and published by the sender.
+        let message = unsafe { (*self.ring.slot(head)).assume_init_read() };
+        self.ring
+            .head
+            .store(head.wrapping_add(1), Ordering::Release);
+        Some(message)
+    }
+
+    /// Iterates all messages currently in the mailbox.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.pop())
+    }
+
+    /// Returns the number of messages waiting to be received.
+    pub fn len(&self) -> usize {
+        pending(&self.ring)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn pending<T>(ring: &Ring<T>) -> usize {
+    let tail = ring.tail.load(Ordering::Acquire);
+    let head = ring.head.load(Ordering::Acquire);
+    tail.wrapping_sub(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::Resources;
+
+    #[derive(Debug, PartialEq)]
+    enum AudioCommand {
+        Play(u32),
+        Stop,
+    }
+
+    #[test]
+    fn push_pop_in_order() {
+        let (mut mailbox, mut receiver) = Mailbox::new(2);
+        assert!(mailbox.push(AudioCommand::Play(1)).is_ok());
+        assert!(mailbox.push(AudioCommand::Stop).is_ok());
+        assert_eq!(
+            mailbox.push(AudioCommand::Play(2)),
+            Err(AudioCommand::Play(2))
+        );
+        assert_eq!(receiver.len(), 2);
+
+        assert_eq!(receiver.pop(), Some(AudioCommand::Play(1)));
+        assert!(mailbox.push(AudioCommand::Play(3)).is_ok());
+        let rest: Vec<_> = receiver.drain().collect();
+        assert_eq!(rest, vec![AudioCommand::Stop, AudioCommand::Play(3)]);
+        assert!(receiver.is_empty());
+    }
+
+    #[test]
+    fn drops_pending_messages() {
+        let counter = Arc::new(());
+        let (mut mailbox, receiver) = Mailbox::new(4);
+        mailbox.push(counter.clone()).unwrap();
+        mailbox.push(counter.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&counter), 3);
+        drop(mailbox);
+        drop(receiver);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn receive_on_other_thread() {
+        let mut resources = Resources::new();
+        let (mailbox, mut receiver) = Mailbox::<u32>::new(16);
+        resources.add(mailbox);
+
+        let handle = std::thread::spawn(move || {
+            let mut received = Vec::new();
+            while received.len() < 100 {
+                received.extend(receiver.drain());
+                std::thread::yield_now();
+            }
+            received
+        });
+
+        let mut next = 0;
+        while next < 100 {
+            let mut mailbox = resources.get_mut::<Mailbox<u32>>().unwrap();
+            if mailbox.push(next).is_ok() {
+                next += 1;
+            }
+        }
+
+        let received = handle.join().unwrap();
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,258 @@
+// Data-less marker components kept as one bit per entity
+
+use std::{any::TypeId, collections::HashMap, fmt};
+
+use crate::{component::Entity, world::Component};
+
+/// A zero-sized component like `Player`, `Dead` or `Frozen`, stored as a bit per entity
+/// with `World::mark` instead of in a component storage. Checking one is a bit test, and
+/// the `Marked<T>`/`Unmarked<T>` query filters join on it without touching a dense array.
+///
+/// A marker type lives in one place: mark it, or insert it as a component, not both.
+/// Markers are kept by `fork`, world clones and prefabs.
+pub trait Marker: Component {}
+
+/// Growable set of entity indices.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl BitSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns false if the index was already set.
+    pub fn insert(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let added = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        self.len += added as usize;
+        added
+    }
+
+    /// Returns false if the index wasn't set.
+    pub fn remove(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / 64, 1 << (index % 64));
+        let Some(bits) = self.words.get_mut(word) else {
+            return false;
+        };
+        let removed = *bits & bit != 0;
+        *bits &= !bit;
+        self.len -= removed as usize;
+        removed
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|bits| bits & (1 << (index % 64)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.len = 0;
+    }
+
+    /// Set indices in ascending order, skipping empty words 64 at a time.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(word, &bits)| {
+            let mut bits = bits;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(word * 64 + bit)
+            })
+        })
+    }
+}
+
+/// Every marker type's bitset, by type. Held by the world; indices are entity slots, and
+/// the world clears an entity's bits when it despawns.
+#[derive(Clone, Default)]
+pub(crate) struct Markers {
+    sets: HashMap<TypeId, (&'static str, BitSet)>,
+}
+
+impl fmt::Debug for Markers {
+    /// Shows each marker type with its entity count.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut counts: Vec<_> = self
+            .sets
+            .values()
+            .map(|(name, bits)| (*name, bits.len()))
+            .collect();
+        counts.sort();
+        f.debug_map().entries(counts).finish()
+    }
+}
+
+impl Markers {
+    pub(crate) fn get<T: Marker>(&self) -> Option<&BitSet> {
+        self.sets.get(&TypeId::of::<T>()).map(|(_, bits)| bits)
+    }
+
+    pub(crate) fn get_mut<T: Marker>(&mut self) -> &mut BitSet {
+        const { assert!(size_of::<T>() == 0, "markers must be zero-sized") };
+        &mut self
+            .sets
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| (std::any::type_name::<T>(), BitSet::new()))
+            .1
+    }
+
+    pub(crate) fn remove_all(&mut self, index: usize) {
+        for (_, bits) in self.sets.values_mut() {
+            bits.remove(index);
+        }
+    }
+
+    /// Sets each `to` entity's bits in `dst` where the `from` entity's are set here.
+    /// Bits already set in `dst` are kept.
+    pub(crate) fn copy_into(&self, dst: &mut Markers, pairs: &[(Entity, Entity)]) {
+        for (key, (name, bits)) in &self.sets {
+            for &(from, to) in pairs {
+                if bits.contains(from.index()) {
+                    dst.sets
+                        .entry(*key)
+                        .or_insert_with(|| (name, BitSet::new()))
+                        .1
+                        .insert(to.index());
+                }
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for (_, bits) in self.sets.values_mut() {
+            bits.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        query::{Marked, Unmarked},
+        world::World,
+    };
+
+    #[derive(Copy, Clone, Debug)]
+    struct Frozen;
+    impl Component for Frozen {}
+    impl Marker for Frozen {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[test]
+    fn bitsets_iterate_set_bits_in_order() {
+        let mut bits = BitSet::new();
+        for index in [130, 3, 64, 3] {
+            bits.insert(index);
+        }
+        assert_eq!(bits.len(), 3);
+        assert!(bits.remove(64));
+        assert!(!bits.remove(64));
+        assert!(!bits.remove(10_000));
+        assert_eq!(bits.iter().collect::<Vec<_>>(), vec![3, 130]);
+        assert!(bits.contains(130) && !bits.contains(131));
+    }
+
+    #[test]
+    fn marked_entities_join_queries() {
+        let mut world = World::new(4);
+        let a = world.spawn_entity().with(Position(1)).build();
+        let b = world.spawn_entity().with(Position(2)).build();
+        let c = world.spawn();
+        assert!(world.mark::<Frozen>(a));
+        assert!(!world.mark::<Frozen>(a));
+        world.mark::<Frozen>(c);
+        assert!(world.is_marked::<Frozen>(a) && !world.is_marked::<Frozen>(b));
+
+        let frozen: Vec<_> = world
+            .query_filtered::<&Position, Marked<Frozen>>()
+            .into_iter()
+            .map(|(_, pos)| *pos)
+            .collect();
+        assert_eq!(frozen, vec![Position(1)]);
+        let moving: Vec<_> = world
+            .query_filtered::<&Position, Unmarked<Frozen>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(moving, vec![b]);
+
+        // Despawning clears the bit, so the slot's next entity starts unmarked
+        world.despawn(c);
+        let reused = world.spawn();
+        assert_eq!(reused.index(), c.index());
+        assert!(!world.is_marked::<Frozen>(reused) && !world.is_marked::<Frozen>(c));
+        assert!(world.unmark::<Frozen>(a));
+        assert_eq!(world.marked::<Frozen>().count(), 0);
+    }
+
+    #[test]
+    fn batched_despawns_clear_markers() {
+        let mut world = World::new(4);
+        world.spawn();
+        let doomed = world.spawn_entity().with(Position(1)).build();
+        world.mark::<Frozen>(doomed);
+        assert_eq!(world.despawn_where::<Position>(|_, _, _| true), 1);
+        assert_eq!(world.marked_count::<Frozen>(), 0);
+
+        let fresh = world.spawn();
+        assert_eq!(fresh.index(), doomed.index());
+        assert!(!world.is_marked::<Frozen>(fresh));
+    }
+
+    #[test]
+    fn marked_skips_trimmed_slots() {
+        let mut world = World::new(4);
+        let kept = world.spawn();
+        let trimmed = world.spawn_entity().with(Position(1)).build();
+        world.mark::<Frozen>(kept);
+        world.mark::<Frozen>(trimmed);
+        world.despawn_where::<Position>(|_, _, _| true);
+        assert_eq!(world.gc(), 1);
+        assert_eq!(world.marked::<Frozen>().collect::<Vec<_>>(), vec![kept]);
+    }
+
+    #[test]
+    fn prefabs_carry_markers() {
+        let mut world = World::new(4);
+        world.spawn();
+        let frozen = world.spawn_entity().with(Position(1)).build();
+        world.mark::<Frozen>(frozen);
+        let prefab = world.snapshot_entities([frozen]);
+        assert!(prefab.world().is_marked::<Frozen>(Entity(0)));
+
+        let stamped = world.spawn_template(&prefab)[0];
+        assert!(world.is_marked::<Frozen>(stamped));
+        assert_eq!(world.marked_count::<Frozen>(), 2);
+    }
+}
@@ -0,0 +1,115 @@
+// Health metrics export for long-running worlds
+
+use std::{fmt::Write, time::Instant};
+
+use crate::world::World;
+
+/// Receives metrics, e.g. to forward them to a Prometheus registry or a stats daemon.
+/// Names follow Prometheus conventions; labels are `(key, value)` pairs.
+pub trait MetricsSink {
+    /// A value that can go up and down, like an entity count.
+    fn gauge(&mut self, name: &str, labels: &[(&str, &str)], value: f64);
+    /// A running total. Sinks should add `delta` to the series' current value.
+    fn counter(&mut self, name: &str, labels: &[(&str, &str)], delta: u64);
+}
+
+/// Reports `ecs_entities_alive` and `ecs_components{component}` gauges for the world.
+pub fn export_world(world: &World, sink: &mut impl MetricsSink) {
+    sink.gauge("ecs_entities_alive", &[], world.entity_count() as f64);
+    for (name, count) in world.component_counts() {
+        sink.gauge("ecs_components", &[("component", name)], count as f64);
+    }
+}
+
+/// Runs `system`, reporting its wall time as `ecs_system_seconds{system}` and counting the
+/// run in `ecs_system_runs_total{system}`.
+pub fn timed<R>(sink: &mut impl MetricsSink, system: &str, run: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = run();
+    let labels = [("system", system)];
+    sink.gauge("ecs_system_seconds", &labels, start.elapsed().as_secs_f64());
+    sink.counter("ecs_system_runs_total", &labels, 1);
+    result
+}
+
+/// A sink that keeps the latest value of every series and renders them in the Prometheus
+/// text exposition format, for serving from a `/metrics` endpoint.
+#[derive(Debug, Default)]
+pub struct PrometheusText {
+    series: Vec<(String, f64)>,
+}
+
+impl PrometheusText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, name: &str, labels: &[(&str, &str)]) -> &mut f64 {
+        let mut key = name.to_string();
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(k, v)| format!("{k}=\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")))
+                .collect();
+            let _ = write!(key, "{{{}}}", labels.join(","));
+        }
+        let idx = match self.series.iter().position(|(k, _)| *k == key) {
+            Some(idx) => idx,
+            None => {
+                self.series.push((key, 0.0));
+                self.series.len() - 1
+            }
+        };
+        &mut self.series[idx].1
+    }
+
+    /// Renders every series, one `name{labels} value` line each.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (key, value) in &self.series {
+            let _ = writeln!(out, "{key} {value}");
+        }
+        out
+    }
+}
+
+impl MetricsSink for PrometheusText {
+    fn gauge(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        *self.entry(name, labels) = value;
+    }
+
+    fn counter(&mut self, name: &str, labels: &[(&str, &str)], delta: u64) {
+        *self.entry(name, labels) += delta as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Component;
+
+    #[derive(Copy, Clone)]
+    struct Health;
+    impl Component for Health {}
+
+    #[test]
+    fn exports_prometheus_text() {
+        let mut world = World::new(4);
+        world.spawn_entity().with(Health).build();
+        world.spawn();
+
+        let mut sink = PrometheusText::new();
+        export_world(&world, &mut sink);
+        timed(&mut sink, "regen", || ());
+        timed(&mut sink, "regen", || ());
+
+        let text = sink.render();
+        assert!(text.contains("ecs_entities_alive 2\n"));
+        assert!(text.contains(&format!(
+            "ecs_components{{component=\"{}\"}} 1\n",
+            std::any::type_name::<Health>()
+        )));
+        assert!(text.contains("ecs_system_runs_total{system=\"regen\"} 2\n"));
+        assert!(text.contains("ecs_system_seconds{system=\"regen\"} "));
+    }
+}
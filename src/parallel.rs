@@ -0,0 +1,618 @@
+// Running systems with disjoint component access on multiple threads
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    component::{Entity, Storage},
+    query::{QueryData, QueryFilter},
+    resource::{Resource, Resources},
+    schedule::{IntoOrdered, Ordered, SystemOrder, sort_systems},
+    tags::EntityTags,
+    world::{Component, World},
+};
+
+/// The component storages and resources a system reads and writes.
+/// Systems whose accesses don't conflict (a write against any access of the same type)
+/// can run at the same time.
+#[derive(Clone, Debug, Default)]
+pub struct Access {
+    pub(crate) reads: HashSet<TypeId>,
+    pub(crate) writes: HashSet<TypeId>,
+    resource_reads: HashSet<TypeId>,
+    resource_writes: HashSet<TypeId>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<T: Component>(mut self) -> Self {
+        self.reads.insert(TypeId::of::<T>());
+        self
+    }
+
+    pub fn write<T: Component>(mut self) -> Self {
+        self.writes.insert(TypeId::of::<T>());
+        self
+    }
+
+    /// Adds everything the query reads and writes.
+    pub fn query<Q: QueryData>(mut self) -> Self {
+        Q::access(&mut self);
+        self
+    }
+
+    /// Adds the components the filter checks as reads.
+    pub fn filter<F: QueryFilter>(mut self) -> Self {
+        F::access(&mut self);
+        self
+    }
+
+    /// Records a read by a query filter. Filtering on a type the query writes is fine.
+    pub(crate) fn add_filter_read<T: Component>(&mut self) {
+        let key = TypeId::of::<T>();
+        if !self.writes.contains(&key) {
+            self.reads.insert(key);
+        }
+    }
+
+    /// Records a read by a query, panicking if the same query also writes `T`.
+    pub(crate) fn add_query_read<T: Component>(&mut self) {
+        let key = TypeId::of::<T>();
+        assert!(
+            !self.writes.contains(&key),
+            "{} is both read and written in one query",
+            std::any::type_name::<T>()
+        );
+        self.reads.insert(key);
+    }
+
+    /// Records a write by a query, panicking if the same query already accesses `T`.
+    pub(crate) fn add_query_write<T: Component>(&mut self) {
+        let key = TypeId::of::<T>();
+        assert!(
+            !self.reads.contains(&key) && self.writes.insert(key),
+            "{} is accessed more than once in one query",
+            std::any::type_name::<T>()
+        );
+    }
+
+    pub fn read_resource<R: Resource>(mut self) -> Self {
+        self.resource_reads.insert(TypeId::of::<R>());
+        self
+    }
+
+    pub fn write_resource<R: Resource>(mut self) -> Self {
+        self.resource_writes.insert(TypeId::of::<R>());
+        self
+    }
+
+    /// Returns true if the two systems can't safely run at the same time.
+    pub fn conflicts(&self, other: &Access) -> bool {
+        fn clash(
+            writes: &HashSet<TypeId>,
+            reads: &HashSet<TypeId>,
+            other_writes: &HashSet<TypeId>,
+        ) -> bool {
+            !writes.is_disjoint(other_writes) || !reads.is_disjoint(other_writes)
+        }
+        clash(&self.writes, &self.reads, &other.writes)
+            || clash(&other.writes, &other.reads, &self.writes)
+            || clash(
+                &self.resource_writes,
+                &self.resource_reads,
+                &other.resource_writes,
+            )
+            || clash(
+                &other.resource_writes,
+                &other.resource_reads,
+                &self.resource_writes,
+            )
+    }
+}
+
+/// A system that declares its access up front so it can run alongside others.
+pub trait ParallelSystem: Send + 'static {
+    fn access(&self) -> Access;
+    fn run(&mut self, world: &WorldView<'_>, resources: &Resources);
+
+    /// Name used in diagnostics and by ordering constraints.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Ordering constraints, set with `IntoOrdered::before`/`after`.
+    fn order(&self) -> SystemOrder {
+        SystemOrder::default()
+    }
+}
+
+impl<S: ParallelSystem> ParallelSystem for Ordered<S> {
+    fn access(&self) -> Access {
+        self.system.access()
+    }
+
+    fn run(&mut self, world: &WorldView<'_>, resources: &Resources) {
+        self.system.run(world, resources)
+    }
+
+    fn name(&self) -> &str {
+        self.system.name()
+    }
+
+    fn order(&self) -> SystemOrder {
+        self.order.clone()
+    }
+}
+
+/// A closure paired with its declared access, from `with_access`.
+pub struct AccessSystem<F> {
+    access: Access,
+    run: F,
+}
+
+impl<F> ParallelSystem for AccessSystem<F>
+where
+    F: FnMut(&WorldView<'_>, &Resources) + Send + 'static,
+{
+    fn access(&self) -> Access {
+        self.access.clone()
+    }
+
+    fn run(&mut self, world: &WorldView<'_>, resources: &Resources) {
+        (self.run)(world, resources)
+    }
+
+    fn name(&self) -> &str {
+        std::any::type_name::<F>()
+    }
+}
+
+impl<F> IntoOrdered for AccessSystem<F> where F: FnMut(&WorldView<'_>, &Resources) + Send + 'static {}
+
+/// Wraps a closure as a `ParallelSystem` with the given access.
+pub fn with_access<F>(access: Access, run: F) -> AccessSystem<F>
+where
+    F: FnMut(&WorldView<'_>, &Resources) + Send + 'static,
+{
+    AccessSystem { access, run }
+}
+
+/// A system's window onto the world, limited to its declared access.
+/// Panics on undeclared access, and on taking a written storage more than once per run.
+pub struct WorldView<'w> {
+    world: &'w World,
+    access: &'w Access,
+    writable: HashMap<TypeId, *mut dyn Any>,
+    taken: RefCell<HashSet<TypeId>>,
+}
+
+// SAFETY: the pointers are to storages only this view may touch; the executor never runs
+// two systems with conflicting access at once.
+unsafe impl Send for WorldView<'_> {}
+
+impl<'w> WorldView<'w> {
+    /// Shared access to a storage declared with `Access::read`.
+    /// Panics for types also declared with `Access::write`: read those through `get_mut`,
+    /// so a shared handle never coexists with the exclusive one.
+    pub fn get<T: Component>(&self) -> Option<&Storage<T>> {
+        let key = TypeId::of::<T>();
+        assert!(
+            self.access.reads.contains(&key),
+            "{} was not declared as read",
+            std::any::type_name::<T>()
+        );
+        assert!(
+            !self.access.writes.contains(&key),
+            "{} is declared as write, so use get_mut",
+            std::any::type_name::<T>()
+        );
+        self.world.get::<T>()
+    }
+
+    /// Exclusive access to a storage declared with `Access::write`.
+    /// Can be called once per type per run, so handles never alias.
+    #[allow(clippy::mut_from_ref)]
+    pub fn get_mut<T: Component>(&self) -> Option<&mut Storage<T>> {
+        let key = TypeId::of::<T>();
+        assert!(
+            self.access.writes.contains(&key),
+            "{} was not declared as write",
+            std::any::type_name::<T>()
+        );
+        assert!(
+            self.taken.borrow_mut().insert(key),
+            "{} is already borrowed by this system",
+            std::any::type_name::<T>()
+        );
+        let ptr = *self.writable.get(&key)?;
+        // SAFETY: the executor made the storage uniquely owned by the world, no other
+        // running system can access it, and `taken` hands it out at most once.
+        unsafe { (*ptr).downcast_mut::<Storage<T>>() }
+    }
+
+    /// Spawns an entity. Needs no declared access: the allocator has its own lock.
+    pub fn spawn(&self) -> Entity {
+        self.world.spawn_shared()
+    }
+
+    /// The world's tags, which any system can read.
+    pub fn tags(&self) -> &EntityTags {
+        &self.world.tags
+    }
+
+    /// Tags an entity. Needs no declared access: the tags have their own lock.
+    pub fn add_tag(&self, tag: &'static str, entity: Entity) {
+        self.world.tags.add_tags_shared(&[tag], entity);
+    }
+
+    pub fn remove_tag(&self, tag: &'static str, entity: Entity) {
+        self.world.tags.remove_tag_shared(tag, &entity);
+    }
+}
+
+/// The systems sharing a stage, and the resources each has touched so far.
+#[cfg(debug_assertions)]
+struct StageAccess {
+    systems: Vec<(String, Access)>,
+    used: parking_lot::Mutex<Vec<(usize, TypeId, bool)>>,
+}
+
+#[cfg(debug_assertions)]
+thread_local! {
+    /// The stage, and the system's index in it, running on this thread.
+    static RUNNING: RefCell<Option<(std::sync::Arc<StageAccess>, usize)>> =
+        const { RefCell::new(None) };
+}
+
+/// Called by `Resources` in debug builds. Systems get `&Resources` directly, so nothing
+/// stops them touching resources they didn't declare; panics, naming both systems, when
+/// that overlaps a write by another system in the same stage, before the lock is taken.
+#[cfg(debug_assertions)]
+pub(crate) fn check_resource_access(key: TypeId, type_name: &str, write: bool) {
+    RUNNING.with_borrow(|running| {
+        let Some((stage, me)) = running else {
+            return;
+        };
+        let mut used = stage.used.lock();
+        let clash = stage
+            .systems
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i != me)
+            .find(|(i, (_, access))| {
+                access.resource_writes.contains(&key)
+                    || (write && access.resource_reads.contains(&key))
+                    || used
+                        .iter()
+                        .any(|&(j, used_key, wrote)| j == *i && used_key == key && (wrote || write))
+            });
+        if let Some((_, (other, _))) = clash {
+            panic!(
+                "{} and {other} overlap on {type_name} in the same stage; \
+                 declare it with Access::read_resource or Access::write_resource",
+                stage.systems[*me].0
+            );
+        }
+        used.push((*me, key, write));
+    });
+}
+
+/// Runs `ParallelSystem`s in stages: each system joins the stage right after the last
+/// earlier system it conflicts with or is ordered after, so conflicting systems keep their
+/// insertion order while independent ones share a stage and run on scoped threads.
+#[derive(Default)]
+pub struct ParallelSchedule {
+    systems: Vec<(Access, Box<dyn ParallelSystem>)>,
+}
+
+impl ParallelSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_system(&mut self, system: impl ParallelSystem) -> &mut Self {
+        self.systems.push((system.access(), Box::new(system)));
+        self
+    }
+
+    /// Groups system indices into stages that can each run concurrently.
+    /// Panics if the ordering constraints form a cycle.
+    pub fn stages(&self) -> Vec<Vec<usize>> {
+        let names: Vec<&str> = self
+            .systems
+            .iter()
+            .map(|(_, system)| system.name())
+            .collect();
+        let orders: Vec<SystemOrder> = self
+            .systems
+            .iter()
+            .map(|(_, system)| system.order())
+            .collect();
+        let (sorted, must_follow) = sort_systems(&names, &orders);
+
+        let mut stage_of: Vec<Option<usize>> = vec![None; self.systems.len()];
+        let mut stages: Vec<Vec<usize>> = Vec::new();
+        for (n, &i) in sorted.iter().enumerate() {
+            let access = &self.systems[i].0;
+            let stage = sorted[..n]
+                .iter()
+                .filter(|&&j| must_follow[i].contains(&j) || self.systems[j].0.conflicts(access))
+                .filter_map(|&j| stage_of[j])
+                .map(|stage| stage + 1)
+                .max()
+                .unwrap_or(0);
+            stage_of[i] = Some(stage);
+            if stage == stages.len() {
+                stages.push(Vec::new());
+            }
+            stages[stage].push(i);
+        }
+        stages
+    }
+
+    /// Runs one frame: every stage in turn, then per-frame maintenance.
+    pub fn run(&mut self, world: &mut World, resources: &Resources) {
+        for stage in self.stages() {
+            world.increment_change_tick();
+            let writes: HashSet<TypeId> = stage
+                .iter()
+                .flat_map(|&i| self.systems[i].0.writes.iter().copied())
+                .collect();
+            let mut pointers = world.storage_ptrs(&writes);
+            let world: &World = world;
+
+            let mut jobs = Vec::with_capacity(stage.len());
+            #[cfg(debug_assertions)]
+            let mut systems = Vec::with_capacity(stage.len());
+            for (i, (access, system)) in self.systems.iter_mut().enumerate() {
+                if !stage.contains(&i) {
+                    continue;
+                }
+                #[cfg(debug_assertions)]
+                systems.push((system.name().to_string(), access.clone()));
+                let writable = access
+                    .writes
+                    .iter()
+                    .filter_map(|key| Some((*key, pointers.remove(key)?)))
+                    .collect();
+                let view = WorldView {
+                    world,
+                    access,
+                    writable,
+                    taken: RefCell::new(HashSet::new()),
+                };
+                jobs.push((system, view));
+            }
+
+            if jobs.len() == 1 {
+                let (system, view) = jobs.pop().expect("one job");
+                system.run(&view, resources);
+                continue;
+            }
+            #[cfg(debug_assertions)]
+            let stage_access = std::sync::Arc::new(StageAccess {
+                systems,
+                used: Default::default(),
+            });
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = jobs
+                    .into_iter()
+                    .zip(0..)
+                    .map(|((system, view), _n)| {
+                        #[cfg(debug_assertions)]
+                        let stage_access = stage_access.clone();
+                        scope.spawn(move || {
+                            #[cfg(debug_assertions)]
+                            RUNNING.set(Some((stage_access, _n)));
+                            system.run(&view, resources)
+                        })
+                    })
+                    .collect();
+                // Rethrow a system's own panic rather than the scope's generic one
+                for handle in handles {
+                    if let Err(panic) = handle.join() {
+                        std::panic::resume_unwind(panic);
+                    }
+                }
+            });
+        }
+        world.clear_tracked();
+        world.update_events();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Health(i32);
+    impl Component for Health {}
+
+    #[test]
+    fn stages_respect_conflicts() {
+        let mut schedule = ParallelSchedule::new();
+        let noop = |_: &WorldView<'_>, _: &Resources| {};
+        schedule
+            .add_system(with_access(
+                Access::new().write::<Position>().read::<Velocity>(),
+                noop,
+            ))
+            .add_system(with_access(Access::new().write::<Health>(), noop))
+            .add_system(with_access(Access::new().read::<Position>(), noop))
+            .add_system(with_access(Access::new().read::<Velocity>(), noop));
+        assert_eq!(schedule.stages(), vec![vec![0, 1, 3], vec![2]]);
+    }
+
+    fn read_input(_: &WorldView<'_>, _: &Resources) {}
+    fn move_things(_: &WorldView<'_>, _: &Resources) {}
+
+    #[test]
+    fn ordering_splits_stages() {
+        let mut schedule = ParallelSchedule::new();
+        schedule
+            .add_system(
+                with_access(Access::new().write::<Position>(), move_things).after(read_input),
+            )
+            .add_system(with_access(Access::new().write::<Velocity>(), read_input));
+        assert_eq!(schedule.stages(), vec![vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn runs_systems_with_their_access() {
+        let mut world = World::new(8);
+        let e = world
+            .spawn_entity()
+            .with(Position(0))
+            .with(Velocity(2))
+            .with(Health(10))
+            .build();
+        let resources = Resources::new();
+
+        let mut schedule = ParallelSchedule::new();
+        schedule
+            .add_system(with_access(
+                Access::new().write::<Position>().read::<Velocity>(),
+                |world: &WorldView<'_>, _: &Resources| {
+                    let velocities = world.get::<Velocity>().unwrap();
+                    for (e, pos) in world.get_mut::<Position>().unwrap().iter_mut() {
+                        pos.0 += velocities.get(e).unwrap().0;
+                    }
+                },
+            ))
+            .add_system(with_access(
+                Access::new().write::<Health>(),
+                |world: &WorldView<'_>, _: &Resources| {
+                    for (_, health) in world.get_mut::<Health>().unwrap().iter_mut() {
+                        health.0 -= 1;
+                    }
+                },
+            ));
+        schedule.run(&mut world, &resources);
+        schedule.run(&mut world, &resources);
+
+        assert_eq!(world.component::<Position>(e), Some(&Position(4)));
+        assert_eq!(world.component::<Health>(e), Some(&Health(8)));
+        assert!(world.get::<Position>().unwrap().added.is_empty());
+    }
+
+    #[test]
+    fn runs_update_events() {
+        struct Ping;
+
+        let mut world = World::new(8);
+        let mut schedule = ParallelSchedule::new();
+        schedule.add_system(with_access(
+            Access::new(),
+            |_: &WorldView<'_>, _: &Resources| {},
+        ));
+        world.send_event(Ping);
+        schedule.run(&mut world, &Resources::new());
+        assert!(!world.events::<Ping>().unwrap().is_empty());
+        schedule.run(&mut world, &Resources::new());
+        assert!(world.events::<Ping>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn systems_spawn_and_tag_in_parallel() {
+        let mut world = World::new(8);
+        let spawner = |tag: &'static str| {
+            with_access(
+                Access::new(),
+                move |world: &WorldView<'_>, _: &Resources| {
+                    for _ in 0..100 {
+                        let e = world.spawn();
+                        world.add_tag(tag, e);
+                    }
+                },
+            )
+        };
+        let mut schedule = ParallelSchedule::new();
+        schedule
+            .add_system(spawner("red"))
+            .add_system(spawner("blue"));
+        assert_eq!(schedule.stages(), vec![vec![0, 1]]);
+        schedule.run(&mut world, &Resources::new());
+
+        assert_eq!(world.entity_count(), 200);
+        assert_eq!(world.tags.count("red"), 100);
+        assert_eq!(world.tags.count("blue"), 100);
+        let red = world.tags.get_entities_with_tag("red").unwrap();
+        assert!(red.iter().all(|e| !world.tags.has_tag("blue", e)));
+    }
+
+    #[test]
+    #[should_panic(expected = "was not declared as read")]
+    fn undeclared_access_panics() {
+        let mut world = World::new(8);
+        world.spawn_entity().with(Health(1)).build();
+        let mut schedule = ParallelSchedule::new();
+        schedule.add_system(with_access(
+            Access::new(),
+            |world: &WorldView<'_>, _: &Resources| {
+                world.get::<Health>();
+            },
+        ));
+        schedule.run(&mut world, &Resources::new());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overlap on")]
+    fn undeclared_resource_overlap_panics() {
+        struct Score(u32);
+        impl Resource for Score {}
+
+        let mut world = World::new(8);
+        let mut resources = Resources::new();
+        resources.add(Score(0));
+        let mut schedule = ParallelSchedule::new();
+        schedule
+            .add_system(with_access(
+                Access::new().write_resource::<Score>(),
+                |_: &WorldView<'_>, resources: &Resources| {
+                    if let Some(mut score) = resources.get_mut::<Score>() {
+                        score.0 += 1;
+                    }
+                },
+            ))
+            .add_system(with_access(
+                Access::new(),
+                |_: &WorldView<'_>, resources: &Resources| {
+                    resources.get::<Score>();
+                },
+            ));
+        assert_eq!(schedule.stages().len(), 1);
+        schedule.run(&mut world, &resources);
+    }
+
+    #[test]
+    #[should_panic(expected = "is declared as write")]
+    fn reading_a_written_storage_panics() {
+        let mut world = World::new(8);
+        world.spawn_entity().with(Health(1)).build();
+        let mut schedule = ParallelSchedule::new();
+        schedule.add_system(with_access(
+            Access::new().read::<Health>().write::<Health>(),
+            |world: &WorldView<'_>, _: &Resources| {
+                let _health = world.get_mut::<Health>().unwrap();
+                world.get::<Health>();
+            },
+        ));
+        schedule.run(&mut world, &Resources::new());
+    }
+}
@@ -0,0 +1,269 @@
+// Append-only event log of world changes, replayable with `World::rebuild_from_log`
+
+use std::{
+    any::TypeId,
+    collections::HashSet,
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::json;
+
+use crate::{
+    component::Entity,
+    registry::PatchError,
+    world::{Component, World},
+};
+
+/// Writes spawns, despawns, and inserts/removals of JSON-registered components as
+/// line-delimited JSON, so a crashed server can rebuild its world without full snapshots.
+///
+/// Call `record` once per frame, before `World::clear_tracked` (which `Schedule::run` does
+/// last). In-place edits aren't tracked; report them with `mutated`.
+pub struct EventLog<W: Write> {
+    out: W,
+    live: HashSet<Entity>,
+    mutated: Vec<(Entity, TypeId)>,
+}
+
+impl<W: Write> EventLog<W> {
+    /// Starts a log for a fresh world, e.g. over a file opened in append mode.
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            live: HashSet::new(),
+            mutated: Vec::new(),
+        }
+    }
+
+    /// Marks the entity's `T` as changed so the next `record` writes its value.
+    pub fn mutated<T: Component>(&mut self, entity: Entity) {
+        self.mutated.push((entity, TypeId::of::<T>()));
+    }
+
+    /// Appends everything that changed since the last call and flushes.
+    pub fn record(&mut self, world: &World) -> io::Result<()> {
+        let despawned: Vec<Entity> = self
+            .live
+            .iter()
+            .copied()
+            .filter(|&entity| world.is_dead(&entity))
+            .collect();
+        for entity in despawned {
+            self.live.remove(&entity);
+            let reason = world
+                .despawned()
+                .iter()
+                .find(|event| event.entity == entity)
+                .and_then(|event| event.reason.as_deref());
+            match reason {
+                Some(reason) => self.write(json!({ "despawn": entity.0, "reason": reason }))?,
+                None => self.write(json!({ "despawn": entity.0 }))?,
+            }
+        }
+        for entity in world.entities() {
+            if self.live.insert(entity) {
+                self.write(json!({ "spawn": entity.0 }))?;
+            }
+        }
+
+        let mut changed: Vec<(Entity, &'static str)> = Vec::new();
+        for (name, tracked) in world.registry.tracked_json() {
+            let Some((added, removed)) = tracked(world) else {
+                continue;
+            };
+            for &entity in removed {
+                // Despawns already cover dead entities; re-added ones are written below
+                if world.is_alive(entity) && world.registry.read_json(world, name, entity).is_none()
+                {
+                    self.write(json!({ "remove": entity.0, "component": name }))?;
+                }
+            }
+            changed.extend(added.iter().map(|&entity| (entity, name)));
+        }
+        for (entity, type_id) in std::mem::take(&mut self.mutated) {
+            if let Some(name) = world.registry.name_of_id(type_id) {
+                changed.push((entity, name));
+            }
+        }
+        for (entity, name) in changed {
+            if let Some(value) = world.registry.read_json(world, name, entity) {
+                self.write(json!({ "set": entity.0, "component": name, "value": value }))?;
+            }
+        }
+        self.out.flush()
+    }
+
+    fn write(&mut self, record: serde_json::Value) -> io::Result<()> {
+        writeln!(self.out, "{record}")
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Errors from `World::rebuild_from_log`. Line numbers start at 1.
+#[derive(Debug)]
+pub enum LogError {
+    Io(io::Error),
+    Json(usize, serde_json::Error),
+    /// The record isn't one `EventLog` writes.
+    Malformed(usize),
+    Patch(usize, PatchError),
+}
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogError::Io(err) => write!(f, "failed to read log: {err}"),
+            LogError::Json(line, err) => write!(f, "line {line}: invalid JSON: {err}"),
+            LogError::Malformed(line) => write!(f, "line {line}: unrecognized record"),
+            LogError::Patch(line, err) => write!(f, "line {line}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LogError {}
+
+impl World {
+    /// Replays an `EventLog` into this world, which should be fresh with the same component
+    /// registrations and storages as the logged one. Returns the number of records applied.
+    pub fn rebuild_from_log(&mut self, log: impl BufRead) -> Result<usize, LogError> {
+        let mut applied = 0;
+        for (i, line) in log.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.map_err(LogError::Io)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: serde_json::Value =
+                serde_json::from_str(&line).map_err(|e| LogError::Json(line_no, e))?;
+            let id = |key: &str| {
+                record
+                    .get(key)
+                    .and_then(|v| v.as_u64())
+                    .map(|id| Entity(id as usize))
+            };
+            let component = record.get("component").and_then(|v| v.as_str());
+
+            if let Some(entity) = id("spawn") {
+                self.restore_entity(entity);
+            } else if let Some(entity) = id("despawn") {
+                // The entity may be unknown here, e.g. when the log starts mid-session
+                if self.is_alive(entity) {
+                    match record.get("reason").and_then(|v| v.as_str()) {
+                        Some(reason) => self.despawn_with_reason(entity, reason.to_string()),
+                        None => self.despawn(entity),
+                    };
+                }
+            } else if let (Some(entity), Some(name)) = (id("set"), component) {
+                let value = record.get("value").ok_or(LogError::Malformed(line_no))?;
+                let patch = self
+                    .registry
+                    .patch_fn(name)
+                    .map_err(|e| LogError::Patch(line_no, e))?;
+                patch(self, entity, value).map_err(|e| LogError::Patch(line_no, e))?(self);
+            } else if let (Some(entity), Some(name)) = (id("remove"), component) {
+                let type_id = self
+                    .registry
+                    .get(name)
+                    .ok_or_else(|| {
+                        LogError::Patch(line_no, PatchError::UnknownComponent(name.to_string()))
+                    })?
+                    .type_id;
+                self.remove_by_type_id(type_id, entity);
+            } else {
+                return Err(LogError::Malformed(line_no));
+            }
+            applied += 1;
+        }
+        Ok(applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    fn new_world() -> World {
+        let mut world = World::new(16);
+        world.registry.register_json::<Position>("Position");
+        world.registry.register_json::<Health>("Health");
+        world.add::<Position>();
+        world.add::<Health>();
+        world
+    }
+
+    #[test]
+    fn rebuilds_logged_world() {
+        let mut world = new_world();
+        let mut log = EventLog::new(Vec::new());
+
+        let a = world
+            .spawn_entity()
+            .with(Position { x: 1.0, y: 2.0 })
+            .with(Health(3))
+            .build();
+        let b = world.spawn_entity().with(Health(1)).build();
+        log.record(&world).unwrap();
+        world.clear_tracked();
+
+        world.despawn_with_reason(b, "killed");
+        let c = world.spawn_entity().with(Health(9)).build();
+        world.remove::<Health>(a);
+        world.component_mut::<Position>(a).unwrap().x = 5.0;
+        log.mutated::<Position>(a);
+        log.record(&world).unwrap();
+        world.clear_tracked();
+
+        let bytes = log.into_inner();
+        assert!(String::from_utf8_lossy(&bytes).contains(r#""reason":"killed""#));
+        let mut rebuilt = new_world();
+        let applied = rebuilt.rebuild_from_log(bytes.as_slice()).unwrap();
+        assert!(applied > 0);
+        assert_eq!(rebuilt.despawned()[0].reason.as_deref(), Some("killed"));
+
+        assert_eq!(
+            rebuilt.component::<Position>(a),
+            Some(&Position { x: 5.0, y: 2.0 })
+        );
+        assert!(!rebuilt.has::<Health>(a));
+        assert!(rebuilt.is_dead(&b));
+        assert_eq!(c.index(), b.index());
+        assert_eq!(rebuilt.component::<Health>(c), Some(&Health(9)));
+        assert_eq!(rebuilt.entity_count(), 2);
+        // Allocation continues where the original left off
+        assert_eq!(rebuilt.spawn(), world.spawn());
+    }
+
+    #[test]
+    fn skips_despawns_of_unknown_entities() {
+        let mut world = new_world();
+        world.set_fault_policy(crate::fault::FaultPolicy::Panic);
+        let log = "{\"despawn\": 3}\n{\"spawn\": 0}\n{\"despawn\": 0}\n{\"despawn\": 0}\n";
+        assert_eq!(world.rebuild_from_log(log.as_bytes()).unwrap(), 4);
+        assert_eq!(world.entity_count(), 0);
+    }
+
+    #[test]
+    fn reports_bad_records() {
+        let mut world = new_world();
+        let log = "{\"spawn\": 0}\n{\"explode\": 0}\n";
+        assert!(matches!(
+            world.rebuild_from_log(log.as_bytes()),
+            Err(LogError::Malformed(2))
+        ));
+    }
+}
@@ -0,0 +1,136 @@
+// Recycled entities stamped from a prefab
+
+use crate::{
+    component::Entity,
+    resource::Resource,
+    template::WorldTemplate,
+    world::{Component, World},
+};
+
+/// Marks an entity parked in a `Pool`. Its components stay in place, so systems should
+/// skip parked entities with a `Without<Pooled>` filter.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pooled;
+
+impl Component for Pooled {}
+
+/// Entities stamped from a one-entity prefab and parked instead of despawned, for things
+/// spawned and killed in bulk like bullets and VFX. Parking only adds a `Pooled` marker,
+/// and acquiring overwrites the prefab's components in place, so neither moves storage
+/// entries or fires insert and remove hooks. Components added after acquiring are kept.
+#[derive(Debug)]
+pub struct Pool {
+    prefab: WorldTemplate,
+    parked: Vec<Entity>,
+}
+
+impl Resource for Pool {}
+
+impl Pool {
+    /// Pools copies of the prefab, e.g. from `World::snapshot_entities([bullet])`.
+    /// Panics unless the prefab holds exactly one entity.
+    pub fn new(prefab: WorldTemplate) -> Self {
+        assert_eq!(
+            prefab.world().entity_count(),
+            1,
+            "pool prefabs must hold exactly one entity"
+        );
+        Self {
+            prefab,
+            parked: Vec::new(),
+        }
+    }
+
+    /// Spawns `count` entities straight into the pool, so the first acquires don't spawn.
+    pub fn prewarm(&mut self, world: &mut World, count: usize) {
+        for _ in 0..count {
+            let entity = world.spawn_template(&self.prefab)[0];
+            self.release(world, entity);
+        }
+    }
+
+    /// Reuses a parked entity, reset to the prefab's components, or spawns a new one.
+    /// Parked entities despawned in the meantime are skipped.
+    pub fn acquire(&mut self, world: &mut World) -> Entity {
+        while let Some(entity) = self.parked.pop() {
+            if world.is_alive(entity) {
+                world.remove::<Pooled>(entity);
+                world.stamp_template(&self.prefab, &[entity]);
+                return entity;
+            }
+        }
+        world.spawn_template(&self.prefab)[0]
+    }
+
+    /// Parks the entity for a later `acquire`, marking it `Pooled`. Releasing a parked
+    /// entity again does nothing.
+    #[track_caller]
+    pub fn release(&mut self, world: &mut World, entity: Entity) {
+        if world.has::<Pooled>(entity) {
+            return;
+        }
+        world.insert(entity, Pooled);
+        self.parked.push(entity);
+    }
+
+    /// Number of parked entities waiting to be reused.
+    pub fn parked(&self) -> usize {
+        self.parked.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Without;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Bullet {
+        speed: f32,
+        travelled: f32,
+    }
+    impl Component for Bullet {}
+
+    #[test]
+    fn released_entities_are_reset_on_acquire() {
+        let mut world = World::new(8);
+        let template = world.spawn();
+        world.insert(
+            template,
+            Bullet {
+                speed: 10.0,
+                travelled: 0.0,
+            },
+        );
+        let mut pool = Pool::new(world.snapshot_entities([template]));
+        world.despawn(template);
+
+        pool.prewarm(&mut world, 2);
+        assert_eq!(pool.parked(), 2);
+        let bullet = pool.acquire(&mut world);
+        assert_eq!(world.entity_count(), 2);
+        world
+            .get_mut::<Bullet>()
+            .unwrap()
+            .get_mut(bullet)
+            .unwrap()
+            .travelled = 50.0;
+
+        pool.release(&mut world, bullet);
+        pool.release(&mut world, bullet);
+        assert_eq!(pool.parked(), 2);
+        let flying = world.query_filtered::<&Bullet, Without<Pooled>>();
+        assert_eq!(flying.into_iter().count(), 0);
+
+        assert_eq!(pool.acquire(&mut world), bullet);
+        assert_eq!(
+            world.component(bullet),
+            Some(&Bullet {
+                speed: 10.0,
+                travelled: 0.0
+            })
+        );
+        assert!(!world.has::<Pooled>(bullet));
+        assert_eq!(world.entity_count(), 2);
+    }
+}
@@ -0,0 +1,1304 @@
+// Fetching several components of one entity at once
+
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt,
+    marker::PhantomData,
+};
+
+use crate::{
+    component::{Entity, Storage},
+    marker::Marker,
+    parallel::Access,
+    world::{Component, World},
+};
+
+/// Components fetched together for one entity: `&T`, `&mut T`, `Option` of either, a
+/// tuple of them, or a struct with `#[derive(QueryData)]`.
+pub trait QueryData {
+    type Item<'w>;
+
+    /// Pointers to the storages `fetch` uses, looked up once per query instead of once
+    /// per entity.
+    type State: Copy;
+
+    /// Records the component types read and written. Panics if a type is written and
+    /// accessed again in the same query, since the items would alias.
+    fn access(access: &mut Access);
+
+    /// Looks up the storages the query reads and writes.
+    fn init_state(world: &QueryWorld<'_>) -> Self::State;
+
+    /// Returns the components if the entity has all of them.
+    ///
+    /// # Safety
+    /// `state` must come from `init_state` on a world made for this query's `access`,
+    /// which stays borrowed for `'w`, and the caller must not hold two items for the same
+    /// entity at once.
+    unsafe fn fetch<'w>(state: Self::State, entity: Entity) -> Option<Self::Item<'w>>;
+
+    /// The smallest storage the query requires, whose entities are the only candidates.
+    /// `None` if it requires none, in which case every live entity is a candidate.
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        let _ = world;
+        None
+    }
+}
+
+/// A storage a query can walk instead of every live entity: its size, and how to list it.
+#[derive(Clone, Copy, Debug)]
+pub struct Driver {
+    pub len: usize,
+    pub entities: fn(&QueryWorld<'_>) -> Vec<Entity>,
+}
+
+impl Driver {
+    /// Drives by `T`'s storage; a missing storage matches nothing.
+    pub fn of<T: Component>(world: &QueryWorld<'_>) -> Self {
+        Self {
+            len: world.world.get::<T>().map_or(0, Storage::len),
+            entities: |world| world.entities_of::<T>(),
+        }
+    }
+
+    /// Picks the driver with the fewest entities.
+    pub fn smallest(drivers: impl IntoIterator<Item = Option<Driver>>) -> Option<Driver> {
+        drivers
+            .into_iter()
+            .flatten()
+            .min_by_key(|driver| driver.len)
+    }
+}
+
+/// The world as a running query sees it: shared access to everything, plus pointers to
+/// the storages it writes, each made unique to this world up front.
+pub struct QueryWorld<'w> {
+    world: &'w World,
+    writable: HashMap<TypeId, *mut dyn Any>,
+}
+
+impl<'w> QueryWorld<'w> {
+    /// For queries through `&World`. Panics if `Q` writes anything.
+    pub(crate) fn read_only<Q: QueryData>(world: &'w World) -> Self {
+        let mut access = Access::new();
+        Q::access(&mut access);
+        assert!(
+            access.writes.is_empty(),
+            "{} writes components, which needs `&mut World`",
+            std::any::type_name::<Q>()
+        );
+        Self {
+            world,
+            writable: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn new<Q: QueryData>(world: &'w mut World) -> Self {
+        let mut access = Access::new();
+        Q::access(&mut access);
+        let writable = world.storage_ptrs(&access.writes);
+        Self { world, writable }
+    }
+
+    /// Shared access only, for drivers and filters.
+    fn shared(world: &'w World) -> Self {
+        Self {
+            world,
+            writable: HashMap::new(),
+        }
+    }
+
+    pub fn world(&self) -> &'w World {
+        self.world
+    }
+
+    /// `T`'s storage, if the world has one.
+    pub fn storage<T: Component>(&self) -> Option<*const Storage<T>> {
+        self.world
+            .get::<T>()
+            .map(|storage| storage as *const Storage<T>)
+    }
+
+    /// `T`'s storage, if the world has one and the query was made to write it.
+    pub fn storage_mut<T: Component>(&self) -> Option<*mut Storage<T>> {
+        let storage = *self.writable.get(&TypeId::of::<T>())?;
+        Some(storage as *mut Storage<T>)
+    }
+
+    /// The entities holding a `T`, collected so no borrow of the storage outlives the call.
+    pub fn entities_of<T: Component>(&self) -> Vec<Entity> {
+        self.world
+            .get::<T>()
+            .map_or_else(Vec::new, |storage| storage.entities().collect())
+    }
+}
+
+impl QueryData for () {
+    type Item<'w> = ();
+    type State = ();
+
+    fn access(_: &mut Access) {}
+
+    fn init_state(_: &QueryWorld<'_>) {}
+
+    unsafe fn fetch<'w>(_: (), _: Entity) -> Option<Self::Item<'w>> {
+        Some(())
+    }
+}
+
+impl<T: Component> QueryData for &T {
+    type Item<'w> = &'w T;
+    type State = Option<*const Storage<T>>;
+
+    fn access(access: &mut Access) {
+        access.add_query_read::<T>();
+    }
+
+    fn init_state(world: &QueryWorld<'_>) -> Self::State {
+        world.storage::<T>()
+    }
+
+    unsafe fn fetch<'w>(state: Self::State, entity: Entity) -> Option<&'w T> {
+        // SAFETY: the world is borrowed for 'w and nothing writes this storage meanwhile
+        unsafe { (*state?).get(entity) }
+    }
+
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        Some(Driver::of::<T>(world))
+    }
+}
+
+impl<T: Component> QueryData for &mut T {
+    type Item<'w> = &'w mut T;
+    type State = Option<*mut Storage<T>>;
+
+    fn access(access: &mut Access) {
+        access.add_query_write::<T>();
+    }
+
+    fn init_state(world: &QueryWorld<'_>) -> Self::State {
+        world.storage_mut::<T>()
+    }
+
+    unsafe fn fetch<'w>(state: Self::State, entity: Entity) -> Option<&'w mut T> {
+        // SAFETY: the storage is unique to this world and only this query writes it; the
+        // caller doesn't fetch the same entity twice, so the value isn't aliased.
+        unsafe { Storage::value_ptr(state?, entity).map(|value| &mut *value) }
+    }
+
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        Some(Driver::of::<T>(world))
+    }
+}
+
+/// Fetches `Q` when the entity has it, without requiring it, e.g. `Option<&Armor>`.
+impl<Q: QueryData> QueryData for Option<Q> {
+    type Item<'w> = Option<Q::Item<'w>>;
+    type State = Q::State;
+
+    fn access(access: &mut Access) {
+        Q::access(access);
+    }
+
+    fn init_state(world: &QueryWorld<'_>) -> Self::State {
+        Q::init_state(world)
+    }
+
+    unsafe fn fetch<'w>(state: Self::State, entity: Entity) -> Option<Self::Item<'w>> {
+        unsafe { Some(Q::fetch(state, entity)) }
+    }
+}
+
+macro_rules! impl_query_data_tuple {
+    ($($ty:ident),+) => {
+        impl<$($ty: QueryData),+> QueryData for ($($ty,)+) {
+            type Item<'w> = ($($ty::Item<'w>,)+);
+            type State = ($($ty::State,)+);
+
+            fn access(access: &mut Access) {
+                $($ty::access(access);)+
+            }
+
+            fn init_state(world: &QueryWorld<'_>) -> Self::State {
+                ($($ty::init_state(world),)+)
+            }
+
+            #[allow(non_snake_case)]
+            unsafe fn fetch<'w>(state: Self::State, entity: Entity) -> Option<Self::Item<'w>> {
+                let ($($ty,)+) = state;
+                unsafe { Some(($($ty::fetch($ty, entity)?,)+)) }
+            }
+
+            fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+                Driver::smallest([$($ty::driver(world)),+])
+            }
+        }
+    };
+}
+
+impl_query_data_tuple!(A);
+impl_query_data_tuple!(A, B);
+impl_query_data_tuple!(A, B, C);
+impl_query_data_tuple!(A, B, C, D);
+impl_query_data_tuple!(A, B, C, D, E);
+impl_query_data_tuple!(A, B, C, D, E, F);
+impl_query_data_tuple!(A, B, C, D, E, F, G);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H, I);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// A condition on which entities a query visits that fetches nothing, e.g.
+/// `(With<Player>, Without<Dead>)`. Tuples of filters match when all of them do.
+pub trait QueryFilter {
+    /// Records the component types checked.
+    fn access(access: &mut Access);
+
+    fn matches(world: &World, entity: Entity) -> bool;
+
+    /// The smallest storage the filter requires, if any; see `QueryData::driver`.
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        let _ = world;
+        None
+    }
+}
+
+/// Filter matching entities that have a `T`, without fetching it.
+pub struct With<T>(PhantomData<T>);
+
+/// Filter matching entities that don't have a `T`.
+pub struct Without<T>(PhantomData<T>);
+
+impl QueryFilter for () {
+    fn access(_: &mut Access) {}
+
+    fn matches(_: &World, _: Entity) -> bool {
+        true
+    }
+}
+
+impl<T: Component> QueryFilter for With<T> {
+    fn access(access: &mut Access) {
+        access.add_filter_read::<T>();
+    }
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        world.has::<T>(entity)
+    }
+
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        Some(Driver::of::<T>(world))
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    fn access(access: &mut Access) {
+        access.add_filter_read::<T>();
+    }
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        !world.has::<T>(entity)
+    }
+}
+
+/// Filter matching entities marked with `T` by `World::mark`.
+pub struct Marked<T>(PhantomData<T>);
+
+/// Filter matching entities not marked with `T`.
+pub struct Unmarked<T>(PhantomData<T>);
+
+impl<T: Marker> QueryFilter for Marked<T> {
+    fn access(access: &mut Access) {
+        access.add_filter_read::<T>();
+    }
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        world.is_marked::<T>(entity)
+    }
+
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        Some(Driver {
+            len: world.world.marked_count::<T>(),
+            entities: |world| world.world.marked::<T>().collect(),
+        })
+    }
+}
+
+impl<T: Marker> QueryFilter for Unmarked<T> {
+    fn access(access: &mut Access) {
+        access.add_filter_read::<T>();
+    }
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        !world.is_marked::<T>(entity)
+    }
+}
+
+/// A tag known at compile time, so it can be named in a query's filter type as
+/// `Tagged<Enemy>`. Declare one with `tag_name!(pub Enemy = "enemy");`.
+pub trait TagName: 'static {
+    const NAME: &'static str;
+}
+
+/// Declares a unit struct implementing `TagName`, e.g. `tag_name!(pub Enemy = "enemy");`.
+#[macro_export]
+macro_rules! tag_name {
+    ($vis:vis $name:ident = $tag:literal) => {
+        $vis struct $name;
+
+        impl $crate::query::TagName for $name {
+            const NAME: &'static str = $tag;
+        }
+    };
+}
+
+/// Filter matching entities with the tag `T::NAME`, e.g.
+/// `world.query_filtered::<&Position, Tagged<Enemy>>()`. Narrow tags drive the query, so
+/// only the tagged entities are visited.
+pub struct Tagged<T>(PhantomData<T>);
+
+/// Filter matching entities without the tag `T::NAME`.
+pub struct Untagged<T>(PhantomData<T>);
+
+impl<T: TagName> QueryFilter for Tagged<T> {
+    // Tags sit behind their own lock, so they need no access entry
+    fn access(_: &mut Access) {}
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        world.tags.has_tag(T::NAME, &entity)
+    }
+
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        Some(Driver {
+            len: world.world.tags.count(T::NAME),
+            entities: |world| {
+                world
+                    .world
+                    .tags
+                    .get_entities_with_tag(T::NAME)
+                    .unwrap_or_default()
+            },
+        })
+    }
+}
+
+impl<T: TagName> QueryFilter for Untagged<T> {
+    fn access(_: &mut Access) {}
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        !world.tags.has_tag(T::NAME, &entity)
+    }
+}
+
+/// Filter matching entities whose `T` was added after `World::last_change_tick`, which
+/// under a `Schedule` is when the running system last ran.
+pub struct Added<T>(PhantomData<T>);
+
+/// Filter matching entities whose `T` was added or mutably accessed after
+/// `World::last_change_tick`. Any `&mut` access counts, even if the value didn't change.
+pub struct Changed<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for Added<T> {
+    fn access(access: &mut Access) {
+        access.add_filter_read::<T>();
+    }
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        let since = world.last_change_tick();
+        world
+            .get::<T>()
+            .and_then(|storage| storage.ticks(entity))
+            .is_some_and(|ticks| ticks.is_added(since))
+    }
+
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        Some(Driver::of::<T>(world))
+    }
+}
+
+impl<T: Component> QueryFilter for Changed<T> {
+    fn access(access: &mut Access) {
+        access.add_filter_read::<T>();
+    }
+
+    fn matches(world: &World, entity: Entity) -> bool {
+        let since = world.last_change_tick();
+        world
+            .get::<T>()
+            .and_then(|storage| storage.ticks(entity))
+            .is_some_and(|ticks| ticks.is_changed(since))
+    }
+
+    fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+        Some(Driver::of::<T>(world))
+    }
+}
+
+macro_rules! impl_query_filter_tuple {
+    ($($ty:ident),+) => {
+        impl<$($ty: QueryFilter),+> QueryFilter for ($($ty,)+) {
+            fn access(access: &mut Access) {
+                $($ty::access(access);)+
+            }
+
+            fn matches(world: &World, entity: Entity) -> bool {
+                $($ty::matches(world, entity))&&+
+            }
+
+            fn driver(world: &QueryWorld<'_>) -> Option<Driver> {
+                Driver::smallest([$($ty::driver(world)),+])
+            }
+        }
+    };
+}
+
+impl_query_filter_tuple!(A);
+impl_query_filter_tuple!(A, B);
+impl_query_filter_tuple!(A, B, C);
+impl_query_filter_tuple!(A, B, C, D);
+impl_query_filter_tuple!(A, B, C, D, E);
+impl_query_filter_tuple!(A, B, C, D, E, F);
+impl_query_filter_tuple!(A, B, C, D, E, F, G);
+impl_query_filter_tuple!(A, B, C, D, E, F, G, H);
+
+/// The entities with every component `Q` asks for that pass the filter `F`, from
+/// `World::query` or `World::query_filtered`. Loop over it directly, or keep it to call
+/// `iter` more than once and look up single entities with `get`/`get_mut`.
+pub struct Query<'w, Q: QueryData, F: QueryFilter = ()> {
+    world: &'w World,
+    state: Q::State,
+    writes: bool,
+    marker: PhantomData<fn() -> F>,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Query<'w, Q, F> {
+    pub(crate) fn new(world: QueryWorld<'w>) -> Self {
+        let mut access = Access::new();
+        Q::access(&mut access);
+        Self {
+            world: world.world,
+            state: Q::init_state(&world),
+            writes: !access.writes.is_empty(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Iterates the matches. The query stays usable afterwards.
+    pub fn iter(&mut self) -> QueryIter<'_, Q, F> {
+        QueryIter::new(self.world, self.state)
+    }
+
+    /// The components of one entity, if it matches, e.g. the entity tagged `"player"`.
+    /// Panics if `Q` writes anything; use `get_mut` for those.
+    pub fn get(&self, entity: Entity) -> Option<Q::Item<'_>> {
+        assert!(
+            !self.writes,
+            "{} writes components, which needs `get_mut`",
+            std::any::type_name::<Q>()
+        );
+        // SAFETY: nothing is written, so items can't alias
+        unsafe { self.fetch(entity) }
+    }
+
+    /// The components of one entity, if it matches, borrowing the query until they're
+    /// dropped.
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        // SAFETY: the item borrows the query exclusively, so no other item is alive
+        unsafe { self.fetch(entity) }
+    }
+
+    /// # Safety
+    /// The caller must not hold another item for the same entity if `Q` writes.
+    unsafe fn fetch(&self, entity: Entity) -> Option<Q::Item<'_>> {
+        if self.world.is_dead(&entity) || !F::matches(self.world, entity) {
+            return None;
+        }
+        unsafe { Q::fetch(self.state, entity) }
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> IntoIterator for Query<'w, Q, F> {
+    type Item = (Entity, Q::Item<'w>);
+    type IntoIter = QueryIter<'w, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        QueryIter::new(self.world, self.state)
+    }
+}
+
+/// A query kept between frames, e.g. in a system's struct, so its storage lookups and
+/// access checks are done once rather than on every run. The cached storage pointers are
+/// reused until the world adds a storage or is forked, or another world is passed in.
+pub struct QueryState<Q: QueryData, F: QueryFilter = ()> {
+    access: Access,
+    cache: Option<CachedQuery<Q::State>>,
+    marker: PhantomData<fn() -> F>,
+}
+
+type SetTick = fn(&mut dyn Any, u64);
+
+struct CachedQuery<S> {
+    layout: (u64, u64),
+    state: S,
+    /// Storages written, with how to stamp them with the current change tick.
+    writes: Vec<(*mut dyn Any, SetTick)>,
+}
+
+// SAFETY: the pointers are only followed while `QueryState::query` borrows their world
+// mutably, and the world is `Send + Sync`.
+unsafe impl<S> Send for CachedQuery<S> {}
+unsafe impl<S> Sync for CachedQuery<S> {}
+
+impl<Q: QueryData, F: QueryFilter> Default for QueryState<Q, F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Q: QueryData, F: QueryFilter> fmt::Debug for QueryState<Q, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryState")
+            .field("query", &std::any::type_name::<Q>())
+            .field("cached", &self.cache.is_some())
+            .finish()
+    }
+}
+
+impl<Q: QueryData, F: QueryFilter> QueryState<Q, F> {
+    /// Checks `Q`'s access up front, panicking if a component would alias.
+    pub fn new() -> Self {
+        let mut access = Access::new();
+        Q::access(&mut access);
+        Self {
+            access,
+            cache: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Queries `world`, reusing the storage pointers found last time if they're still
+    /// valid. Every storage `Q` touches is copied up front if shared with another world,
+    /// since a cached pointer can't follow a later copy.
+    pub fn query<'w>(&mut self, world: &'w mut World) -> Query<'w, Q, F> {
+        let layout = world.layout_key();
+        match &self.cache {
+            Some(cache) if cache.layout == layout => {
+                let tick = world.change_tick();
+                for &(storage, set_tick) in &cache.writes {
+                    // SAFETY: the storage is unique to this world, which is borrowed mutably
+                    set_tick(unsafe { &mut *storage }, tick);
+                }
+            }
+            _ => {
+                let touched = &self.access.reads | &self.access.writes;
+                let mut pointers = world.storage_ptrs(&touched);
+                let set_tick = world.tick_fns(&self.access.writes);
+                pointers.retain(|key, _| self.access.writes.contains(key));
+                let writes = pointers
+                    .iter()
+                    .map(|(key, &storage)| (storage, set_tick[key]))
+                    .collect();
+                let state = Q::init_state(&QueryWorld {
+                    world,
+                    writable: pointers,
+                });
+                self.cache = Some(CachedQuery {
+                    layout,
+                    state,
+                    writes,
+                });
+            }
+        }
+        let cache = self.cache.as_ref().expect("cache was just filled");
+        Query {
+            world,
+            state: cache.state,
+            writes: !self.access.writes.is_empty(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Scratch space for collecting query matches, kept in a system's struct next to its
+/// `QueryState` so sorting or two-phase logic doesn't reallocate every frame:
+/// `let mut matches = self.buffer.collect(query.iter()); matches.sort_by_key(...)`.
+pub struct QueryBuffer<Q: QueryData> {
+    /// Always empty between `collect` calls; only the capacity is kept.
+    items: Vec<(Entity, Q::Item<'static>)>,
+}
+
+impl<Q: QueryData> Default for QueryBuffer<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Q: QueryData> fmt::Debug for QueryBuffer<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryBuffer")
+            .field("query", &std::any::type_name::<Q>())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<Q: QueryData> QueryBuffer<Q> {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    /// Matches the buffer can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+
+    /// Collects matches, e.g. from `Query::iter` or `QueryIter::every_nth`, into the
+    /// buffer's retained allocation.
+    pub fn collect<'b, 'w>(
+        &'b mut self,
+        matches: impl IntoIterator<Item = (Entity, Q::Item<'w>)>,
+    ) -> Collected<'b, 'w, Q> {
+        // SAFETY: shrinking only leaks, and leaks anything left by a forgotten `Collected`
+        // rather than dropping items whose borrows may have ended
+        unsafe { self.items.set_len(0) };
+        let items = &mut self.items as *mut Vec<(Entity, Q::Item<'static>)>;
+        // SAFETY: the items differ only in lifetime, so the layouts match, and the Vec is
+        // empty; `Collected` empties it again before the `'w` borrows can end
+        let items = unsafe { &mut *items.cast::<Vec<(Entity, Q::Item<'w>)>>() };
+        items.extend(matches);
+        Collected { items }
+    }
+}
+
+impl<Q: QueryData> Drop for QueryBuffer<Q> {
+    fn drop(&mut self) {
+        // SAFETY: as in `collect`, leak rather than drop leftovers
+        unsafe { self.items.set_len(0) };
+    }
+}
+
+/// Matches held in a `QueryBuffer`, as a slice of `(entity, item)` pairs to sort and walk.
+/// Emptied when dropped, so the buffer keeps only its capacity.
+pub struct Collected<'b, 'w, Q: QueryData> {
+    items: &'b mut Vec<(Entity, Q::Item<'w>)>,
+}
+
+impl<'w, Q: QueryData> std::ops::Deref for Collected<'_, 'w, Q> {
+    type Target = [(Entity, Q::Item<'w>)];
+
+    fn deref(&self) -> &Self::Target {
+        self.items
+    }
+}
+
+impl<Q: QueryData> std::ops::DerefMut for Collected<'_, '_, Q> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.items
+    }
+}
+
+impl<Q: QueryData> Drop for Collected<'_, '_, Q> {
+    fn drop(&mut self) {
+        self.items.clear();
+    }
+}
+
+/// Iterator over a `Query`'s matches.
+/// Walks the smallest required storage and fetches the rest for each of its entities, so
+/// joining a rare component with a common one costs as much as the rare one.
+pub struct QueryIter<'w, Q: QueryData, F: QueryFilter = ()> {
+    world: &'w World,
+    state: Q::State,
+    entities: std::vec::IntoIter<Entity>,
+    marker: PhantomData<fn() -> F>,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> QueryIter<'w, Q, F> {
+    fn new(world: &'w World, state: Q::State) -> Self {
+        let shared = QueryWorld::shared(world);
+        let entities = match Driver::smallest([Q::driver(&shared), F::driver(&shared)]) {
+            Some(driver) => (driver.entities)(&shared),
+            None => world.entities().collect(),
+        };
+        Self {
+            world,
+            state,
+            entities: entities.into_iter(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Keeps one of `n` buckets of entities, picked by `frame`, so each entity is visited
+    /// once every `n` frames. Pass a counter that goes up by one each frame. Spreads
+    /// expensive per-entity work, e.g. AI, across frames.
+    pub fn every_nth(mut self, n: usize, frame: u64) -> Self {
+        assert!(n > 0, "every_nth needs at least one bucket");
+        let bucket = (frame % n as u64) as usize;
+        let entities: Vec<Entity> = self
+            .entities
+            .filter(|entity| entity.index() % n == bucket)
+            .collect();
+        self.entities = entities.into_iter();
+        self
+    }
+
+    /// Visits entities by the first of `tags` they have, in order, then those with none of
+    /// them, e.g. `.by_tag_priority(&["phase:early", "phase:late"])`. Order within each
+    /// group is unchanged. Reorders the entity list in place rather than sorting matches.
+    pub fn by_tag_priority(mut self, tags: &[&'static str]) -> Self {
+        let mut entities: Vec<Entity> = self.entities.collect();
+        self.world.tags.order_by_priority(tags, &mut entities);
+        self.entities = entities.into_iter();
+        self
+    }
+
+    /// The `budget` matches with the highest `key`, highest first, e.g. keyed by how many
+    /// frames an entity has waited, so the stalest are always served. Fetches every match
+    /// to compare them.
+    pub fn most_urgent<K: Ord>(
+        self,
+        budget: usize,
+        mut key: impl FnMut(&Q::Item<'w>) -> K,
+    ) -> std::vec::IntoIter<(Entity, Q::Item<'w>)> {
+        let mut keyed: Vec<_> = self
+            .map(|(entity, item)| (key(&item), entity, item))
+            .collect();
+        if budget < keyed.len() {
+            keyed.select_nth_unstable_by(budget, |a, b| b.0.cmp(&a.0));
+            keyed.truncate(budget);
+        }
+        keyed.sort_by(|a, b| b.0.cmp(&a.0));
+        let urgent: Vec<_> = keyed
+            .into_iter()
+            .map(|(_, entity, item)| (entity, item))
+            .collect();
+        urgent.into_iter()
+    }
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'w, Q, F> {
+    type Item = (Entity, Q::Item<'w>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entity in self.entities.by_ref() {
+            if !F::matches(self.world, entity) {
+                continue;
+            }
+            // SAFETY: the state was made for `Q`, and each entity is visited once
+            if let Some(item) = unsafe { Q::fetch(self.state, entity) } {
+                return Some((entity, item));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.entities.len()))
+    }
+}
+
+/// Filter matching entities that gained a `T` since the last `clear_tracked`.
+/// Walks `T`'s `added` list, so the cost follows the number of new components.
+pub struct Spawned<T>(PhantomData<T>);
+
+impl<T: Component> Spawned<T> {
+    /// Joins the added list with `Q`, skipping entities that lost `T` again or lack any of `Q`.
+    /// `Q` must only read.
+    pub fn iter<Q: QueryData>(world: &World) -> impl Iterator<Item = (Entity, Q::Item<'_>)> {
+        let state = Q::init_state(&QueryWorld::read_only::<Q>(world));
+        let mut seen = HashSet::new();
+        world
+            .get::<T>()
+            .into_iter()
+            .flat_map(|set| set.added.iter().copied().filter(|&e| set.has(e)))
+            .filter(move |&e| seen.insert(e))
+            // SAFETY: read-only, so items can't alias
+            .filter_map(move |e| Some((e, unsafe { Q::fetch(state, e)? })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Renderable;
+    impl Component for Renderable {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    #[test]
+    fn spawned_joins_added_with_query() {
+        let mut world = World::new(8);
+        let old = world
+            .spawn_entity()
+            .with(Renderable)
+            .with(Position(0))
+            .build();
+        world.clear_tracked();
+
+        let a = world
+            .spawn_entity()
+            .with(Renderable)
+            .with(Position(1))
+            .build();
+        let no_position = world.spawn_entity().with(Renderable).build();
+        let gone = world
+            .spawn_entity()
+            .with(Renderable)
+            .with(Position(3))
+            .build();
+        world.remove::<Renderable>(gone);
+        world.remove::<Renderable>(a);
+        world.insert(a, Renderable);
+
+        let found: Vec<_> = world
+            .spawned::<Renderable, (&Renderable, &Position)>()
+            .map(|(e, (_, pos))| (e, *pos))
+            .collect();
+        assert_eq!(found, vec![(a, Position(1))]);
+        assert_eq!(world.spawned::<Renderable, ()>().count(), 2);
+        assert!(!found.iter().any(|&(e, _)| e == old || e == no_position));
+    }
+
+    #[test]
+    fn fetch_mut_writes_through() {
+        let mut world = World::new(8);
+        let e = world
+            .spawn_entity()
+            .with(Position(1))
+            .with(Velocity(2))
+            .build();
+        let shared = world.clone();
+
+        let (pos, vel) = world.fetch_mut::<(&mut Position, &Velocity)>(e).unwrap();
+        pos.0 += vel.0;
+        assert_eq!(world.fetch::<(&Position,)>(e), Some((&Position(3),)));
+        // The clone shared the storage, which was copied before the write
+        assert_eq!(shared.component::<Position>(e), Some(&Position(1)));
+        assert!(world.fetch::<&Renderable>(e).is_none());
+    }
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Armor(i32);
+    impl Component for Armor {}
+
+    #[test]
+    fn optional_components_dont_filter() {
+        let mut world = World::new(8);
+        let bare = world.spawn_entity().with(Position(10)).build();
+        let armored = world
+            .spawn_entity()
+            .with(Position(10))
+            .with(Armor(4))
+            .build();
+        world.spawn_entity().with(Armor(1)).build();
+
+        for (_, (pos, armor, vel)) in
+            world.query::<(&mut Position, Option<&Armor>, Option<&mut Velocity>)>()
+        {
+            pos.0 -= armor.map_or(0, |armor| armor.0);
+            assert!(vel.is_none());
+        }
+        assert_eq!(world.component::<Position>(bare), Some(&Position(10)));
+        assert_eq!(world.component::<Position>(armored), Some(&Position(6)));
+
+        // With nothing required, every live entity matches
+        assert_eq!(world.query::<Option<&Position>>().iter().count(), 3);
+    }
+
+    #[derive(crate::QueryData)]
+    struct MoveQuery<'a> {
+        pos: &'a mut Position,
+        vel: &'a Velocity,
+    }
+
+    #[test]
+    fn derived_query_data_has_named_fields() {
+        let mut world = World::new(8);
+        let e = world
+            .spawn_entity()
+            .with(Position(1))
+            .with(Velocity(2))
+            .build();
+
+        let MoveQuery { pos, vel } = world.fetch_mut::<MoveQuery>(e).unwrap();
+        pos.0 += vel.0;
+        assert_eq!(world.component::<Position>(e), Some(&Position(3)));
+
+        let access = Access::new().query::<MoveQuery>();
+        assert!(access.conflicts(&Access::new().read::<Position>()));
+        assert!(!access.conflicts(&Access::new().read::<Velocity>()));
+    }
+
+    #[test]
+    fn query_joins_storages() {
+        let mut world = World::new(8);
+        let moving = world
+            .spawn_entity()
+            .with(Position(1))
+            .with(Velocity(2))
+            .build();
+        world.spawn_entity().with(Position(5)).build();
+        world.spawn_entity().with(Velocity(7)).build();
+
+        for (_, (pos, vel)) in world.query::<(&mut Position, &Velocity)>() {
+            pos.0 += vel.0;
+        }
+        let moved: Vec<_> = world
+            .query::<(&Velocity, &Position)>()
+            .iter()
+            .map(|(e, (_, pos))| (e, *pos))
+            .collect();
+        assert_eq!(moved, vec![(moving, Position(3))]);
+        assert_eq!(world.query::<&Position>().iter().count(), 2);
+        assert_eq!(world.query::<()>().iter().count(), 3);
+        assert_eq!(world.query::<&Renderable>().iter().count(), 0);
+
+        let MoveQuery { pos, .. } = world.query::<MoveQuery>().into_iter().next().unwrap().1;
+        assert_eq!(*pos, Position(3));
+        let wide = world.query::<(
+            &Position,
+            &Velocity,
+            (),
+            &Position,
+            &Velocity,
+            (),
+            &Position,
+            &Velocity,
+            (),
+            &Position,
+            &Velocity,
+            &mut Renderable,
+        )>();
+        assert_eq!(wide.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn single_entities_can_be_looked_up() {
+        let mut world = World::new(8);
+        let spawned = world
+            .spawn_entity()
+            .with(Position(1))
+            .with(Velocity(2))
+            .build();
+        let still = world.spawn_entity().with(Position(5)).build();
+        world.tags.add_tag("player", spawned);
+
+        let player = world.tags.expect_one("player");
+        let mut query = world.query::<(&mut Position, &Velocity)>();
+        let (pos, vel) = query.get_mut(player).unwrap();
+        pos.0 += vel.0;
+        assert!(query.get_mut(still).is_none());
+        assert_eq!(query.iter().count(), 1);
+        assert_eq!(world.component::<Position>(player), Some(&Position(3)));
+
+        let query = world.query_filtered::<&Position, Without<Velocity>>();
+        assert_eq!(query.get(still), Some(&Position(5)));
+        assert_eq!(query.get(player), None);
+        world.despawn(still);
+        assert!(world.query::<&Position>().get(still).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "needs `get_mut`")]
+    fn get_refuses_writes() {
+        let mut world = World::new(8);
+        let e = world.spawn_entity().with(Position(1)).build();
+        world.query::<&mut Position>().get(e);
+    }
+
+    #[test]
+    fn query_state_is_reused_across_frames() {
+        let mut world = World::new(8);
+        let e = world
+            .spawn_entity()
+            .with(Position(0))
+            .with(Velocity(1))
+            .build();
+        let mut state = QueryState::<(&mut Position, &Velocity)>::new();
+        let mut changed = QueryState::<&Position, Changed<Position>>::new();
+
+        for frame in 1..=3 {
+            let last = world.change_tick();
+            world.set_last_change_tick(last);
+            world.increment_change_tick();
+            for (_, (pos, vel)) in state.query(&mut world) {
+                pos.0 += vel.0;
+            }
+            // Writes through the cached pointers still get this frame's tick
+            assert_eq!(changed.query(&mut world).iter().count(), 1);
+            assert_eq!(world.component::<Position>(e), Some(&Position(frame)));
+        }
+
+        // A fork shares the storages, so the next run copies before writing again
+        let before = world.clone();
+        state.query(&mut world).get_mut(e).unwrap().0.0 = 10;
+        assert_eq!(before.component::<Position>(e), Some(&Position(3)));
+        assert_eq!(world.component::<Position>(e), Some(&Position(10)));
+
+        // Storages added later are picked up
+        let mut armored = QueryState::<(&Position, Option<&Armor>)>::new();
+        assert_eq!(
+            armored.query(&mut world).get(e),
+            Some((&Position(10), None))
+        );
+        world.insert(e, Armor(2));
+        assert_eq!(
+            armored.query(&mut world).get(e),
+            Some((&Position(10), Some(&Armor(2))))
+        );
+
+        let mut other = World::new(8);
+        assert_eq!(state.query(&mut other).iter().count(), 0);
+    }
+
+    #[test]
+    fn work_can_be_spread_across_frames() {
+        let mut world = World::new(16);
+        for i in 0..10 {
+            world.spawn_entity().with(Position(i)).build();
+        }
+
+        let mut visits = [0; 10];
+        for frame in 0..8 {
+            for (_, pos) in world.query::<&Position>().iter().every_nth(4, frame) {
+                visits[pos.0 as usize] += 1;
+            }
+        }
+        assert_eq!(visits, [2; 10]);
+        assert_eq!(world.query::<&Position>().iter().every_nth(4, 1).count(), 3);
+
+        let urgent: Vec<_> = world
+            .query::<&Position>()
+            .iter()
+            .most_urgent(3, |pos| pos.0 % 7)
+            .map(|(_, pos)| pos.0)
+            .collect();
+        assert_eq!(urgent, vec![6, 5, 4]);
+        assert_eq!(
+            world
+                .query::<&Position>()
+                .iter()
+                .most_urgent(20, |_| 0)
+                .len(),
+            10
+        );
+    }
+
+    #[test]
+    fn tag_filters_compose_with_components() {
+        crate::tag_name!(Enemy = "enemy");
+        crate::tag_name!(Boss = "boss");
+
+        let mut world = World::new(8);
+        let grunt = world.spawn_entity().with(Position(1)).tag("enemy").build();
+        let boss = world.spawn_entity().with(Position(2)).tag("enemy").build();
+        world.tags.add_tag("boss", boss);
+        world.spawn_entity().with(Position(3)).build();
+        world.spawn_entity().tag("enemy").build();
+
+        let mut enemies: Vec<_> = world
+            .query_filtered::<&Position, Tagged<Enemy>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        enemies.sort();
+        assert_eq!(enemies, vec![grunt, boss]);
+        let minions: Vec<_> = world
+            .query_filtered::<&Position, (Tagged<Enemy>, Untagged<Boss>)>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(minions, vec![grunt]);
+    }
+
+    #[test]
+    fn tag_priority_orders_matches() {
+        let mut world = World::new(16);
+        let entities: Vec<Entity> = (0..6)
+            .map(|i| world.spawn_entity().with(Position(i)).build())
+            .collect();
+        world.tags.add_tag("phase:late", entities[1]);
+        world.tags.add_tag("phase:early", entities[4]);
+        world.tags.add_tag("phase:late", entities[4]);
+        world.tags.add_tag("phase:early", entities[5]);
+
+        let order: Vec<i32> = world
+            .query::<&Position>()
+            .iter()
+            .by_tag_priority(&["phase:early", "phase:late"])
+            .map(|(_, pos)| pos.0)
+            .collect();
+        assert_eq!(order, vec![4, 5, 1, 0, 2, 3]);
+    }
+
+    #[test]
+    fn query_buffers_keep_capacity() {
+        let mut world = World::new(16);
+        for i in 0..8 {
+            world.spawn_entity().with(Position(i)).build();
+        }
+        let mut buffer = QueryBuffer::<&mut Position>::new();
+        for _ in 0..2 {
+            let mut query = world.query::<&mut Position>();
+            let mut matches = buffer.collect(query.iter());
+            matches.sort_by_key(|(_, pos)| std::cmp::Reverse(pos.0));
+            for (rank, (_, pos)) in matches.iter_mut().enumerate() {
+                pos.0 = rank as i32;
+            }
+        }
+        let capacity = buffer.capacity();
+        assert!(capacity >= 8);
+
+        let ranks: Vec<i32> = world
+            .query::<&Position>()
+            .iter()
+            .map(|(_, pos)| pos.0)
+            .collect();
+        assert_eq!(ranks, (0..8).collect::<Vec<_>>());
+        let query = world.query::<&mut Position>();
+        let matches = buffer.collect(query.into_iter().every_nth(2, 0));
+        assert_eq!(matches.len(), 4);
+        drop(matches);
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn joins_walk_the_smallest_storage() {
+        let mut world = World::new(16);
+        for i in 0..10 {
+            world.spawn_entity().with(Position(i)).build();
+        }
+        let rare = world
+            .spawn_entity()
+            .with(Position(0))
+            .with(Velocity(1))
+            .build();
+
+        let query = world.query::<(&Position, &Velocity)>().into_iter();
+        assert_eq!(query.size_hint(), (0, Some(1)));
+        assert_eq!(query.map(|(e, _)| e).collect::<Vec<_>>(), vec![rare]);
+        let mut query = world.query_filtered::<&Position, With<Velocity>>();
+        assert_eq!(query.iter().size_hint(), (0, Some(1)));
+        assert_eq!(world.query::<MoveQuery>().iter().size_hint(), (0, Some(1)));
+        // A missing storage matches nothing
+        assert_eq!(
+            world.query::<(&Position, &Renderable)>().iter().size_hint(),
+            (0, Some(0))
+        );
+    }
+
+    #[test]
+    fn filters_check_presence() {
+        let mut world = World::new(8);
+        let a = world
+            .spawn_entity()
+            .with(Position(1))
+            .with(Renderable)
+            .build();
+        let b = world.spawn_entity().with(Position(2)).build();
+        let c = world
+            .spawn_entity()
+            .with(Position(3))
+            .with(Velocity(0))
+            .build();
+
+        let visible: Vec<_> = world
+            .query_filtered::<&Position, With<Renderable>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(visible, vec![a]);
+        let still: Vec<_> = world
+            .query_filtered::<(), (Without<Velocity>, Without<Renderable>)>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(still, vec![b]);
+        for (_, pos) in world.query_filtered::<&mut Position, (With<Velocity>,)>() {
+            pos.0 = 0;
+        }
+        assert_eq!(world.component::<Position>(c), Some(&Position(0)));
+        assert_eq!(
+            world.query_filtered::<(), With<Velocity>>().iter().count(),
+            1
+        );
+
+        let access = Access::new()
+            .query::<&Position>()
+            .filter::<With<Velocity>>();
+        assert!(access.conflicts(&Access::new().write::<Velocity>()));
+    }
+
+    #[test]
+    fn added_and_changed_follow_ticks() {
+        let mut world = World::new(8);
+        let a = world.spawn_entity().with(Position(1)).build();
+        let b = world.spawn_entity().with(Position(2)).build();
+        assert_eq!(
+            world.query_filtered::<(), Added<Position>>().iter().count(),
+            2
+        );
+
+        let since = world.change_tick();
+        world.increment_change_tick();
+        world.set_last_change_tick(since);
+        let c = world.spawn_entity().with(Position(3)).build();
+        world.get_mut::<Position>().unwrap().get_mut(a).unwrap().0 += 1;
+        world
+            .get_mut::<Position>()
+            .unwrap()
+            .get_mut_untracked(b)
+            .unwrap()
+            .0 += 1;
+
+        let added: Vec<_> = world
+            .query_filtered::<(), Added<Position>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(added, vec![c]);
+        let changed: Vec<_> = world
+            .query_filtered::<&Position, Changed<Position>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(changed, vec![a, c]);
+
+        // Fetching `&mut` counts as a change
+        let since = world.change_tick();
+        world.increment_change_tick();
+        world.set_last_change_tick(since);
+        world.fetch_mut::<&mut Position>(b);
+        let changed: Vec<_> = world
+            .query_filtered::<(), Changed<Position>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect();
+        assert_eq!(changed, vec![b]);
+    }
+
+    #[test]
+    #[should_panic(expected = "accessed more than once")]
+    fn aliasing_queries_panic() {
+        let mut world = World::new(8);
+        let e = world.spawn_entity().with(Position(1)).build();
+        world.fetch_mut::<(&Position, &mut Position)>(e);
+    }
+
+    #[test]
+    #[should_panic(expected = "needs `&mut World`")]
+    fn writes_need_mut_world() {
+        let world = World::new(8);
+        world.fetch::<&mut Position>(Entity(0));
+    }
+}
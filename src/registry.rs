@@ -0,0 +1,690 @@
+// Named component registry, used for runtime reflection (patching, inspection)
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::Arc,
+};
+
+use crate::{resource::Resource, world::Component};
+
+#[cfg(feature = "json")]
+use crate::{component::Entity, world::World};
+
+/// A component type ID that stays the same across builds, unlike `TypeId`.
+/// Set with `#[component(uuid = "...")]` or `#[component(name = "...")]` on the derive,
+/// and use it anywhere a type needs to be identified outside the running process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StableId(pub u128);
+
+impl StableId {
+    /// Derives an ID from a name with 128-bit FNV-1a.
+    /// Names must be unique across every component you register.
+    pub const fn from_name(name: &str) -> Self {
+        const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+        const PRIME: u128 = 0x0000000001000000000000000000013b;
+        let bytes = name.as_bytes();
+        let mut hash = OFFSET;
+        let mut i = 0;
+        while i < bytes.len() {
+            hash ^= bytes[i] as u128;
+            hash = hash.wrapping_mul(PRIME);
+            i += 1;
+        }
+        StableId(hash)
+    }
+}
+
+impl fmt::Display for StableId {
+    /// Formats as a hyphenated UUID.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            (v >> 96) as u32,
+            (v >> 80) as u16,
+            (v >> 64) as u16,
+            (v >> 48) as u16,
+            v & 0xffff_ffff_ffff
+        )
+    }
+}
+
+impl FromStr for StableId {
+    type Err = &'static str;
+
+    /// Parses a UUID, with or without hyphens.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex: String = s.chars().filter(|&c| c != '-').collect();
+        if hex.len() != 32 {
+            return Err("Invalid UUID string");
+        }
+        u128::from_str_radix(&hex, 16)
+            .map(StableId)
+            .map_err(|_| "Invalid UUID string")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for StableId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A single field of a component or resource, as written in the Rust source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Field {
+    pub name: &'static str,
+    pub ty: &'static str,
+}
+
+/// Runtime description of a registered component type.
+#[derive(Debug, Clone)]
+pub struct ComponentInfo {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub type_id: TypeId,
+    pub stable_id: Option<StableId>,
+    pub fields: &'static [Field],
+    #[cfg(feature = "json")]
+    json: Option<JsonFns>,
+}
+
+#[cfg(feature = "json")]
+pub(crate) type PatchFn =
+    fn(&World, Entity, &serde_json::Value) -> Result<PendingPatch, PatchError>;
+
+/// A deserialized patch waiting to be written, so a multi-component patch can fail before
+/// touching the world.
+#[cfg(feature = "json")]
+pub(crate) type PendingPatch = Box<dyn FnOnce(&mut World)>;
+
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy)]
+struct JsonFns {
+    patch: PatchFn,
+    read: fn(&World, Entity) -> Option<serde_json::Value>,
+    tracked: TrackedFn,
+}
+
+/// Reads a storage's `added` and `removed` lists, if the world has the storage.
+#[cfg(feature = "json")]
+pub(crate) type TrackedFn = for<'w> fn(&'w World) -> Option<(&'w [Entity], &'w [Entity])>;
+
+/// Runtime description of a registered resource type.
+#[derive(Debug, Clone)]
+pub struct ResourceInfo {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    pub type_id: TypeId,
+    pub fields: &'static [Field],
+}
+
+/// Machine-readable description of one registered type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TypeSchema {
+    pub name: &'static str,
+    pub type_name: &'static str,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub stable_id: Option<StableId>,
+    pub fields: Vec<Field>,
+}
+
+/// Machine-readable description of all registered components and resources, sorted by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Schema {
+    pub components: Vec<TypeSchema>,
+    pub resources: Vec<TypeSchema>,
+}
+
+/// Maps component names to their types, so tooling can refer to components by name.
+#[derive(Debug, Default, Clone)]
+pub struct ComponentRegistry {
+    by_name: HashMap<&'static str, ComponentInfo>,
+    names: HashMap<TypeId, &'static str>,
+    stable_ids: HashMap<StableId, &'static str>,
+    resources: HashMap<&'static str, ResourceInfo>,
+    /// A `DefaultFn<T>` per component type.
+    defaults: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+type DefaultFn<T> = Box<dyn Fn() -> T + Send + Sync>;
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a component type under `name`.
+    /// Returns `false` if the name is already taken.
+    pub fn register<T: Component>(&mut self, name: &'static str) -> bool {
+        self.insert(ComponentInfo {
+            name,
+            type_name: std::any::type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            stable_id: T::stable_id(),
+            fields: T::fields(),
+            #[cfg(feature = "json")]
+            json: None,
+        })
+    }
+
+    /// Registers a component type that can be read and patched as JSON.
+    #[cfg(feature = "json")]
+    pub fn register_json<T>(&mut self, name: &'static str) -> bool
+    where
+        T: Component + serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.insert(ComponentInfo {
+            name,
+            type_name: std::any::type_name::<T>(),
+            type_id: TypeId::of::<T>(),
+            stable_id: T::stable_id(),
+            fields: T::fields(),
+            json: Some(JsonFns {
+                patch: patch_component::<T>,
+                read: read_component::<T>,
+                tracked: |world| {
+                    let storage = world.get::<T>()?;
+                    Some((&storage.added, &storage.removed))
+                },
+            }),
+        })
+    }
+
+    fn insert(&mut self, info: ComponentInfo) -> bool {
+        if self.by_name.contains_key(info.name) {
+            return false;
+        }
+        if let Some(id) = info.stable_id {
+            if let Some(existing) = self.stable_ids.get(&id) {
+                panic!(
+                    "Stable ID {id} of {} collides with {existing}",
+                    info.type_name
+                );
+            }
+            self.stable_ids.insert(id, info.name);
+        }
+        self.names.insert(info.type_id, info.name);
+        self.by_name.insert(info.name, info);
+        true
+    }
+
+    /// Looks up a component by its registered name.
+    pub fn get(&self, name: &str) -> Option<&ComponentInfo> {
+        self.by_name.get(name)
+    }
+
+    /// Looks up a component by its stable ID.
+    pub fn get_by_stable_id(&self, id: StableId) -> Option<&ComponentInfo> {
+        self.get(self.stable_ids.get(&id)?)
+    }
+
+    /// Returns the registered name of the component type, if any.
+    pub fn name_of<T: Component>(&self) -> Option<&'static str> {
+        self.name_of_id(TypeId::of::<T>())
+    }
+
+    /// Returns the registered name for the type ID, if any.
+    pub fn name_of_id(&self, type_id: TypeId) -> Option<&'static str> {
+        self.names.get(&type_id).copied()
+    }
+
+    /// Iterates all registered components, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.by_name.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// Sets the value `World::insert_default` gives new `T`s, replacing any earlier default.
+    /// JSON patches for entities without a `T` are merged over it, so they can leave out
+    /// fields.
+    pub fn set_default<T: Component>(&mut self, value: T) {
+        self.set_default_with(move || value);
+    }
+
+    /// Like `set_default`, but builds each default with `constructor`.
+    pub fn set_default_with<T: Component>(
+        &mut self,
+        constructor: impl Fn() -> T + Send + Sync + 'static,
+    ) {
+        let constructor: DefaultFn<T> = Box::new(constructor);
+        self.defaults
+            .insert(TypeId::of::<T>(), Arc::new(constructor));
+    }
+
+    /// A fresh default `T`, if one was set.
+    pub fn default_of<T: Component>(&self) -> Option<T> {
+        let constructor = self.defaults.get(&TypeId::of::<T>())?;
+        let constructor = constructor
+            .downcast_ref::<DefaultFn<T>>()
+            .expect("type mismatch");
+        Some(constructor())
+    }
+
+    /// Registers a resource type under `name`, for schema export.
+    /// Returns `false` if the name is already taken.
+    pub fn register_resource<T: Resource>(&mut self, name: &'static str) -> bool {
+        if self.resources.contains_key(name) {
+            return false;
+        }
+        self.resources.insert(
+            name,
+            ResourceInfo {
+                name,
+                type_name: std::any::type_name::<T>(),
+                type_id: TypeId::of::<T>(),
+                fields: T::fields(),
+            },
+        );
+        true
+    }
+
+    /// Returns the registered name for the resource type ID, if any.
+    pub fn resource_name_of_id(&self, type_id: TypeId) -> Option<&'static str> {
+        self.resources
+            .values()
+            .find(|info| info.type_id == type_id)
+            .map(|info| info.name)
+    }
+
+    /// Iterates all registered resources, in no particular order.
+    pub fn iter_resources(&self) -> impl Iterator<Item = &ResourceInfo> {
+        self.resources.values()
+    }
+
+    /// Describes every registered component and resource (names, fields and field types),
+    /// so external editors and codegen tools can stay in sync with the Rust definitions.
+    pub fn export_schema(&self) -> Schema {
+        let mut components: Vec<TypeSchema> = self
+            .iter()
+            .map(|info| TypeSchema {
+                name: info.name,
+                type_name: info.type_name,
+                stable_id: info.stable_id,
+                fields: info.fields.to_vec(),
+            })
+            .collect();
+        let mut resources: Vec<TypeSchema> = self
+            .iter_resources()
+            .map(|info| TypeSchema {
+                name: info.name,
+                type_name: info.type_name,
+                stable_id: None,
+                fields: info.fields.to_vec(),
+            })
+            .collect();
+        components.sort_by_key(|schema| schema.name);
+        resources.sort_by_key(|schema| schema.name);
+        Schema {
+            components,
+            resources,
+        }
+    }
+
+    /// Returns the JSON patch function for the named component.
+    #[cfg(feature = "json")]
+    pub(crate) fn patch_fn(&self, name: &str) -> Result<PatchFn, PatchError> {
+        let info = self
+            .get(name)
+            .ok_or_else(|| PatchError::UnknownComponent(name.to_string()))?;
+        let json = info.json.ok_or(PatchError::NotPatchable(info.name))?;
+        Ok(json.patch)
+    }
+
+    /// Iterates JSON-registered components with their change tracking accessors.
+    #[cfg(feature = "json")]
+    pub(crate) fn tracked_json(&self) -> impl Iterator<Item = (&'static str, TrackedFn)> + '_ {
+        self.iter()
+            .filter_map(|info| Some((info.name, info.json?.tracked)))
+    }
+
+    /// Whether the component named `name` was registered with `register_json`.
+    #[cfg(feature = "json")]
+    pub fn has_json(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|info| info.json.is_some())
+    }
+
+    /// Reads the component named `name` on `entity` as JSON.
+    #[cfg(feature = "json")]
+    pub fn read_json(
+        &self,
+        world: &World,
+        name: &str,
+        entity: Entity,
+    ) -> Option<serde_json::Value> {
+        (self.get(name)?.json?.read)(world, entity)
+    }
+}
+
+/// Errors from applying a JSON patch to the world.
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub enum PatchError {
+    /// The patch wasn't a JSON object.
+    NotAnObject,
+    /// The patch had no (or an invalid) `"entity"` field.
+    MissingEntity,
+    /// The entity has been despawned.
+    DeadEntity(usize),
+    /// No component is registered with this name.
+    UnknownComponent(String),
+    /// The component is registered but not JSON-patchable.
+    NotPatchable(&'static str),
+    /// The component's storage hasn't been added to the world.
+    MissingStorage(&'static str),
+    /// The patched value didn't deserialize back into the component.
+    Json(&'static str, serde_json::Error),
+}
+
+#[cfg(feature = "json")]
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::NotAnObject => write!(f, "patch must be a JSON object"),
+            PatchError::MissingEntity => write!(f, "patch is missing a numeric \"entity\" field"),
+            PatchError::DeadEntity(id) => write!(f, "entity {id} is dead"),
+            PatchError::UnknownComponent(name) => write!(f, "unknown component {name:?}"),
+            PatchError::NotPatchable(name) => write!(f, "component {name:?} is not patchable"),
+            PatchError::MissingStorage(name) => {
+                write!(f, "component {name:?} has no storage in this world")
+            }
+            PatchError::Json(name, err) => write!(f, "invalid value for {name:?}: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl std::error::Error for PatchError {}
+
+/// Recursively merges `patch` into `target`; objects merge field-by-field, anything else replaces.
+#[cfg(feature = "json")]
+fn merge(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    match (target, patch) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+#[cfg(feature = "json")]
+fn patch_component<T>(
+    world: &World,
+    entity: Entity,
+    patch: &serde_json::Value,
+) -> Result<PendingPatch, PatchError>
+where
+    T: Component + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let name = world
+        .registry
+        .name_of::<T>()
+        .unwrap_or(std::any::type_name::<T>());
+    let current = world
+        .get::<T>()
+        .ok_or(PatchError::MissingStorage(name))?
+        .get(entity)
+        .copied();
+    // Merge over the current value, or the default, so partial patches work; a missing
+    // component without a default must be patched whole.
+    let base = current.or_else(|| world.registry.default_of::<T>());
+    let mut value = match base {
+        Some(current) => serde_json::to_value(current).map_err(|e| PatchError::Json(name, e))?,
+        None => serde_json::Value::Null,
+    };
+    merge(&mut value, patch);
+    let data: T = serde_json::from_value(value).map_err(|e| PatchError::Json(name, e))?;
+    Ok(Box::new(move |world: &mut World| {
+        world
+            .get_mut::<T>()
+            .expect("storage checked above")
+            .set(data, entity);
+    }))
+}
+
+#[cfg(feature = "json")]
+fn read_component<T>(world: &World, entity: Entity) -> Option<serde_json::Value>
+where
+    T: Component + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let data = world.get::<T>()?.get(entity)?;
+    serde_json::to_value(data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+    #[cfg(feature = "json")]
+    use serde_json::json;
+
+    #[derive(Copy, Clone, Debug, PartialEq, crate::Component)]
+    #[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Copy, Clone)]
+    struct Opaque;
+    impl Component for Opaque {}
+
+    #[derive(Copy, Clone, crate::Component)]
+    #[component(uuid = "6f1c0a2e-8d4b-4c39-9e57-0b1d2f3a4c5d")]
+    struct Velocity;
+
+    #[derive(Copy, Clone, crate::Component)]
+    #[component(name = "game::Health")]
+    struct Health;
+
+    #[derive(crate::Resource)]
+    #[allow(dead_code)]
+    struct Settings {
+        volume: f32,
+        names: Vec<Option<[u8; 4]>>,
+    }
+
+    #[test]
+    fn schema_export() {
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Position>("Position");
+        registry.register::<Opaque>("Opaque");
+        registry.register_resource::<Settings>("Settings");
+        assert!(!registry.register::<Opaque>("Opaque"));
+        assert_eq!(
+            registry.resource_name_of_id(TypeId::of::<Settings>()),
+            Some("Settings")
+        );
+
+        let schema = registry.export_schema();
+        let names: Vec<_> = schema.components.iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["Opaque", "Position"]);
+        assert!(schema.components[0].fields.is_empty());
+        assert_eq!(
+            schema.components[1].fields,
+            vec![
+                Field {
+                    name: "x",
+                    ty: "f32"
+                },
+                Field {
+                    name: "y",
+                    ty: "f32"
+                }
+            ]
+        );
+        assert_eq!(
+            schema.resources[0].fields[1],
+            Field {
+                name: "names",
+                ty: "Vec<Option<[u8; 4]>>"
+            }
+        );
+    }
+
+    #[test]
+    fn stable_ids() {
+        let uuid = "6f1c0a2e-8d4b-4c39-9e57-0b1d2f3a4c5d";
+        assert_eq!(Velocity::stable_id(), Some(uuid.parse().unwrap()));
+        assert_eq!(Velocity::stable_id().unwrap().to_string(), uuid);
+        assert_eq!(
+            Health::stable_id(),
+            Some(StableId::from_name("game::Health"))
+        );
+        assert_eq!(Opaque::stable_id(), None);
+        assert!("not-a-uuid".parse::<StableId>().is_err());
+
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Velocity>("Velocity");
+        registry.register::<Health>("Health");
+        let id = StableId::from_name("game::Health");
+        assert_eq!(registry.get_by_stable_id(id).unwrap().name, "Health");
+    }
+
+    #[test]
+    #[should_panic(expected = "collides")]
+    fn stable_id_collision() {
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Health>("Health");
+        registry.register::<Health>("HealthAgain");
+    }
+
+    #[test]
+    fn defaults_fill_inserts() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut world = World::new(8);
+        let e = world.spawn();
+        assert!(!world.insert_default::<Position>(e));
+        assert!(!world.has::<Position>(e));
+
+        world.registry.set_default(Position { x: 0.0, y: 1.0 });
+        assert!(world.insert_default::<Position>(e));
+        assert_eq!(world.component(e), Some(&Position { x: 0.0, y: 1.0 }));
+
+        let spawn_points = [(4.0, 4.0), (8.0, 8.0)];
+        let next = AtomicUsize::new(0);
+        world.registry.set_default_with(move || {
+            let (x, y) = spawn_points[next.fetch_add(1, Ordering::Relaxed) % 2];
+            Position { x, y }
+        });
+        world.insert_default::<Position>(e);
+        world.insert_default::<Position>(e);
+        assert_eq!(world.component(e), Some(&Position { x: 8.0, y: 8.0 }));
+    }
+
+    #[cfg(feature = "json")]
+    fn world() -> (World, Entity) {
+        let mut world = World::new(8);
+        world.add::<Position>();
+        world.add::<Opaque>();
+        world.registry.register_json::<Position>("Position");
+        world.registry.register::<Opaque>("Opaque");
+        let e = world.spawn();
+        world
+            .get_mut::<Position>()
+            .unwrap()
+            .add_entity(Position { x: 1.0, y: 2.0 }, e);
+        (world, e)
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn partial_patch() {
+        let (mut world, e) = world();
+        world
+            .apply(&json!({ "entity": e.0, "Position": { "x": 10.0 } }))
+            .unwrap();
+        assert_eq!(
+            world.get::<Position>().unwrap().get(e),
+            Some(&Position { x: 10.0, y: 2.0 })
+        );
+        assert_eq!(
+            world.registry.read_json(&world, "Position", e),
+            Some(json!({ "x": 10.0, "y": 2.0 }))
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn patch_errors() {
+        let (mut world, e) = world();
+        assert!(matches!(
+            world.apply(&json!({ "Position": {} })),
+            Err(PatchError::MissingEntity)
+        ));
+        assert!(matches!(
+            world.apply(&json!({ "entity": e.0, "Velocity": {} })),
+            Err(PatchError::UnknownComponent(_))
+        ));
+        assert!(matches!(
+            world.apply(&json!({ "entity": e.0, "Opaque": {} })),
+            Err(PatchError::NotPatchable("Opaque"))
+        ));
+        let other = world.spawn();
+        // Missing component must be given in full
+        assert!(matches!(
+            world.apply(&json!({ "entity": other.0, "Position": { "x": 1.0 } })),
+            Err(PatchError::Json("Position", _))
+        ));
+        world
+            .apply(&json!({ "entity": other.0, "Position": { "x": 1.0, "y": 1.0 } }))
+            .unwrap();
+        assert!(world.get::<Position>().unwrap().has(other));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn failed_patches_write_nothing() {
+        #[derive(
+            Copy, Clone, Debug, PartialEq, crate::Component, serde::Serialize, serde::Deserialize,
+        )]
+        struct Armor(u32);
+
+        let (mut world, e) = world();
+        world.add::<Armor>();
+        world.registry.register_json::<Armor>("Armor");
+        world.get_mut::<Armor>().unwrap().add_entity(Armor(1), e);
+        // Armor deserializes fine, but Position doesn't, so neither is written
+        assert!(matches!(
+            world.apply(&json!({ "entity": e.0, "Armor": 5, "Position": { "x": "far" } })),
+            Err(PatchError::Json("Position", _))
+        ));
+        assert_eq!(world.component(e), Some(&Armor(1)));
+        assert_eq!(world.component(e), Some(&Position { x: 1.0, y: 2.0 }));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn patches_merge_over_defaults() {
+        let (mut world, _) = world();
+        world.registry.set_default(Position { x: 0.0, y: 5.0 });
+        let e = world.spawn();
+        world
+            .apply(&json!({ "entity": e.0, "Position": { "x": 1.0 } }))
+            .unwrap();
+        assert_eq!(world.component(e), Some(&Position { x: 1.0, y: 5.0 }));
+    }
+}
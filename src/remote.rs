@@ -0,0 +1,362 @@
+// Remote debug server: line-delimited JSON over TCP
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use serde_json::{Value, json};
+
+use crate::{component::Entity, world::World};
+
+/// Unanswered request bytes a client may have buffered before it's dropped.
+const MAX_READ_BUFFER: usize = 1 << 20;
+/// Response bytes a client may leave unread before it's dropped.
+const MAX_WRITE_BUFFER: usize = 4 << 20;
+
+struct Client {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    /// Response bytes the socket hasn't taken yet, sent on later polls.
+    outgoing: Vec<u8>,
+    subscribed: bool,
+}
+
+/// A debug server external inspector tools can attach to.
+///
+/// The world isn't shared with a background thread; instead call `poll` once per frame
+/// from the thread that owns the world. Each request is one JSON object per line, and each
+/// gets one JSON line back, either `{"ok": ...}` or `{"error": "..."}`:
+///
+/// - `{"cmd": "stats"}`: entity count and per-component counts
+/// - `{"cmd": "entities"}`: all live entity IDs
+/// - `{"cmd": "schema"}`: the registry's schema, including stable component IDs
+/// - `{"cmd": "entity", "entity": 42}`: JSON values of the entity's registered components
+/// - `{"cmd": "subscribe"}`: stream stats to this client on every poll
+/// - anything with an `"entity"` key and no `"cmd"`: a patch passed to `World::apply`
+pub struct DebugServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+impl std::fmt::Debug for DebugServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugServer")
+            .field("addr", &self.listener.local_addr().ok())
+            .field("clients", &self.clients.len())
+            .finish()
+    }
+}
+
+impl DebugServer {
+    /// Binds the server. Use a loopback address unless you really mean to expose the world.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns the number of connected clients.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Accepts new clients, answers pending requests and streams stats to subscribers.
+    /// Never blocks: responses the socket can't take yet are kept and sent on later polls.
+    /// Clients that error, disconnect, or let more than a few megabytes of requests or
+    /// responses pile up are dropped.
+    pub fn poll(&mut self, world: &mut World) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.clients.push(Client {
+                            stream,
+                            buffer: Vec::new(),
+                            outgoing: Vec::new(),
+                            subscribed: false,
+                        });
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => break,
+            }
+        }
+
+        self.clients
+            .retain_mut(|client| serve(client, world).is_ok());
+    }
+}
+
+fn serve(client: &mut Client, world: &mut World) -> io::Result<()> {
+    let mut chunk = [0u8; 4096];
+    loop {
+        match client.stream.read(&mut chunk) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                client.buffer.extend_from_slice(&chunk[..n]);
+                if client.buffer.len() > MAX_READ_BUFFER {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "request too long"));
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    while let Some(end) = client.buffer.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = client.buffer.drain(..=end).collect();
+        let line = String::from_utf8_lossy(&line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => handle(client, world, &request),
+            Err(e) => Err(format!("invalid JSON: {e}")),
+        };
+        send(client, response);
+    }
+
+    // A subscriber still reading the last update gets a fresh one once it catches up
+    if client.subscribed && client.outgoing.is_empty() {
+        send(client, Ok(stats(world)));
+    }
+    flush(&mut client.stream, &mut client.outgoing)?;
+    if client.outgoing.len() > MAX_WRITE_BUFFER {
+        return Err(io::Error::other("client isn't reading"));
+    }
+    Ok(())
+}
+
+/// Queues a response line; `flush` sends it.
+fn send(client: &mut Client, response: Result<Value, String>) {
+    let message = match response {
+        Ok(value) => json!({ "ok": value }),
+        Err(error) => json!({ "error": error }),
+    };
+    client
+        .outgoing
+        .extend_from_slice(message.to_string().as_bytes());
+    client.outgoing.push(b'\n');
+}
+
+/// Writes as much of `pending` as the socket takes without blocking, keeping the rest.
+fn flush(out: &mut impl Write, pending: &mut Vec<u8>) -> io::Result<()> {
+    let mut written = 0;
+    while written < pending.len() {
+        match out.write(&pending[written..]) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => written += n,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    pending.drain(..written);
+    Ok(())
+}
+
+fn handle(client: &mut Client, world: &mut World, request: &Value) -> Result<Value, String> {
+    match request.get("cmd").and_then(Value::as_str) {
+        Some("stats") => Ok(stats(world)),
+        Some("entities") => Ok(world.entities().map(|e| e.0).collect()),
+        Some("schema") => {
+            serde_json::to_value(world.registry.export_schema()).map_err(|e| e.to_string())
+        }
+        Some("entity") => {
+            let id = request
+                .get("entity")
+                .and_then(Value::as_u64)
+                .ok_or("missing \"entity\"")? as usize;
+            let entity = Entity(id);
+            if world.is_dead(&entity) {
+                return Err(format!("entity {id} is dead"));
+            }
+            let components: serde_json::Map<String, Value> = world
+                .registry
+                .iter()
+                .filter_map(|info| {
+                    let value = world.registry.read_json(world, info.name, entity)?;
+                    Some((info.name.to_string(), value))
+                })
+                .collect();
+            Ok(json!({ "entity": id, "components": components }))
+        }
+        Some("subscribe") => {
+            client.subscribed = true;
+            Ok(Value::Bool(true))
+        }
+        Some(other) => Err(format!("unknown command {other:?}")),
+        None => world
+            .apply(request)
+            .map(|_| Value::Bool(true))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+fn stats(world: &World) -> Value {
+    let components: serde_json::Map<String, Value> = world
+        .component_counts()
+        .map(|(name, count)| (name.to_string(), count.into()))
+        .collect();
+    json!({ "entities": world.entity_count(), "components": components })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Component;
+    use serde::{Deserialize, Serialize};
+    use std::io::{BufRead, BufReader};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    fn request(
+        server: &mut DebugServer,
+        world: &mut World,
+        stream: &mut TcpStream,
+        reader: &mut BufReader<TcpStream>,
+        line: &str,
+    ) -> Value {
+        stream.write_all(line.as_bytes()).unwrap();
+        stream.write_all(b"\n").unwrap();
+        let mut response = String::new();
+        // Poll until the server has read the request and answered it
+        while response.is_empty() {
+            server.poll(world);
+            let _ = reader.read_line(&mut response);
+        }
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn stats_and_patch() {
+        let mut world = World::new(4);
+        world.add::<Health>();
+        world.registry.register_json::<Health>("Health");
+        let e = world.spawn();
+        world.get_mut::<Health>().unwrap().add_entity(Health(10), e);
+
+        let mut server = DebugServer::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(std::time::Duration::from_millis(10)))
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let stats = request(
+            &mut server,
+            &mut world,
+            &mut stream,
+            &mut reader,
+            r#"{"cmd": "stats"}"#,
+        );
+        assert_eq!(stats["ok"]["entities"], 1);
+        assert_eq!(stats["ok"]["components"]["Health"], 1);
+
+        let patched = request(
+            &mut server,
+            &mut world,
+            &mut stream,
+            &mut reader,
+            &format!(r#"{{"entity": {}, "Health": 3}}"#, e.0),
+        );
+        assert_eq!(patched["ok"], true);
+        assert_eq!(world.get::<Health>().unwrap().get(e), Some(&Health(3)));
+
+        let entity = request(
+            &mut server,
+            &mut world,
+            &mut stream,
+            &mut reader,
+            &format!(r#"{{"cmd": "entity", "entity": {}}}"#, e.0),
+        );
+        assert_eq!(entity["ok"]["components"]["Health"], 3);
+
+        let error = request(
+            &mut server,
+            &mut world,
+            &mut stream,
+            &mut reader,
+            r#"{"cmd": "nope"}"#,
+        );
+        assert!(error["error"].is_string());
+
+        let schema = request(
+            &mut server,
+            &mut world,
+            &mut stream,
+            &mut reader,
+            r#"{"cmd": "schema"}"#,
+        );
+        assert_eq!(schema["ok"]["components"][0]["name"], "Health");
+    }
+
+    /// Takes `budget` bytes, then blocks.
+    struct Trickle {
+        taken: Vec<u8>,
+        budget: usize,
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.budget);
+            if n == 0 {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            self.budget -= n;
+            self.taken.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn short_writes_keep_the_rest() {
+        let mut out = Trickle {
+            taken: Vec::new(),
+            budget: 4,
+        };
+        let mut pending = b"hello\nworld\n".to_vec();
+        flush(&mut out, &mut pending).unwrap();
+        assert_eq!(pending, b"o\nworld\n");
+        out.budget = 64;
+        flush(&mut out, &mut pending).unwrap();
+        assert!(pending.is_empty());
+        assert_eq!(out.taken, b"hello\nworld\n");
+    }
+
+    #[test]
+    fn oversized_requests_disconnect() {
+        let mut world = World::new(4);
+        let mut server = DebugServer::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        while server.client_count() == 0 {
+            server.poll(&mut world);
+        }
+        // No newline, so it can never be answered
+        let chunk = vec![b' '; 16 * 1024];
+        let mut sent = 0;
+        while server.client_count() == 1 {
+            if sent <= MAX_READ_BUFFER {
+                stream.write_all(&chunk).unwrap();
+                sent += chunk.len();
+            }
+            server.poll(&mut world);
+        }
+        assert!(sent > MAX_READ_BUFFER);
+    }
+}
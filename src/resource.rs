@@ -9,6 +9,8 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
+use crate::registry::Field;
+
 /// A read-only handle to a resource.
 /// Provides shared access to the underlying resource.
 pub struct ResourceHandle<'a, T: Resource> {
@@ -48,19 +50,56 @@ impl<'a, T: Resource> DerefMut for ResourceMutHandle<'a, T> {
 
 /// Trait representing a resource in the ECS.
 /// Resources must be thread-safe and have a unique key for identification.
-pub trait Resource: Send + Sync + 'static {}
+pub trait Resource: Send + Sync + 'static {
+    /// Describes the resource's fields for schema export.
+    /// `#[derive(Resource)]` fills this in for structs.
+    fn fields() -> &'static [Field]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+}
+
+/// What `Resources::iter` knows about a stored resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceMeta {
+    pub type_id: TypeId,
+    /// From `std::any::type_name`, which can change between compiler versions. For names
+    /// that stay put, e.g. in savegames, look the type up with
+    /// `ComponentRegistry::resource_name_of_id`.
+    pub type_name: &'static str,
+    /// `size_of` the value itself; heap memory it owns isn't counted.
+    pub size: usize,
+}
+
+impl ResourceMeta {
+    fn of<T: Resource>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            size: std::mem::size_of::<T>(),
+        }
+    }
+}
 
 /// Container for managing resources in the ECS.
 /// Provides methods to add, retrieve, and remove resources.
 pub struct Resources {
     resources: std::collections::HashMap<TypeId, RwLock<Box<dyn Any + Send + Sync + 'static>>>,
+    clone_fns: std::collections::HashMap<TypeId, CloneFn>,
+    meta: std::collections::HashMap<TypeId, ResourceMeta>,
 }
 
+type CloneFn = fn(&(dyn Any + Send + Sync)) -> Box<dyn Any + Send + Sync>;
+
 impl Debug for Resources {
-    /// Formats the resources for debugging, showing their keys.
+    /// Formats the resources for debugging, showing their type names.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names: Vec<_> = self.iter().map(|meta| meta.type_name).collect();
+        names.sort();
         f.debug_struct("Resources")
-            .field("resources", &self.resources.keys().collect::<Vec<_>>())
+            .field("resources", &names)
             .finish()
     }
 }
@@ -77,6 +116,8 @@ impl Resources {
     pub fn new() -> Self {
         Self {
             resources: std::collections::HashMap::new(),
+            clone_fns: std::collections::HashMap::new(),
+            meta: std::collections::HashMap::new(),
         }
     }
 
@@ -84,13 +125,40 @@ impl Resources {
     /// The resource is stored using its unique key.
     pub fn add<T: Resource>(&mut self, resource: T) {
         let key = TypeId::of::<T>();
+        self.clone_fns.remove(&key);
+        self.meta.insert(key, ResourceMeta::of::<T>());
         self.resources.insert(key, RwLock::new(Box::new(resource)));
     }
 
+    /// Adds a resource that is copied by `clone_registered`.
+    pub fn add_cloneable<T: Resource + Clone>(&mut self, resource: T) {
+        self.add(resource);
+        self.clone_fns.insert(TypeId::of::<T>(), |any| {
+            Box::new(any.downcast_ref::<T>().expect("type mismatch").clone())
+        });
+    }
+
+    /// Deep copies every resource added with `add_cloneable`; other resources are left out.
+    /// Blocks while any of them is mutably borrowed.
+    pub fn clone_registered(&self) -> Resources {
+        let mut copy = Resources::new();
+        for (key, clone_fn) in &self.clone_fns {
+            if let Some(lock) = self.resources.get(key) {
+                let value = clone_fn(lock.read().as_ref());
+                copy.resources.insert(*key, RwLock::new(value));
+                copy.clone_fns.insert(*key, *clone_fn);
+                copy.meta.insert(*key, self.meta[key]);
+            }
+        }
+        copy
+    }
+
     /// Retrieves a read-only handle to a resource by its type.
     /// Returns `None` if the resource is not found.
-    pub fn get<T: Resource>(&self) -> Option<ResourceHandle<T>> {
+    pub fn get<T: Resource>(&self) -> Option<ResourceHandle<'_, T>> {
         let key = TypeId::of::<T>();
+        #[cfg(debug_assertions)]
+        crate::parallel::check_resource_access(key, std::any::type_name::<T>(), false);
         self.resources.get(&key).map(|item| {
             item.try_read().map(|lock| {
                 let guard = RwLockReadGuard::map(lock, |b| b.downcast_ref::<T>().unwrap());
@@ -101,8 +169,10 @@ impl Resources {
 
     /// Retrieves a mutable handle to a resource by its type.
     /// Returns `None` if the resource is not found.
-    pub fn get_mut<T: Resource>(&self) -> Option<ResourceMutHandle<T>> {
+    pub fn get_mut<T: Resource>(&self) -> Option<ResourceMutHandle<'_, T>> {
         let key = TypeId::of::<T>();
+        #[cfg(debug_assertions)]
+        crate::parallel::check_resource_access(key, std::any::type_name::<T>(), true);
         self.resources.get(&key).map(|item| {
             item.try_write().map(|lock| {
                 let guard = RwLockWriteGuard::map(lock, |b| b.downcast_mut::<T>().unwrap());
@@ -115,6 +185,22 @@ impl Resources {
     pub fn remove<T: Resource>(&mut self) {
         let key = TypeId::of::<T>();
         self.resources.remove(&key);
+        self.clone_fns.remove(&key);
+        self.meta.remove(&key);
+    }
+
+    /// Iterates every stored resource's type, in no particular order, for inspectors,
+    /// metrics and savegames. Doesn't lock any resource.
+    pub fn iter(&self) -> impl Iterator<Item = &ResourceMeta> {
+        self.meta.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
     }
 }
 
@@ -140,6 +226,49 @@ mod tests {
         assert_eq!(res.unwrap().resource.value, 42);
     }
 
+    #[test]
+    /// Tests that only cloneable resources are copied.
+    fn test_clone_registered() {
+        #[derive(Clone)]
+        struct Score(u32);
+        impl Resource for Score {}
+
+        let mut resources = Resources::new();
+        resources.add_cloneable(Score(1));
+        resources.add(TestResource { value: 42 });
+
+        let copy = resources.clone_registered();
+        resources.get_mut::<Score>().unwrap().0 = 2;
+        assert_eq!(copy.get::<Score>().unwrap().0, 1);
+        assert!(copy.get::<TestResource>().is_none());
+    }
+
+    #[test]
+    /// Tests listing resources without knowing their types.
+    fn test_iter_types() {
+        struct Gravity(f32);
+        impl Resource for Gravity {}
+
+        let mut resources = Resources::new();
+        resources.add(TestResource { value: 42 });
+        resources.add(Gravity(9.8));
+        resources.remove::<TestResource>();
+
+        let held = resources.get_mut::<Gravity>().unwrap();
+        let listed: Vec<_> = resources.iter().copied().collect();
+        assert_eq!(
+            listed,
+            vec![ResourceMeta {
+                type_id: TypeId::of::<Gravity>(),
+                type_name: std::any::type_name::<Gravity>(),
+                size: 4,
+            }]
+        );
+        assert!(listed[0].type_name.ends_with("Gravity"));
+        assert_eq!(resources.len(), 1);
+        assert_eq!(held.0, 9.8);
+    }
+
     #[test]
     /// Tests thread-safe access to resources in the container.
     fn test_thread_access() {
@@ -0,0 +1,689 @@
+// Systems and the schedule that runs them
+
+use crate::{
+    resource::{Resource, Resources},
+    world::World,
+};
+
+/// A unit of per-frame logic.
+/// Any `FnMut(&mut World, &Resources)` closure or function is a system.
+pub trait System: Send + Sync + 'static {
+    fn run(&mut self, world: &mut World, resources: &Resources);
+
+    /// Name used in diagnostics and by ordering constraints.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Ordering constraints, set with `before`/`after`.
+    fn order(&self) -> SystemOrder {
+        SystemOrder::default()
+    }
+}
+
+impl<F> System for F
+where
+    F: FnMut(&mut World, &Resources) + Send + Sync + 'static,
+{
+    fn run(&mut self, world: &mut World, resources: &Resources) {
+        self(world, resources)
+    }
+}
+
+/// One-off setup run by `Schedule::run_startup` before the first frame: populating the
+/// world, adding storages, inserting resources.
+/// Any `FnOnce(&mut World, &mut Resources)` closure or function is a startup system.
+pub trait StartupSystem: Send + Sync + 'static {
+    fn run(self: Box<Self>, world: &mut World, resources: &mut Resources);
+}
+
+impl<F> StartupSystem for F
+where
+    F: FnOnce(&mut World, &mut Resources) + Send + Sync + 'static,
+{
+    fn run(self: Box<Self>, world: &mut World, resources: &mut Resources) {
+        (*self)(world, resources)
+    }
+}
+
+/// Names of the systems that must run before and after one system.
+/// A name is a function's path, as given by `std::any::type_name`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemOrder {
+    pub before: Vec<&'static str>,
+    pub after: Vec<&'static str>,
+}
+
+/// A system with ordering constraints, from `before`/`after`:
+/// `schedule.add_system(move_system.after(input_system))`.
+pub struct Ordered<S> {
+    pub(crate) system: S,
+    pub(crate) order: SystemOrder,
+}
+
+impl<S> Ordered<S> {
+    /// Also runs this system before `other`, which should be the function itself.
+    pub fn before<O>(mut self, other: O) -> Self {
+        self.order.before.push(std::any::type_name_of_val(&other));
+        self
+    }
+
+    /// Also runs this system after `other`, which should be the function itself.
+    pub fn after<O>(mut self, other: O) -> Self {
+        self.order.after.push(std::any::type_name_of_val(&other));
+        self
+    }
+}
+
+impl<S: System> System for Ordered<S> {
+    fn run(&mut self, world: &mut World, resources: &Resources) {
+        self.system.run(world, resources)
+    }
+
+    fn name(&self) -> &str {
+        self.system.name()
+    }
+
+    fn order(&self) -> SystemOrder {
+        self.order.clone()
+    }
+}
+
+/// Adds `before`/`after` to every system. For your own `ParallelSystem` types, opt in with
+/// an empty `impl IntoOrdered for MySystem {}`.
+pub trait IntoOrdered: Sized {
+    /// Runs this system before `other`, which should be the function itself.
+    fn before<O>(self, other: O) -> Ordered<Self> {
+        Ordered {
+            system: self,
+            order: SystemOrder::default(),
+        }
+        .before(other)
+    }
+
+    /// Runs this system after `other`, which should be the function itself.
+    fn after<O>(self, other: O) -> Ordered<Self> {
+        Ordered {
+            system: self,
+            order: SystemOrder::default(),
+        }
+        .after(other)
+    }
+}
+
+impl<S: System> IntoOrdered for S {}
+
+/// Sorts systems so every `before`/`after` constraint holds, otherwise keeping the
+/// insertion order. Constraints naming systems that aren't in the list are ignored.
+/// Returns the indices in run order, and for each index the indices that must run before
+/// it. Panics on a cycle.
+pub(crate) fn sort_systems(
+    names: &[&str],
+    orders: &[SystemOrder],
+) -> (Vec<usize>, Vec<Vec<usize>>) {
+    let mut must_follow: Vec<Vec<usize>> = vec![Vec::new(); names.len()];
+    for (i, order) in orders.iter().enumerate() {
+        for (j, name) in names.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            if order.after.contains(name) {
+                must_follow[i].push(j);
+            }
+            if order.before.contains(name) {
+                must_follow[j].push(i);
+            }
+        }
+    }
+
+    let mut sorted = Vec::with_capacity(names.len());
+    let mut done = vec![false; names.len()];
+    while sorted.len() < names.len() {
+        let next = (0..names.len())
+            .find(|&i| !done[i] && must_follow[i].iter().all(|&j| done[j]))
+            .unwrap_or_else(|| {
+                let stuck: Vec<_> = (0..names.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| names[i])
+                    .collect();
+                panic!("system ordering has a cycle among {stuck:?}")
+            });
+        done[next] = true;
+        sorted.push(next);
+    }
+    (sorted, must_follow)
+}
+
+type RunCondition = Box<dyn Fn(&World, &Resources) -> bool + Send + Sync>;
+
+/// A named group of systems that can be switched off together, e.g. `"debug_draw"`.
+struct SystemSet {
+    name: &'static str,
+    enabled: bool,
+    condition: Option<RunCondition>,
+    /// Whether the set runs this frame, from `enabled` and `condition`.
+    active: bool,
+}
+
+/// An ordered list of systems.
+/// `run` calls each system once, in the order they were added unless `before`/`after`
+/// say otherwise, then clears the storages' `added`/`removed` lists so systems see each
+/// change for exactly one frame, and updates the world's event queues.
+/// Systems added to a set are skipped while the set is disabled or its run condition is
+/// false; conditions are checked once per set per frame.
+/// Each system runs at a fresh world change tick, with `World::last_change_tick` set to
+/// when it last ran, so `Added`/`Changed` filters show it what's new since then.
+#[derive(Default)]
+pub struct Schedule {
+    /// Taken by `run_startup`, so each runs exactly once.
+    startup: Vec<Box<dyn StartupSystem>>,
+    systems: Vec<Box<dyn System>>,
+    /// Index into `sets` for each system that belongs to one.
+    set_of: Vec<Option<usize>>,
+    sets: Vec<SystemSet>,
+    /// World change tick each system last ran at, for `Added`/`Changed` filters.
+    last_run: Vec<u64>,
+    /// Run order, recomputed after systems are added.
+    sorted: Option<Vec<usize>>,
+}
+
+impl std::fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.systems.iter().map(|system| system.name()))
+            .finish()
+    }
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a system to the end of the schedule.
+    pub fn add_system(&mut self, system: impl System) -> &mut Self {
+        self.systems.push(Box::new(system));
+        self.set_of.push(None);
+        self.last_run.push(0);
+        self.sorted = None;
+        self
+    }
+
+    /// Appends a system that only runs while `set` is enabled and its run condition holds.
+    pub fn add_system_to_set(&mut self, set: &'static str, system: impl System) -> &mut Self {
+        let set = self.set_index(set);
+        self.add_system(system);
+        *self.set_of.last_mut().expect("system was just added") = Some(set);
+        self
+    }
+
+    /// Turns a set's systems on or off. Sets are enabled until disabled.
+    pub fn set_enabled(&mut self, set: &'static str, enabled: bool) -> &mut Self {
+        let set = self.set_index(set);
+        self.sets[set].enabled = enabled;
+        self
+    }
+
+    /// Returns false if the set was disabled with `set_enabled`. Run conditions aren't
+    /// checked here.
+    pub fn is_enabled(&self, set: &str) -> bool {
+        self.sets
+            .iter()
+            .find(|known| known.name == set)
+            .is_none_or(|known| known.enabled)
+    }
+
+    /// Runs a set's systems only on frames where `condition` returns true, e.g. while a
+    /// console variable resource is on. Replaces any earlier condition for the set.
+    pub fn run_if(
+        &mut self,
+        set: &'static str,
+        condition: impl Fn(&World, &Resources) -> bool + Send + Sync + 'static,
+    ) -> &mut Self {
+        let set = self.set_index(set);
+        self.sets[set].condition = Some(Box::new(condition));
+        self
+    }
+
+    fn set_index(&mut self, name: &'static str) -> usize {
+        if let Some(index) = self.sets.iter().position(|set| set.name == name) {
+            return index;
+        }
+        self.sets.push(SystemSet {
+            name,
+            enabled: true,
+            condition: None,
+            active: true,
+        });
+        self.sets.len() - 1
+    }
+
+    /// Adds a system for `run_startup`, which runs startup systems in the order added.
+    pub fn add_startup_system(&mut self, system: impl StartupSystem) -> &mut Self {
+        self.startup.push(Box::new(system));
+        self
+    }
+
+    /// Runs and drops the pending startup systems, then clears the storages' `added` and
+    /// `removed` lists so setup doesn't show up as the first frame's changes. Call once
+    /// before the main loop; later calls only run startup systems added since.
+    pub fn run_startup(&mut self, world: &mut World, resources: &mut Resources) {
+        for system in self.startup.drain(..) {
+            system.run(world, resources);
+        }
+        world.clear_tracked();
+    }
+
+    /// System names in the order `run` calls them. Panics if the constraints form a cycle.
+    pub fn run_order(&mut self) -> Vec<&str> {
+        let order = self.sorted_indices().to_vec();
+        order.iter().map(|&i| self.systems[i].name()).collect()
+    }
+
+    fn sorted_indices(&mut self) -> &[usize] {
+        let systems = &self.systems;
+        self.sorted.get_or_insert_with(|| {
+            let names: Vec<&str> = systems.iter().map(|system| system.name()).collect();
+            let orders: Vec<SystemOrder> = systems.iter().map(|system| system.order()).collect();
+            sort_systems(&names, &orders).0
+        })
+    }
+
+    /// Returns the number of systems.
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    /// Runs one frame: every system in order, then per-frame maintenance.
+    pub fn run(&mut self, world: &mut World, resources: &Resources) {
+        debug_assert!(
+            self.startup.is_empty(),
+            "Schedule::run called with startup systems pending; call run_startup first"
+        );
+        self.run_systems(world, resources);
+        world.clear_tracked();
+        world.update_events();
+    }
+
+    /// Runs every system in order, without per-frame maintenance.
+    fn run_systems(&mut self, world: &mut World, resources: &Resources) {
+        self.sorted_indices();
+        for set in &mut self.sets {
+            set.active = set.enabled
+                && set
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition(world, resources));
+        }
+        let sorted = self.sorted.as_ref().expect("sorted above");
+        for &i in sorted {
+            if self.set_of[i].is_some_and(|set| !self.sets[set].active) {
+                continue;
+            }
+            world.set_last_change_tick(self.last_run[i]);
+            self.last_run[i] = world.increment_change_tick();
+            self.systems[i].run(world, resources);
+        }
+    }
+}
+
+/// Frame timing resource. Advance it once per frame with the frame's delta; a
+/// `FixedSchedule` then spends the accumulated time in whole fixed steps.
+/// Times are in whatever unit `advance` is given, usually seconds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Time {
+    delta: f32,
+    elapsed: f32,
+    fixed_delta: f32,
+    accumulator: f32,
+}
+
+impl Resource for Time {}
+
+impl Time {
+    /// Fixed steps of `fixed_delta`, e.g. `1.0 / 60.0`.
+    pub fn new(fixed_delta: f32) -> Self {
+        assert!(fixed_delta > 0.0, "fixed timestep must be positive");
+        Self {
+            delta: 0.0,
+            elapsed: 0.0,
+            fixed_delta,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Starts a frame that took `dt`.
+    pub fn advance(&mut self, dt: f32) {
+        self.delta = dt;
+        self.elapsed += dt;
+        self.accumulator += dt;
+    }
+
+    /// Length of the current frame.
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Total time advanced so far.
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Length of one fixed step; use this rather than `delta` in fixed systems.
+    pub fn fixed_delta(&self) -> f32 {
+        self.fixed_delta
+    }
+
+    /// How far into the next fixed step the frame is, from 0 to 1. Pass it to
+    /// `interpolate::interpolated` to render between the last two fixed states.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.fixed_delta
+    }
+
+    /// Takes one fixed step out of the accumulator, if a whole one is there.
+    fn take_step(&mut self) -> bool {
+        if self.accumulator < self.fixed_delta {
+            return false;
+        }
+        self.accumulator -= self.fixed_delta;
+        true
+    }
+}
+
+/// Systems run at a constant rate: zero or more times per frame, once per fixed step the
+/// `Time` resource has accumulated. Run it each frame before the per-frame `Schedule`,
+/// which still owns clearing `added`/`removed`, so per-frame systems see every change.
+pub struct FixedSchedule {
+    schedule: Schedule,
+    max_steps: u32,
+}
+
+impl Default for FixedSchedule {
+    fn default() -> Self {
+        Self {
+            schedule: Schedule::default(),
+            max_steps: 8,
+        }
+    }
+}
+
+impl std::fmt::Debug for FixedSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FixedSchedule")
+            .field("systems", &self.schedule)
+            .field("max_steps", &self.max_steps)
+            .finish()
+    }
+}
+
+impl FixedSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the steps run in one frame (8 by default). Time beyond the cap is dropped, so a
+    /// slow frame can't snowball into ever more steps.
+    pub fn set_max_steps(&mut self, max_steps: u32) {
+        self.max_steps = max_steps;
+    }
+
+    /// Appends a system; ordering constraints work as in `Schedule`.
+    pub fn add_system(&mut self, system: impl System) -> &mut Self {
+        self.schedule.add_system(system);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.schedule.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schedule.is_empty()
+    }
+
+    /// Runs the systems once per whole fixed step in the `Time` resource, returning the
+    /// number of steps. Panics if there is no `Time` resource, or it is borrowed.
+    pub fn run(&mut self, world: &mut World, resources: &Resources) -> u32 {
+        let mut steps = 0;
+        loop {
+            let mut time = resources
+                .get_mut::<Time>()
+                .expect("FixedSchedule needs a Time resource");
+            if !time.take_step() {
+                break;
+            }
+            if steps == self.max_steps {
+                time.accumulator %= time.fixed_delta;
+                break;
+            }
+            drop(time);
+            self.schedule.run_systems(world, resources);
+            steps += 1;
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{resource::Resource, world::Component};
+
+    #[derive(Copy, Clone)]
+    struct Position;
+    impl Component for Position {}
+
+    struct Log(Vec<&'static str>);
+    impl Resource for Log {}
+
+    struct Spawner;
+    impl System for Spawner {
+        fn run(&mut self, world: &mut World, resources: &Resources) {
+            world.spawn_entity().with(Position).build();
+            resources.get_mut::<Log>().unwrap().0.push("spawn");
+        }
+    }
+
+    #[test]
+    fn runs_in_order_and_clears_tracking() {
+        let mut world = World::new(8);
+        let mut resources = Resources::new();
+        resources.add(Log(Vec::new()));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Spawner)
+            .add_system(|world: &mut World, resources: &Resources| {
+                let added = world.get::<Position>().unwrap().added.len();
+                assert_eq!(added, 1);
+                resources.get_mut::<Log>().unwrap().0.push("count");
+            });
+        assert_eq!(schedule.len(), 2);
+
+        schedule.run(&mut world, &resources);
+        schedule.run(&mut world, &resources);
+        assert_eq!(
+            resources.get::<Log>().unwrap().0,
+            vec!["spawn", "count", "spawn", "count"]
+        );
+        assert!(world.get::<Position>().unwrap().added.is_empty());
+    }
+
+    fn input(_: &mut World, resources: &Resources) {
+        resources.get_mut::<Log>().unwrap().0.push("input");
+    }
+
+    fn movement(_: &mut World, resources: &Resources) {
+        resources.get_mut::<Log>().unwrap().0.push("move");
+    }
+
+    fn render(_: &mut World, resources: &Resources) {
+        resources.get_mut::<Log>().unwrap().0.push("render");
+    }
+
+    #[test]
+    fn before_and_after_reorder_systems() {
+        let mut world = World::new(8);
+        let mut resources = Resources::new();
+        resources.add(Log(Vec::new()));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(render)
+            .add_system(movement.after(input).before(render))
+            .add_system(input);
+        schedule.run(&mut world, &resources);
+        assert_eq!(
+            resources.get::<Log>().unwrap().0,
+            vec!["input", "move", "render"]
+        );
+    }
+
+    #[test]
+    fn disabled_sets_are_skipped() {
+        struct DebugDraw(bool);
+        impl Resource for DebugDraw {}
+
+        let mut world = World::new(8);
+        let mut resources = Resources::new();
+        resources.add(Log(Vec::new()));
+        resources.add(DebugDraw(false));
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(movement)
+            .add_system_to_set("debug", render)
+            .add_system_to_set("input", input.before(movement))
+            .run_if("debug", |_, resources| {
+                resources.get::<DebugDraw>().unwrap().0
+            });
+
+        schedule.run(&mut world, &resources);
+        resources.get_mut::<DebugDraw>().unwrap().0 = true;
+        schedule.run(&mut world, &resources);
+        schedule.set_enabled("input", false);
+        assert!(!schedule.is_enabled("input"));
+        assert!(schedule.is_enabled("debug"));
+        schedule.run(&mut world, &resources);
+        assert_eq!(
+            resources.get::<Log>().unwrap().0,
+            vec!["input", "move", "render", "input", "move", "render", "move"]
+        );
+    }
+
+    #[test]
+    fn systems_see_changes_since_they_last_ran() {
+        #[derive(Copy, Clone)]
+        struct Health(u32);
+        impl Component for Health {}
+
+        struct Seen(Vec<usize>);
+        impl Resource for Seen {}
+
+        let mut world = World::new(8);
+        let mut resources = Resources::new();
+        resources.add(Seen(Vec::new()));
+        let hurt = world.spawn_entity().with(Health(10)).build();
+        world.spawn_entity().with(Health(10)).build();
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(|world: &mut World, resources: &Resources| {
+                let seen = world
+                    .query_filtered::<&Health, crate::query::Changed<Health>>()
+                    .iter()
+                    .count();
+                resources.get_mut::<Seen>().unwrap().0.push(seen);
+            })
+            .add_system(move |world: &mut World, _: &Resources| {
+                // Check first: taking `&mut` alone counts as a change
+                if world.component::<Health>(hurt).unwrap().0 > 8 {
+                    world.component_mut::<Health>(hurt).unwrap().0 -= 1;
+                }
+            });
+
+        for _ in 0..4 {
+            schedule.run(&mut world, &resources);
+        }
+        // Both are new, then one is hurt each frame until it stops at 8
+        assert_eq!(resources.get::<Seen>().unwrap().0, vec![2, 1, 1, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cycle")]
+    fn ordering_cycles_panic() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(input.after(movement))
+            .add_system(movement.after(input));
+        schedule.run_order();
+    }
+
+    #[test]
+    fn startup_runs_once_before_frames() {
+        let mut world = World::new(8);
+        let mut resources = Resources::new();
+
+        let mut schedule = Schedule::new();
+        schedule
+            .add_startup_system(|world: &mut World, resources: &mut Resources| {
+                resources.add(Log(vec!["startup"]));
+                world.spawn_entity().with(Position).build();
+            })
+            .add_system(|world: &mut World, resources: &Resources| {
+                let added = world.get::<Position>().unwrap().added.len();
+                resources.get_mut::<Log>().unwrap().0.push(if added == 0 {
+                    "frame"
+                } else {
+                    "frame saw setup"
+                });
+            });
+
+        schedule.run_startup(&mut world, &mut resources);
+        schedule.run(&mut world, &resources);
+        schedule.run_startup(&mut world, &mut resources);
+        schedule.run(&mut world, &resources);
+        assert_eq!(
+            resources.get::<Log>().unwrap().0,
+            vec!["startup", "frame", "frame"]
+        );
+        assert_eq!(world.entity_count(), 1);
+    }
+
+    #[test]
+    fn fixed_schedule_runs_per_step() {
+        #[derive(Default)]
+        struct Steps(u32);
+        impl Resource for Steps {}
+
+        let mut world = World::new(8);
+        let mut resources = Resources::new();
+        resources.add(Time::new(0.25));
+        resources.add(Steps::default());
+
+        let mut fixed = FixedSchedule::new();
+        fixed.add_system(|_: &mut World, resources: &Resources| {
+            assert_eq!(resources.get::<Time>().unwrap().fixed_delta(), 0.25);
+            resources.get_mut::<Steps>().unwrap().0 += 1;
+        });
+
+        resources.get_mut::<Time>().unwrap().advance(0.6);
+        assert_eq!(fixed.run(&mut world, &resources), 2);
+        let alpha = resources.get::<Time>().unwrap().alpha();
+        assert!((alpha - 0.4).abs() < 1e-4);
+
+        resources.get_mut::<Time>().unwrap().advance(0.2);
+        assert_eq!(fixed.run(&mut world, &resources), 1);
+        assert_eq!(resources.get::<Steps>().unwrap().0, 3);
+
+        // A long stall runs at most max_steps and drops the rest
+        fixed.set_max_steps(2);
+        resources.get_mut::<Time>().unwrap().advance(10.0);
+        assert_eq!(fixed.run(&mut world, &resources), 2);
+        assert!(resources.get::<Time>().unwrap().alpha() < 1.0);
+    }
+}
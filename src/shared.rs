@@ -0,0 +1,118 @@
+// Read-only, cheaply cloneable views of a finished world
+
+use std::sync::Arc;
+
+use crate::{
+    component::{Entity, Storage},
+    resource::{Resource, ResourceHandle, Resources},
+    tags::EntityTags,
+    world::{Component, World},
+};
+
+/// A frozen world that can be read from any number of threads at once.
+/// Created with `World::into_shared`; to keep simulating while others read, share a clone
+/// (`world.clone().into_shared()`), which only copies storages the sim writes to.
+#[derive(Debug, Clone)]
+pub struct SharedWorld {
+    world: Arc<World>,
+    resources: Option<Arc<Resources>>,
+}
+
+impl SharedWorld {
+    pub(crate) fn new(world: World) -> Self {
+        Self {
+            world: Arc::new(world),
+            resources: None,
+        }
+    }
+
+    /// Attaches resources for readers to look up alongside the world.
+    pub fn with_resources(mut self, resources: Resources) -> Self {
+        self.resources = Some(Arc::new(resources));
+        self
+    }
+
+    /// Retrieves storage for the component type, if present.
+    pub fn get<T: Component>(&self) -> Option<&Storage<T>> {
+        self.world.get::<T>()
+    }
+
+    /// Returns an iterator over the component storage, or empty if not present.
+    pub fn iter<T: Component>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.world.iter::<T>()
+    }
+
+    pub fn tags(&self) -> &EntityTags {
+        &self.world.tags
+    }
+
+    pub fn is_dead(&self, entity: &Entity) -> bool {
+        self.world.is_dead(entity)
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.world.is_alive(entity)
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.world.entities()
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.world.entity_count()
+    }
+
+    /// Read access to an attached resource.
+    /// Returns `None` if no resources were attached or the resource is missing.
+    pub fn resource<T: Resource>(&self) -> Option<ResourceHandle<'_, T>> {
+        self.resources.as_ref()?.get::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[derive(Copy, Clone)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    struct Frame(u64);
+    impl Resource for Frame {}
+
+    #[test]
+    fn readers_see_frozen_frame() {
+        let mut world = World::new(8);
+        world.add::<Position>();
+        let e = world.spawn();
+        world
+            .get_mut::<Position>()
+            .unwrap()
+            .add_entity(Position(1), e);
+        world.tags.add_tag("player", e);
+
+        let mut resources = Resources::new();
+        resources.add(Frame(7));
+        let frame = world.clone().into_shared().with_resources(resources);
+
+        // The sim keeps going on its own copy
+        world.get_mut::<Position>().unwrap().set(Position(2), e);
+
+        let readers: Vec<_> = (0..2)
+            .map(|_| {
+                let frame = frame.clone();
+                thread::spawn(move || {
+                    assert_eq!(frame.get::<Position>().unwrap().get(e).unwrap().0, 1);
+                    assert_eq!(frame.iter::<Position>().count(), 1);
+                    assert!(frame.tags().has_tag("player", &e));
+                    assert_eq!(frame.resource::<Frame>().unwrap().0, 7);
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(world.get::<Position>().unwrap().get(e).unwrap().0, 2);
+    }
+}
@@ -0,0 +1,226 @@
+// Mirroring components into external engines (physics, audio, ...)
+
+use std::collections::HashMap;
+
+use crate::{
+    component::Entity,
+    world::{Component, World},
+};
+
+/// The external side of a mirror, e.g. a physics world that owns rigid bodies.
+pub trait External {
+    /// The component mirrored into the external engine.
+    type Source: Component;
+    /// The engine's handle to its copy of the object.
+    type Handle: Copy + Send + Sync + 'static;
+
+    /// Creates the external object when an entity gains `Source`.
+    fn create(&mut self, entity: Entity, source: &Self::Source) -> Self::Handle;
+    /// Destroys the external object when `Source` is removed or the entity despawns.
+    fn destroy(&mut self, handle: Self::Handle);
+    /// Writes a changed component to the external object.
+    fn push(&mut self, handle: Self::Handle, source: &Self::Source);
+    /// Reads the external object back, e.g. after a physics step.
+    fn pull(&mut self, handle: Self::Handle, source: &mut Self::Source);
+}
+
+/// Component holding an entity's external handle, added and removed by `Mirror`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Synced<H>(pub H);
+
+impl<H: Copy + Send + Sync + 'static> Component for Synced<H> {}
+
+/// Keeps one component type in step with an external engine.
+/// Call `push` before stepping the engine and `pull` after. Change detection uses change
+/// ticks, so only components written since the last `push` are sent across. `pull` writes
+/// untracked, so pulled values aren't echoed back and don't match `Changed` filters.
+pub struct Mirror<E: External> {
+    mirrored: HashMap<Entity, E::Handle>,
+    /// World change tick as of the last `push`.
+    synced: u64,
+}
+
+impl<E: External> Default for Mirror<E> {
+    fn default() -> Self {
+        Self {
+            mirrored: HashMap::new(),
+            synced: 0,
+        }
+    }
+}
+
+impl<E: External> Mirror<E> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates external objects for new components, destroys them for removed ones, and
+    /// writes changed components across. Advances the world's change tick, so writes made
+    /// after it are sent by the next `push` even outside a `Schedule`.
+    pub fn push(&mut self, world: &mut World, external: &mut E) {
+        world.add::<Synced<E::Handle>>();
+
+        // Removed components and despawned entities
+        let sources = world.get::<E::Source>();
+        let gone: Vec<Entity> = self
+            .mirrored
+            .keys()
+            .copied()
+            .filter(|&entity| !sources.is_some_and(|set| set.has(entity)))
+            .collect();
+        for entity in gone {
+            let handle = self.mirrored.remove(&entity).expect("entity was mirrored");
+            external.destroy(handle);
+            if let Some(set) = world.get_mut::<Synced<E::Handle>>() {
+                set.remove_entity(entity);
+            }
+        }
+
+        let since = self.synced;
+        self.synced = world.change_tick();
+        world.increment_change_tick();
+        let Some(sources) = world.get::<E::Source>() else {
+            return;
+        };
+        let mut created = Vec::new();
+        for (entity, source) in sources.iter() {
+            match self.mirrored.get(&entity) {
+                Some(&handle) => {
+                    if sources
+                        .ticks(entity)
+                        .is_some_and(|ticks| ticks.is_changed(since))
+                    {
+                        external.push(handle, source);
+                    }
+                }
+                None => {
+                    let handle = external.create(entity, source);
+                    self.mirrored.insert(entity, handle);
+                    created.push((entity, handle));
+                }
+            }
+        }
+        let handles = world
+            .get_mut::<Synced<E::Handle>>()
+            .expect("storage was just added");
+        for (entity, handle) in created {
+            handles.set(Synced(handle), entity);
+        }
+    }
+
+    /// Reads every external object back into its component, without marking it changed.
+    pub fn pull(&mut self, world: &mut World, external: &mut E) {
+        let Some(sources) = world.get_mut::<E::Source>() else {
+            return;
+        };
+        for (&entity, &handle) in &self.mirrored {
+            if let Some(source) = sources.get_mut_untracked(entity) {
+                external.pull(handle, source);
+            }
+        }
+    }
+
+    /// Returns the external handle for the entity, if it is mirrored.
+    pub fn handle(&self, entity: Entity) -> Option<E::Handle> {
+        self.mirrored.get(&entity).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.mirrored.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mirrored.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug)]
+    struct Body {
+        y: f32,
+    }
+    impl Component for Body {}
+
+    /// A "physics engine" that drops every body by one unit per step.
+    #[derive(Default)]
+    struct Physics {
+        bodies: HashMap<u32, f32>,
+        next: u32,
+        pushes: usize,
+    }
+
+    impl Physics {
+        fn step(&mut self) {
+            for y in self.bodies.values_mut() {
+                *y -= 1.0;
+            }
+        }
+    }
+
+    impl External for Physics {
+        type Source = Body;
+        type Handle = u32;
+
+        fn create(&mut self, _: Entity, source: &Body) -> u32 {
+            self.next += 1;
+            self.bodies.insert(self.next, source.y);
+            self.next
+        }
+
+        fn destroy(&mut self, handle: u32) {
+            self.bodies.remove(&handle);
+        }
+
+        fn push(&mut self, handle: u32, source: &Body) {
+            self.pushes += 1;
+            self.bodies.insert(handle, source.y);
+        }
+
+        fn pull(&mut self, handle: u32, source: &mut Body) {
+            source.y = self.bodies[&handle];
+        }
+    }
+
+    #[test]
+    fn mirrors_lifecycle_and_changes() {
+        let mut world = World::new(8);
+        let mut physics = Physics::default();
+        let mut mirror = Mirror::<Physics>::new();
+
+        let e = world.spawn_entity().with(Body { y: 10.0 }).build();
+        mirror.push(&mut world, &mut physics);
+        let handle = mirror.handle(e).unwrap();
+        assert_eq!(
+            world.get::<Synced<u32>>().unwrap().get(e),
+            Some(&Synced(handle))
+        );
+
+        physics.step();
+        mirror.pull(&mut world, &mut physics);
+        assert_eq!(world.get::<Body>().unwrap().get(e).unwrap().y, 9.0);
+
+        // Pulled values aren't echoed back
+        mirror.push(&mut world, &mut physics);
+        assert_eq!(physics.pushes, 0);
+
+        world.get_mut::<Body>().unwrap().set(Body { y: 50.0 }, e);
+        mirror.push(&mut world, &mut physics);
+        assert_eq!(physics.pushes, 1);
+        assert_eq!(physics.bodies[&handle], 50.0);
+
+        // Writing the value it already had still counts as a change
+        world.get_mut::<Body>().unwrap().set(Body { y: 50.0 }, e);
+        mirror.push(&mut world, &mut physics);
+        assert_eq!(physics.pushes, 2);
+        mirror.push(&mut world, &mut physics);
+        assert_eq!(physics.pushes, 2);
+
+        world.despawn(e);
+        mirror.push(&mut world, &mut physics);
+        assert!(physics.bodies.is_empty());
+        assert!(mirror.is_empty());
+    }
+}
@@ -1,9 +1,14 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use parking_lot::{Mutex, RwLock};
 
 use crate::component::Entity;
 
 /// List of entities associated with a specific tag.
-#[derive(Debug, Default)]
+#[derive(Default, Clone)]
 pub struct TagList {
     set: HashSet<Entity>,
 }
@@ -36,9 +41,50 @@ impl TagList {
 
 /// Tag collection management, each tag is associated with a set of entities.
 /// It allows adding, removing, and querying entities by their tags.
-#[derive(Debug)]
+/// Changing tags needs `&mut EntityTags`, so a shared `&World` (a `SharedWorld`, a
+/// template, a recorded tick) only reads them. The tags still sit behind their own lock, so
+/// systems running in parallel can tag through `WorldView::add_tag`.
+/// Tags gained and lost are recorded until `clear_tracked`, like `Storage::added`/`removed`.
 pub struct EntityTags {
-    tags: HashMap<&'static str, TagList>,
+    tags: RwLock<HashMap<&'static str, TagList>>,
+    changes: Mutex<TagChanges>,
+}
+
+#[derive(Default, Clone)]
+struct TagChanges {
+    added: Vec<(&'static str, Entity)>,
+    removed: Vec<(&'static str, Entity)>,
+}
+
+impl Clone for EntityTags {
+    fn clone(&self) -> Self {
+        Self {
+            tags: RwLock::new(self.tags.read().clone()),
+            changes: Mutex::new(self.changes.lock().clone()),
+        }
+    }
+}
+
+impl fmt::Debug for TagList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TagList")
+            .field("len", &self.set.len())
+            .finish()
+    }
+}
+
+impl fmt::Debug for EntityTags {
+    /// Shows each tag with its entity count, sorted by tag.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut counts: Vec<_> = self
+            .tags
+            .read()
+            .iter()
+            .map(|(tag, list)| (*tag, list.set.len()))
+            .collect();
+        counts.sort();
+        f.debug_map().entries(counts).finish()
+    }
 }
 
 impl Default for EntityTags {
@@ -50,52 +96,120 @@ impl Default for EntityTags {
 impl EntityTags {
     pub fn new() -> Self {
         Self {
-            tags: HashMap::new(),
+            tags: RwLock::new(HashMap::new()),
+            changes: Mutex::default(),
         }
     }
 
     /// Adds a tag to the given entity.
     pub fn add_tag(&mut self, tag: &'static str, entity: Entity) {
-        self.tags.entry(tag).or_default().add_entity(entity);
+        self.add_tags_shared(&[tag], entity);
     }
 
     /// Adds multiple tags to the given entity.
     pub fn add_tags(&mut self, tags: &[&'static str], entity: Entity) {
-        for &tag in tags {
-            self.add_tag(tag, entity);
-        }
+        self.add_tags_shared(tags, entity);
     }
 
     /// Removes a tag from the given entity.
     pub fn remove_tag(&mut self, tag: &'static str, entity: &Entity) {
-        if let Some(entities) = self.tags.get_mut(&tag) {
-            entities.remove_entity(entity);
+        self.remove_tag_shared(tag, entity);
+    }
+
+    /// `add_tags` through the lock, for `WorldView`.
+    pub(crate) fn add_tags_shared(&self, tags: &[&'static str], entity: Entity) {
+        let mut map = self.tags.write();
+        for &tag in tags {
+            if map.entry(tag).or_default().set.insert(entity) {
+                self.changes.lock().added.push((tag, entity));
+            }
+        }
+    }
+
+    /// `remove_tag` through the lock, for `WorldView`.
+    pub(crate) fn remove_tag_shared(&self, tag: &'static str, entity: &Entity) {
+        if let Some(entities) = self.tags.write().get_mut(&tag)
+            && entities.set.remove(entity)
+        {
+            self.changes.lock().removed.push((tag, *entity));
         }
     }
 
     /// Removes all tags from the given entity.
     pub fn remove_all_tags(&mut self, entity: &Entity) {
-        for tag in self.tags.keys().cloned().collect::<Vec<&'static str>>() {
-            self.remove_tag(tag, entity);
+        for (&tag, list) in self.tags.write().iter_mut() {
+            if list.set.remove(entity) {
+                self.changes.lock().removed.push((tag, *entity));
+            }
+        }
+    }
+
+    /// Every `(tag, entity)` pair gained since the last `clear_tracked`, in order.
+    pub fn added(&self) -> Vec<(&'static str, Entity)> {
+        self.changes.lock().added.clone()
+    }
+
+    /// Every `(tag, entity)` pair lost since the last `clear_tracked`, including tags
+    /// dropped by despawning, in order.
+    pub fn removed(&self) -> Vec<(&'static str, Entity)> {
+        self.changes.lock().removed.clone()
+    }
+
+    /// Entities that gained the tag since the last `clear_tracked`.
+    pub fn added_with(&self, tag: &'static str) -> Vec<Entity> {
+        let changes = self.changes.lock();
+        changes
+            .added
+            .iter()
+            .filter(|(added, _)| *added == tag)
+            .map(|&(_, entity)| entity)
+            .collect()
+    }
+
+    /// Entities that lost the tag since the last `clear_tracked`.
+    pub fn removed_with(&self, tag: &'static str) -> Vec<Entity> {
+        let changes = self.changes.lock();
+        changes
+            .removed
+            .iter()
+            .filter(|(removed, _)| *removed == tag)
+            .map(|&(_, entity)| entity)
+            .collect()
+    }
+
+    /// Empties the added and removed lists. `World::clear_tracked` calls this.
+    pub fn clear_tracked(&mut self) {
+        *self.changes.lock() = TagChanges::default();
+    }
+
+    /// Removes every tag from every entity, recording each removal.
+    pub fn clear(&mut self) {
+        let mut map = self.tags.write();
+        let mut changes = self.changes.lock();
+        for (tag, list) in map.drain() {
+            changes
+                .removed
+                .extend(list.set.into_iter().map(|entity| (tag, entity)));
         }
     }
 
     /// Returns a list of all entities that have the given tag.
     pub fn get_entities_with_tag(&self, tag: &'static str) -> Option<Vec<Entity>> {
         self.tags
+            .read()
             .get(&tag)
             .map(|list| list.set.iter().cloned().collect::<Vec<Entity>>())
     }
 
     /// Returns the number of entities that have the given tag.
     pub fn count(&self, tag: &'static str) -> usize {
-        self.tags.get(&tag).map_or(0, |list| list.set.len())
+        self.tags.read().get(&tag).map_or(0, |list| list.set.len())
     }
 
     /// Returns the entity with the given tag, panics if there isn't one.
     /// Does *not* assert there is only one entity with the tag.
     pub fn expect_one(&self, tag: &'static str) -> Entity {
-        if let Some(list) = self.tags.get(&tag) {
+        if let Some(list) = self.tags.read().get(&tag) {
             list.expect_one()
         } else {
             panic!("Expected exactly one entity with tag: {tag:?}");
@@ -105,19 +219,49 @@ impl EntityTags {
     /// Returns a single entity with the given tag.
     /// Does *not* assert there is only one entity with the tag.
     pub fn want_one(&self, tag: &'static str) -> Option<Entity> {
-        if let Some(list) = self.tags.get(&tag) {
-            if list.set.len() == 1 {
-                return self
-                    .tags
-                    .get(&tag)
-                    .and_then(|list| list.set.iter().next().cloned());
-            }
+        if let Some(list) = self.tags.read().get(&tag)
+            && list.set.len() == 1
+        {
+            return list.set.iter().next().cloned();
         }
         None
     }
 
+    /// Iterates the tags the entity has, in no particular order.
+    /// The tags are collected up front, so the lock isn't held while iterating.
+    pub fn tags_of(&self, entity: Entity) -> impl Iterator<Item = &'static str> + '_ {
+        let tags: Vec<&'static str> = self
+            .tags
+            .read()
+            .iter()
+            .filter(|(_, list)| list.contains(&entity))
+            .map(|(tag, _)| *tag)
+            .collect();
+        tags.into_iter()
+    }
+
+    /// Stably reorders `entities` by the first of `tags` each has: those with `tags[0]`
+    /// first, then `tags[1]`, and so on, then those with none of them. Takes the lock once
+    /// and makes a single pass, so it's cheap enough to run every frame.
+    pub fn order_by_priority(&self, tags: &[&'static str], entities: &mut Vec<Entity>) {
+        let map = self.tags.read();
+        let lists: Vec<Option<&TagList>> = tags.iter().map(|tag| map.get(tag)).collect();
+        let mut groups = vec![Vec::new(); lists.len() + 1];
+        for entity in entities.drain(..) {
+            let rank = lists
+                .iter()
+                .position(|list| list.is_some_and(|list| list.contains(&entity)))
+                .unwrap_or(lists.len());
+            groups[rank].push(entity);
+        }
+        entities.extend(groups.into_iter().flatten());
+    }
+
     /// Returns true if the given entity has the given tag.
     pub fn has_tag(&self, tag: &'static str, entity: &Entity) -> bool {
-        self.tags.get(tag).is_some_and(|l| l.contains(entity))
+        self.tags
+            .read()
+            .get(tag)
+            .is_some_and(|l| l.contains(entity))
     }
 }
@@ -0,0 +1,164 @@
+// World templates for quickly stamping out preset worlds
+
+use std::collections::HashMap;
+
+use crate::{resource::Resource, world::World};
+
+/// A frozen copy of a world, created with `World::as_template`.
+/// Instantiating shares the template's storages with the new world, and each storage is
+/// only copied the first time the new world writes to it, so restarting a match or setting
+/// up a test from a big preset costs almost nothing.
+#[derive(Debug)]
+pub struct WorldTemplate {
+    pub(crate) world: World,
+}
+
+impl WorldTemplate {
+    /// Creates a fresh world from the template.
+    pub fn instantiate(&self) -> World {
+        self.world.fork()
+    }
+
+    /// Read access to the captured world.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+}
+
+/// Named scenario presets.
+#[derive(Debug, Default)]
+pub struct WorldTemplates {
+    templates: HashMap<&'static str, WorldTemplate>,
+}
+
+impl Resource for WorldTemplates {}
+
+impl WorldTemplates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a template under `name`, replacing any previous one.
+    pub fn insert(&mut self, name: &'static str, template: WorldTemplate) {
+        self.templates.insert(name, template);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&WorldTemplate> {
+        self.templates.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<WorldTemplate> {
+        self.templates.remove(name)
+    }
+
+    /// Creates a fresh world from the named template.
+    pub fn instantiate(&self, name: &str) -> Option<World> {
+        self.get(name).map(WorldTemplate::instantiate)
+    }
+
+    /// Iterates template names, in no particular order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.templates.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{component::Entity, world::Component};
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Parent(Entity);
+    impl Component for Parent {
+        fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) {
+            self.0 = map(self.0);
+        }
+    }
+
+    #[test]
+    fn instances_are_independent() {
+        let mut world = World::new(8);
+        world.add::<Position>();
+        let e = world.spawn();
+        world
+            .get_mut::<Position>()
+            .unwrap()
+            .add_entity(Position(1), e);
+        world.tags.add_tag("spawn", e);
+
+        let mut templates = WorldTemplates::new();
+        templates.insert("arena", world.as_template());
+
+        let mut a = templates.instantiate("arena").unwrap();
+        let b = templates.instantiate("arena").unwrap();
+        a.get_mut::<Position>().unwrap().set(Position(5), e);
+        let extra = a.spawn();
+        a.despawn(e);
+
+        assert!(!a.get::<Position>().unwrap().has(e));
+        assert_eq!(b.get::<Position>().unwrap().get(e), Some(&Position(1)));
+        assert!(b.tags.has_tag("spawn", &e));
+        assert_eq!(b.entity_count(), 1);
+        assert_eq!(a.entity_count(), 1);
+        assert_ne!(extra, e);
+
+        // The template itself is untouched
+        let template = templates.get("arena").unwrap();
+        assert_eq!(
+            template.world().get::<Position>().unwrap().get(e),
+            Some(&Position(1))
+        );
+        assert!(templates.instantiate("missing").is_none());
+    }
+
+    #[test]
+    fn snapshots_remap_links_between_selected_entities() {
+        let mut world = World::new(8);
+        world.add::<Position>();
+        world.add::<Parent>();
+        let outside = world.spawn();
+        let root = world.spawn();
+        let child = world.spawn();
+        let dead = world.spawn();
+        world.despawn(dead);
+        world.get_mut::<Position>().unwrap().set(Position(1), root);
+        world.get_mut::<Parent>().unwrap().set(Parent(root), child);
+        world
+            .get_mut::<Parent>()
+            .unwrap()
+            .set(Parent(outside), root);
+        world.tags.add_tag("door", child);
+
+        let prefab = world.snapshot_entities([root, child, dead, root]);
+        let local = prefab.world();
+        let (local_root, local_child) = (Entity::new(0, 0), Entity::new(1, 0));
+        assert_eq!(local.entity_count(), 2);
+        assert_eq!(
+            local.get::<Position>().unwrap().get(local_root),
+            Some(&Position(1))
+        );
+        assert_eq!(
+            local.get::<Parent>().unwrap().get(local_child),
+            Some(&Parent(local_root))
+        );
+        assert_eq!(
+            local.get::<Parent>().unwrap().get(local_root),
+            Some(&Parent(outside))
+        );
+        assert!(local.tags.has_tag("door", &local_child));
+        assert!(local.get::<Position>().unwrap().added.is_empty());
+
+        let stamped = world.spawn_template(&prefab);
+        assert_eq!(stamped.len(), 2);
+        assert_eq!(world.entity_count(), 5);
+        assert_eq!(
+            world.get::<Parent>().unwrap().get(stamped[1]),
+            Some(&Parent(stamped[0]))
+        );
+        assert!(world.tags.has_tag("door", &stamped[1]));
+    }
+}
@@ -0,0 +1,259 @@
+// Timer and cooldown components
+
+use crate::{
+    component::Entity,
+    world::{Component, World},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimerMode {
+    /// Finishes once and stays finished until reset.
+    Once,
+    /// Wraps around on finishing, carrying over any excess time.
+    Repeating,
+}
+
+/// Counts up to a duration (in whatever unit `dt` is given in, usually seconds).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Timer {
+    duration: f32,
+    elapsed: f32,
+    mode: TimerMode,
+    paused: bool,
+    finished: bool,
+    times_finished_this_tick: u32,
+}
+
+impl Component for Timer {}
+
+impl Timer {
+    pub fn new(duration: f32, mode: TimerMode) -> Self {
+        Self {
+            duration,
+            elapsed: 0.0,
+            mode,
+            paused: false,
+            finished: false,
+            times_finished_this_tick: 0,
+        }
+    }
+
+    pub fn once(duration: f32) -> Self {
+        Self::new(duration, TimerMode::Once)
+    }
+
+    pub fn repeating(duration: f32) -> Self {
+        Self::new(duration, TimerMode::Repeating)
+    }
+
+    /// Advances the timer, returning how many times it finished during this tick.
+    /// A repeating timer can finish several times if `dt` spans multiple durations.
+    pub fn tick(&mut self, dt: f32) -> u32 {
+        self.times_finished_this_tick = 0;
+        if self.paused || (self.mode == TimerMode::Once && self.finished) {
+            return 0;
+        }
+        self.elapsed += dt;
+        if self.elapsed < self.duration {
+            return 0;
+        }
+        match self.mode {
+            TimerMode::Once => {
+                self.elapsed = self.duration;
+                self.finished = true;
+                self.times_finished_this_tick = 1;
+            }
+            TimerMode::Repeating => {
+                if self.duration > 0.0 {
+                    let times = (self.elapsed / self.duration).floor();
+                    self.elapsed -= times * self.duration;
+                    self.times_finished_this_tick = times as u32;
+                } else {
+                    self.elapsed = 0.0;
+                    self.times_finished_this_tick = 1;
+                }
+                self.finished = true;
+            }
+        }
+        self.times_finished_this_tick
+    }
+
+    /// Returns true if the timer has finished (ever, for `Once`; last tick, for `Repeating`).
+    pub fn finished(&self) -> bool {
+        match self.mode {
+            TimerMode::Once => self.finished,
+            TimerMode::Repeating => self.times_finished_this_tick > 0,
+        }
+    }
+
+    /// Returns true only on the tick the timer finished.
+    pub fn just_finished(&self) -> bool {
+        self.times_finished_this_tick > 0
+    }
+
+    /// Returns how many times the timer finished during the last tick.
+    pub fn times_finished_this_tick(&self) -> u32 {
+        self.times_finished_this_tick
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn remaining(&self) -> f32 {
+        (self.duration - self.elapsed).max(0.0)
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Returns progress through the current cycle in the range `0..=1`.
+    pub fn fraction(&self) -> f32 {
+        if self.duration > 0.0 {
+            (self.elapsed / self.duration).min(1.0)
+        } else {
+            1.0
+        }
+    }
+
+    pub fn mode(&self) -> TimerMode {
+        self.mode
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Restarts the timer from zero.
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+        self.times_finished_this_tick = 0;
+    }
+}
+
+/// Blocks an action for a duration after it's been triggered.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cooldown {
+    duration: f32,
+    remaining: f32,
+}
+
+impl Component for Cooldown {}
+
+impl Cooldown {
+    /// Creates a cooldown that starts ready.
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            remaining: 0.0,
+        }
+    }
+
+    pub fn tick(&mut self, dt: f32) {
+        self.remaining = (self.remaining - dt).max(0.0);
+    }
+
+    pub fn ready(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    /// Starts the cooldown if it's ready, returning true if the action may run.
+    pub fn trigger(&mut self) -> bool {
+        if !self.ready() {
+            return false;
+        }
+        self.remaining = self.duration;
+        true
+    }
+
+    pub fn remaining(&self) -> f32 {
+        self.remaining
+    }
+
+    /// Makes the cooldown ready immediately.
+    pub fn reset(&mut self) {
+        self.remaining = 0.0;
+    }
+}
+
+/// Maintenance system: ticks every `Timer` in the world by `dt`, and pushes each
+/// entity whose timer finished this tick onto `finished` (once per finish).
+pub fn tick_timers(world: &mut World, dt: f32, finished: &mut Vec<Entity>) {
+    for (entity, timer) in world.iter_mut::<Timer>() {
+        for _ in 0..timer.tick(dt) {
+            finished.push(entity);
+        }
+    }
+}
+
+/// Maintenance system: ticks every `Cooldown` in the world by `dt`.
+pub fn tick_cooldowns(world: &mut World, dt: f32) {
+    for (_entity, cooldown) in world.iter_mut::<Cooldown>() {
+        cooldown.tick(dt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn once_finishes_a_single_time() {
+        let mut timer = Timer::once(1.0);
+        assert_eq!(timer.tick(0.6), 0);
+        assert_eq!(timer.tick(0.6), 1);
+        assert!(timer.finished());
+        assert!(timer.just_finished());
+        assert_eq!(timer.tick(0.6), 0);
+        assert!(timer.finished());
+        assert!(!timer.just_finished());
+    }
+
+    #[test]
+    fn repeating_carries_excess() {
+        let mut timer = Timer::repeating(1.0);
+        assert_eq!(timer.tick(2.5), 2);
+        assert!((timer.elapsed() - 0.5).abs() < f32::EPSILON);
+        assert_eq!(timer.tick(0.5), 1);
+        assert_eq!(timer.tick(0.1), 0);
+        assert!(!timer.finished());
+    }
+
+    #[test]
+    fn cooldown_trigger() {
+        let mut cooldown = Cooldown::new(1.0);
+        assert!(cooldown.trigger());
+        assert!(!cooldown.trigger());
+        cooldown.tick(1.0);
+        assert!(cooldown.trigger());
+    }
+
+    #[test]
+    fn tick_timers_reports_finished() {
+        let mut world = World::new(4);
+        world.add::<Timer>();
+        let a = world.spawn();
+        let b = world.spawn();
+        let timers = world.get_mut::<Timer>().unwrap();
+        timers.add_entity(Timer::once(1.0), a);
+        timers.add_entity(Timer::repeating(0.25), b);
+
+        let mut finished = Vec::new();
+        tick_timers(&mut world, 0.5, &mut finished);
+        assert_eq!(finished, vec![b, b]);
+
+        finished.clear();
+        tick_timers(&mut world, 0.5, &mut finished);
+        assert_eq!(finished, vec![a, b, b]);
+    }
+}
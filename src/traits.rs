@@ -0,0 +1,156 @@
+// Querying components by a trait they implement
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use crate::{
+    component::{Entity, Storage},
+    world::Component,
+};
+
+/// Reads a type-erased `Storage<T>` as `Dyn` trait objects.
+pub(crate) trait Caster<Dyn: ?Sized>: Send + Sync {
+    fn iter<'a>(&self, storage: &'a dyn Any) -> Box<dyn Iterator<Item = (Entity, &'a Dyn)> + 'a>;
+    fn iter_mut<'a>(
+        &self,
+        storage: &'a mut dyn Any,
+    ) -> Box<dyn Iterator<Item = (Entity, &'a mut Dyn)> + 'a>;
+}
+
+struct Cast<T, Dyn: ?Sized> {
+    cast: fn(&T) -> &Dyn,
+    cast_mut: fn(&mut T) -> &mut Dyn,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T: Component, Dyn: ?Sized + 'static> Caster<Dyn> for Cast<T, Dyn> {
+    fn iter<'a>(&self, storage: &'a dyn Any) -> Box<dyn Iterator<Item = (Entity, &'a Dyn)> + 'a> {
+        let cast = self.cast;
+        let storage = storage.downcast_ref::<Storage<T>>().expect("type mismatch");
+        Box::new(
+            storage
+                .iter()
+                .map(move |(entity, data)| (entity, cast(data))),
+        )
+    }
+
+    fn iter_mut<'a>(
+        &self,
+        storage: &'a mut dyn Any,
+    ) -> Box<dyn Iterator<Item = (Entity, &'a mut Dyn)> + 'a> {
+        let cast_mut = self.cast_mut;
+        let storage = storage.downcast_mut::<Storage<T>>().expect("type mismatch");
+        Box::new(
+            storage
+                .iter_mut()
+                .map(move |(entity, data)| (entity, cast_mut(data))),
+        )
+    }
+}
+
+/// Casters for one trait, keyed by component type.
+type Casters<Dyn> = HashMap<TypeId, Arc<dyn Caster<Dyn>>>;
+
+/// Which component types implement which traits, for `World::iter_trait`.
+#[derive(Clone, Default)]
+pub(crate) struct TraitRegistry {
+    /// `Casters<Dyn>` keyed by the `TypeId` of `Dyn`.
+    traits: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl std::fmt::Debug for TraitRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraitRegistry")
+            .field("traits", &self.traits.len())
+            .finish()
+    }
+}
+
+impl TraitRegistry {
+    pub(crate) fn register<Dyn: ?Sized + 'static, T: Component>(
+        &mut self,
+        cast: fn(&T) -> &Dyn,
+        cast_mut: fn(&mut T) -> &mut Dyn,
+    ) {
+        let mut casters = self.casters::<Dyn>().cloned().unwrap_or_default();
+        casters.insert(
+            TypeId::of::<T>(),
+            Arc::new(Cast {
+                cast,
+                cast_mut,
+                _marker: PhantomData,
+            }),
+        );
+        self.traits.insert(TypeId::of::<Dyn>(), Arc::new(casters));
+    }
+
+    pub(crate) fn casters<Dyn: ?Sized + 'static>(&self) -> Option<&Casters<Dyn>> {
+        self.traits
+            .get(&TypeId::of::<Dyn>())?
+            .downcast_ref::<Casters<Dyn>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::{Component, World};
+
+    trait Damageable {
+        fn damage(&mut self, amount: u32);
+        fn health(&self) -> u32;
+    }
+
+    #[derive(Copy, Clone)]
+    struct Player(u32);
+    impl Component for Player {}
+    impl Damageable for Player {
+        fn damage(&mut self, amount: u32) {
+            self.0 = self.0.saturating_sub(amount);
+        }
+        fn health(&self) -> u32 {
+            self.0
+        }
+    }
+
+    #[derive(Copy, Clone)]
+    struct Crate {
+        hp: u32,
+        armor: u32,
+    }
+    impl Component for Crate {}
+    impl Damageable for Crate {
+        fn damage(&mut self, amount: u32) {
+            self.hp = self.hp.saturating_sub(amount.saturating_sub(self.armor));
+        }
+        fn health(&self) -> u32 {
+            self.hp
+        }
+    }
+
+    #[test]
+    fn iterates_all_implementors() {
+        let mut world = World::new(8);
+        world.register_trait::<dyn Damageable, Player>(|c| c, |c| c);
+        world.register_trait::<dyn Damageable, Crate>(|c| c, |c| c);
+        let player = world.spawn_entity().with(Player(10)).build();
+        let crate_ = world
+            .spawn_entity()
+            .with(Crate { hp: 10, armor: 2 })
+            .build();
+
+        for (_, target) in world.iter_trait_mut::<dyn Damageable>() {
+            target.damage(5);
+        }
+
+        let mut health: Vec<_> = world
+            .iter_trait::<dyn Damageable>()
+            .map(|(entity, target)| (entity, target.health()))
+            .collect();
+        health.sort_by_key(|(entity, _)| entity.0);
+        assert_eq!(health, vec![(player, 5), (crate_, 7)]);
+    }
+}
@@ -0,0 +1,102 @@
+// Cached single-entity component watches for UI binding
+
+use std::marker::PhantomData;
+
+use crate::{
+    component::{ComponentTicks, Entity},
+    world::{Component, World},
+};
+
+/// Watches component `T` on a single entity.
+/// Remembers the component's change ticks as of the last poll, so polling is one storage
+/// lookup with no copy of the value, and UI code only rebuilds when something was written.
+#[derive(Copy, Clone, Debug)]
+pub struct Watch<T: Component> {
+    entity: Entity,
+    seen: Option<ComponentTicks>,
+    polled: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Component> Watch<T> {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            seen: None,
+            polled: false,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Returns the current value.
+    pub fn value<'w>(&self, world: &'w World) -> Option<&'w T> {
+        world.get::<T>()?.get(self.entity)
+    }
+
+    /// Returns true if the component was written since the last poll, even with the value
+    /// it already had. The component being added or removed counts as a change, and so
+    /// does the first poll. Like the `Changed` filter, a second write within the tick of
+    /// one already seen waits for a write in a later tick.
+    pub fn poll(&mut self, world: &World) -> bool {
+        let current = world
+            .get::<T>()
+            .and_then(|storage| storage.ticks(self.entity));
+        let changed = match (self.seen, current) {
+            _ if !self.polled => true,
+            (Some(seen), Some(now)) => now.is_added(seen.added) || now.is_changed(seen.changed),
+            (seen, now) => seen.is_some() != now.is_some(),
+        };
+        self.polled = true;
+        self.seen = current;
+        changed
+    }
+
+    /// Polls and calls `f` with the current value if it changed.
+    pub fn on_change(&mut self, world: &World, f: impl FnOnce(Option<&T>)) {
+        if self.poll(world) {
+            f(self.value(world));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[test]
+    fn poll_reports_changes() {
+        let mut world = World::new(4);
+        world.add::<Health>();
+        let e = world.spawn();
+        world.get_mut::<Health>().unwrap().add_entity(Health(10), e);
+
+        let mut watch = Watch::<Health>::new(e);
+        assert!(watch.poll(&world));
+        assert_eq!(watch.value(&world), Some(&Health(10)));
+        assert!(!watch.poll(&world));
+
+        world.increment_change_tick();
+        world.get_mut::<Health>().unwrap().set(Health(5), e);
+        let mut seen = None;
+        watch.on_change(&world, |v| seen = v.copied());
+        assert_eq!(seen, Some(Health(5)));
+
+        // Rewriting the same value is still a write
+        world.increment_change_tick();
+        world.get_mut::<Health>().unwrap().set(Health(5), e);
+        assert!(watch.poll(&world));
+        assert!(!watch.poll(&world));
+
+        world.get_mut::<Health>().unwrap().remove_entity(e);
+        assert!(watch.poll(&world));
+        assert_eq!(watch.value(&world), None);
+    }
+}
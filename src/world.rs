@@ -1,77 +1,1115 @@
 use std::{
     any::{Any, TypeId},
+    borrow::Cow,
     collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
 };
 
+use parking_lot::RwLock;
+
 use crate::{
-    component::{self, Entity, Storage},
+    builder::EntityBuilder,
+    bundle::Bundle,
+    commands::CommandBuffer,
+    component::{self, Entity, QuotaPolicy, Storage},
+    entity::{EntityMut, EntityRef},
+    events::{AnyEvents, Events},
+    fault::{Fault, FaultPolicy},
+    label::EntityLabels,
+    marker::{BitSet, Marker, Markers},
+    query::{Query, QueryData, QueryFilter, QueryWorld, Spawned},
+    registry::{ComponentRegistry, Field, StableId},
+    shared::SharedWorld,
     tags,
+    template::WorldTemplate,
+    traits::TraitRegistry,
 };
 
-/// Storage for components and tags, as well as basic entity management.
-#[derive(Debug)]
+#[cfg(feature = "json")]
+use crate::registry::PatchError;
+
+type SharedStorage = Arc<dyn Any + Send + Sync>;
+
+static NEXT_WORLD_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Hands out entity slots and tracks which are alive.
+/// Kept behind its own lock so systems holding `&World` can spawn.
+#[derive(Clone, Default)]
+struct EntityAllocator {
+    /// Current generation of every slot ever spawned.
+    generations: Vec<u32>,
+    /// Whether each slot currently holds a spawned entity.
+    alive: Vec<bool>,
+    live: usize,
+    /// Despawned slots, reused by `spawn` most recent first. May hold slots that were
+    /// since restored or trimmed; `spawn` skips those.
+    free: Vec<usize>,
+    /// Generation new slots start at, so handles to slots trimmed by `gc` never match.
+    base_generation: u32,
+}
+
+impl EntityAllocator {
+    fn spawn(&mut self) -> Entity {
+        while let Some(index) = self.free.pop() {
+            if index < self.alive.len() && !self.alive[index] {
+                self.alive[index] = true;
+                self.live += 1;
+                return Entity::new(index, self.generations[index]);
+            }
+        }
+        self.push_slot(true);
+        Entity::new(self.generations.len() - 1, self.base_generation)
+    }
+
+    fn push_slot(&mut self, alive: bool) {
+        self.generations.push(self.base_generation);
+        self.alive.push(alive);
+        if alive {
+            self.live += 1;
+        } else {
+            self.free.push(self.alive.len() - 1);
+        }
+    }
+
+    fn is_dead(&self, entity: Entity) -> bool {
+        let index = entity.index();
+        self.generations.get(index) != Some(&entity.generation()) || !self.alive[index]
+    }
+
+    fn alive(&self) -> usize {
+        self.live
+    }
+
+    /// Whether `entity` could have come from this allocator: its slot exists, or was
+    /// trimmed by `gc` after the handle was given out.
+    fn issued(&self, entity: Entity) -> bool {
+        entity.index() < self.generations.len() || entity.generation() < self.base_generation
+    }
+
+    fn release(&mut self, index: usize) {
+        self.generations[index] = match self.generations[index] {
+            Entity::MAX_GENERATION => 0,
+            generation => generation + 1,
+        };
+        self.alive[index] = false;
+        self.live -= 1;
+        self.free.push(index);
+    }
+
+    fn restore(&mut self, entity: Entity) {
+        let index = entity.index();
+        while self.generations.len() <= index {
+            self.push_slot(false);
+        }
+        self.generations[index] = entity.generation();
+        if !self.alive[index] {
+            self.alive[index] = true;
+            self.live += 1;
+        }
+    }
+
+    /// Drops dead slots from the end and stale free-list entries. Returns the number of
+    /// slots dropped.
+    fn gc(&mut self) -> usize {
+        let keep = self
+            .alive
+            .iter()
+            .rposition(|&alive| alive)
+            .map_or(0, |last| last + 1);
+        let trimmed = self.alive.len() - keep;
+        if let Some(&highest) = self.generations[keep..].iter().max() {
+            self.base_generation = self.base_generation.max(highest);
+        }
+        self.generations.truncate(keep);
+        self.alive.truncate(keep);
+        let alive = &self.alive;
+        let mut queued = vec![false; keep];
+        self.free.retain(|&index| {
+            index < keep && !alive[index] && !std::mem::replace(&mut queued[index], true)
+        });
+        self.generations.shrink_to_fit();
+        self.alive.shrink_to_fit();
+        self.free.shrink_to_fit();
+        trimmed
+    }
+}
+
+/// Type-erased component storage.
+/// Storages are shared between worlds forked from the same source (templates, clones)
+/// and copied on first write.
+#[derive(Debug, Clone)]
 struct AnyStorageEntry {
-    inner: Box<dyn Any>,
+    inner: SharedStorage,
+    type_name: &'static str,
+    clone_fn: fn(&dyn Any) -> SharedStorage,
     remove_fn: fn(&mut dyn Any, Entity),
+    has_fn: fn(&dyn Any, Entity) -> bool,
+    len_fn: fn(&dyn Any) -> usize,
+    tracked_fn: fn(&dyn Any) -> bool,
+    evicted_fn: fn(&dyn Any) -> &[Entity],
+    clear_tracked_fn: fn(&mut dyn Any),
+    clear_fn: fn(&mut dyn Any),
+    set_tick_fn: fn(&mut dyn Any, u64),
+    copy_fn: fn(&dyn Any, &mut World, &EntityMap),
+}
+
+/// Source to destination entities for copying between worlds, in copy order.
+struct EntityMap {
+    pairs: Vec<(Entity, Entity)>,
+    lookup: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    fn new(pairs: Vec<(Entity, Entity)>) -> Self {
+        let lookup = pairs.iter().copied().collect();
+        Self { pairs, lookup }
+    }
+
+    /// Entities outside the map are left as they are.
+    fn get(&self, entity: Entity) -> Entity {
+        self.lookup.get(&entity).copied().unwrap_or(entity)
+    }
+}
+
+impl AnyStorageEntry {
+    fn new<T: Component>(mut storage: Storage<T>) -> Self {
+        storage.set_validator(T::validate);
+        Self {
+            inner: Arc::new(storage),
+            type_name: std::any::type_name::<T>(),
+            clone_fn: |any: &dyn Any| {
+                Arc::new(
+                    any.downcast_ref::<Storage<T>>()
+                        .expect("type mismatch")
+                        .clone(),
+                )
+            },
+            remove_fn: |any: &mut dyn Any, e: Entity| {
+                let storage = any.downcast_mut::<Storage<T>>().expect("type mismatch");
+                let _ = storage.remove_entity(e);
+            },
+            has_fn: |any: &dyn Any, e: Entity| {
+                any.downcast_ref::<Storage<T>>()
+                    .expect("type mismatch")
+                    .has(e)
+            },
+            len_fn: |any: &dyn Any| {
+                any.downcast_ref::<Storage<T>>()
+                    .expect("type mismatch")
+                    .len()
+            },
+            tracked_fn: |any: &dyn Any| {
+                let storage = any.downcast_ref::<Storage<T>>().expect("type mismatch");
+                !storage.added.is_empty()
+                    || !storage.removed.is_empty()
+                    || !storage.evicted.is_empty()
+            },
+            evicted_fn: |any: &dyn Any| {
+                &any.downcast_ref::<Storage<T>>()
+                    .expect("type mismatch")
+                    .evicted
+            },
+            clear_tracked_fn: |any: &mut dyn Any| {
+                any.downcast_mut::<Storage<T>>()
+                    .expect("type mismatch")
+                    .clear_tracked();
+            },
+            clear_fn: |any: &mut dyn Any| {
+                any.downcast_mut::<Storage<T>>()
+                    .expect("type mismatch")
+                    .clear();
+            },
+            set_tick_fn: |any: &mut dyn Any, tick: u64| {
+                any.downcast_mut::<Storage<T>>()
+                    .expect("type mismatch")
+                    .set_change_tick(tick);
+            },
+            copy_fn: |any: &dyn Any, dst: &mut World, map: &EntityMap| {
+                let storage = any.downcast_ref::<Storage<T>>().expect("type mismatch");
+                for &(from, to) in &map.pairs {
+                    let Some(&component) = storage.get(from) else {
+                        continue;
+                    };
+                    let mut component = component;
+                    component.map_entities(&mut |entity| map.get(entity));
+                    dst.add::<T>();
+                    dst.get_mut::<T>()
+                        .expect("storage was just added")
+                        .set(component, to);
+                }
+            },
+        }
+    }
+
+    fn storage(&self) -> &dyn Any {
+        self.inner.as_ref()
+    }
+
+    /// Mutable access to the storage, copying it first if another world shares it.
+    /// Writes through it are stamped with `tick`.
+    fn storage_mut(&mut self, tick: u64) -> &mut dyn Any {
+        if Arc::get_mut(&mut self.inner).is_none() {
+            self.inner = (self.clone_fn)(self.inner.as_ref());
+        }
+        let set_tick = self.set_tick_fn;
+        let storage = Arc::get_mut(&mut self.inner).expect("storage is uniquely owned after copy");
+        set_tick(storage, tick);
+        storage
+    }
 }
 
-#[derive(Debug)]
+/// Owns component storages, tags and the component registry.
+/// `World` is `Send + Sync`, so it can be moved to another thread or shared by reference;
+/// the assertions below keep it that way.
 pub struct World {
     pub tags: tags::EntityTags,
+    pub labels: EntityLabels,
+    pub registry: ComponentRegistry,
     map: HashMap<TypeId, AnyStorageEntry>,
-    dead_entities: HashSet<usize>,
-    next_entity_id: usize,
+    entities: RwLock<EntityAllocator>,
+    fault_policy: FaultPolicy,
+    faults: Vec<Fault>,
+    /// Despawns since the last `clear_tracked`.
+    despawned: Vec<EntityDespawned>,
+    /// Event queues by event type. Not carried over by `fork`.
+    events: HashMap<TypeId, Box<dyn AnyEvents>>,
+    traits: TraitRegistry,
+    /// Stamped on component writes; advanced by `increment_change_tick`.
+    change_tick: u64,
+    /// What `Added`/`Changed` filters compare against, usually when the system last ran.
+    last_change_tick: u64,
+    /// Unique to this world, so cached queries notice being handed another one.
+    id: u64,
+    /// Bumped whenever pointers cached by a `QueryState` may have gone stale: a storage was
+    /// added, or the world was forked and its storages became shared.
+    layout: AtomicU64,
+    /// What happens to components linking to a despawned entity, by component type.
+    links: Vec<LinkRule>,
+    /// Component sets at most one entity may have, checked on insert in debug builds.
+    #[cfg(debug_assertions)]
+    unique: Vec<UniqueRule>,
+    markers: Markers,
 
     size: usize,
 }
 
+// Compile-time auto-trait guarantees. Adding a field that isn't `Send + Sync` breaks the build.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<World>();
+    assert_send_sync::<tags::EntityTags>();
+    assert_send_sync::<ComponentRegistry>();
+    assert_send_sync::<WorldTemplate>();
+    assert_send_sync::<crate::resource::Resources>();
+    assert_send_sync::<SharedWorld>();
+};
+
+impl std::fmt::Debug for World {
+    /// Summarizes entity and per-component counts rather than dumping storages.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut components: Vec<_> = self.component_counts().collect();
+        components.sort();
+        f.debug_struct("World")
+            .field("entities", &self.entity_count())
+            .field("slots", &self.entities.read().generations.len())
+            .field("size", &self.size)
+            .field("components", &components)
+            .field("tags", &self.tags)
+            .field("labels", &self.labels)
+            .field("markers", &self.markers)
+            .finish()
+    }
+}
+
+impl Clone for World {
+    /// Deep copy. Component storages are copied lazily, the first time either world writes
+    /// to them, so cloning a big world to snapshot it is cheap. Event queues start empty.
+    fn clone(&self) -> Self {
+        self.fork()
+    }
+}
+
+/// Event recorded for every despawn, kept until the next `World::clear_tracked`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntityDespawned {
+    pub entity: Entity,
+    /// Set by `World::despawn_with_reason`, e.g. `"killed"` or `"expired"`.
+    pub reason: Option<Cow<'static, str>>,
+}
+
+/// What despawning an entity does to components that link to it, like a database's
+/// foreign key rules. Links are the entity handles a component visits in
+/// `Component::map_entities`. Set per component type with `World::set_link_policy`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LinkPolicy {
+    /// Despawn the entities holding a link too, e.g. children with their parent.
+    Cascade,
+    /// Remove the linking component from the entities holding it.
+    Nullify,
+    /// Refuse the despawn, raising a `Fault::Linked`, while any entity holds a link.
+    Restrict,
+}
+
+#[derive(Copy, Clone)]
+struct LinkRule {
+    component: TypeId,
+    policy: LinkPolicy,
+    /// Entities whose component links to the given one.
+    holders: fn(&World, Entity) -> Vec<Entity>,
+    remove: fn(&mut World, Entity),
+}
+
+fn link_holders<T: Component>(world: &World, target: Entity) -> Vec<Entity> {
+    world
+        .iter::<T>()
+        .filter(|&(_, &data)| {
+            let mut data = data;
+            let mut linked = false;
+            data.map_entities(&mut |entity| {
+                linked |= entity == target;
+                entity
+            });
+            linked
+        })
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+/// Component types checked together by `World::require_unique`: a tuple of one to four.
+pub trait ComponentSet: 'static {
+    fn type_ids() -> Vec<TypeId>;
+    /// Entities with every component in the set.
+    fn holders(world: &World) -> Vec<Entity>;
+}
+
+macro_rules! impl_component_set {
+    ($first:ident $(, $rest:ident)*) => {
+        impl<$first: Component $(, $rest: Component)*> ComponentSet for ($first, $($rest,)*) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![TypeId::of::<$first>() $(, TypeId::of::<$rest>())*]
+            }
+
+            fn holders(world: &World) -> Vec<Entity> {
+                world
+                    .iter::<$first>()
+                    .map(|(entity, _)| entity)
+                    .filter(|&_entity| true $(&& world.has::<$rest>(_entity))*)
+                    .collect()
+            }
+        }
+    };
+}
+
+impl_component_set!(A);
+impl_component_set!(A, B);
+impl_component_set!(A, B, C);
+impl_component_set!(A, B, C, D);
+
+#[cfg(debug_assertions)]
+#[derive(Clone)]
+struct UniqueRule {
+    components: Vec<TypeId>,
+    name: &'static str,
+    holders: fn(&World) -> Vec<Entity>,
+}
+
 /// Which backing storage to use for a component type.
 /// Pick per component depending on density/access patterns.
 pub enum ComponentStorageKind {
     Sparse,
+    /// Sparse index allocated in pages as entity ranges are first used. Between `Sparse`
+    /// and `HashMap` in cost, for large id spaces with scattered entities.
+    Paged,
     HashMap,
+    /// Hashmap-indexed storage that iterates in insertion order regardless of removals.
+    /// For deterministic runs over low-density components.
+    OrderedHashMap,
+    /// Sparse storage holding at most this many entries; inserting past it evicts the
+    /// oldest. For short-lived entities like particles and decals. Evicted entities stay
+    /// alive until `World::despawn_evicted`, so call it each frame if nothing else
+    /// despawns them.
+    Ring(usize),
+    /// Sparse storage whose values never move, allocated in chunks of this many entries.
+    /// For FFI consumers holding component pointers between frames.
+    Pool(usize),
 }
 
 #[allow(dead_code)]
 impl World {
-    /// Creates a new world.
+    /// Creates a new world. `size` is how many entities sparse storages make room for up
+    /// front; they grow past it as needed.
     pub fn new(size: usize) -> Self {
         World {
             map: HashMap::new(),
-            dead_entities: HashSet::new(),
-            next_entity_id: 0,
+            entities: RwLock::default(),
+            fault_policy: FaultPolicy::default(),
+            faults: Vec::new(),
+            despawned: Vec::new(),
+            events: HashMap::new(),
+            traits: TraitRegistry::default(),
+            change_tick: 1,
+            last_change_tick: 0,
+            id: NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed),
+            layout: AtomicU64::new(0),
+            links: Vec::new(),
+            #[cfg(debug_assertions)]
+            unique: Vec::new(),
+            markers: Markers::default(),
             tags: tags::EntityTags::new(),
+            labels: EntityLabels::new(),
+            registry: ComponentRegistry::new(),
             size,
         }
     }
 
+    /// Returns true if the entity was despawned, including stale handles to a slot that has
+    /// since been reused, and handles the world never spawned.
     pub fn is_dead(&self, entity: &component::Entity) -> bool {
-        self.dead_entities.contains(&entity.0)
+        self.entities.read().is_dead(*entity)
+    }
+
+    pub fn set_fault_policy(&mut self, policy: FaultPolicy) {
+        self.fault_policy = policy;
+    }
+
+    pub fn fault_policy(&self) -> FaultPolicy {
+        self.fault_policy
+    }
+
+    /// Drains faults gathered under `FaultPolicy::CollectErrors`.
+    pub fn take_faults(&mut self) -> Vec<Fault> {
+        std::mem::take(&mut self.faults)
+    }
+
+    #[track_caller]
+    fn fault(&mut self, fault: Fault) {
+        match self.fault_policy {
+            FaultPolicy::Panic => panic!("{fault}"),
+            FaultPolicy::LogAndSkip => eprintln!("sparse_ecs: {fault}"),
+            FaultPolicy::CollectErrors => self.faults.push(fault),
+        }
+    }
+
+    /// Returns true if the entity was spawned by this world and hasn't been despawned.
+    /// Use to validate handles that arrive from outside, e.g. the network or UI.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        !self.is_dead(&entity)
+    }
+
+    /// Creates a world sharing this world's storages, each copied on first write.
+    pub(crate) fn fork(&self) -> World {
+        self.layout.fetch_add(1, Ordering::Relaxed);
+        World {
+            tags: self.tags.clone(),
+            labels: self.labels.clone(),
+            registry: self.registry.clone(),
+            map: self.map.clone(),
+            entities: RwLock::new(self.entities.read().clone()),
+            fault_policy: self.fault_policy,
+            faults: Vec::new(),
+            despawned: self.despawned.clone(),
+            events: HashMap::new(),
+            traits: self.traits.clone(),
+            change_tick: self.change_tick,
+            last_change_tick: self.last_change_tick,
+            id: NEXT_WORLD_ID.fetch_add(1, Ordering::Relaxed),
+            layout: AtomicU64::new(0),
+            links: self.links.clone(),
+            #[cfg(debug_assertions)]
+            unique: self.unique.clone(),
+            markers: self.markers.clone(),
+            size: self.size,
+        }
+    }
+
+    /// Runs `f` against a staged copy of the world, keeping its changes only if it returns
+    /// `Ok`; on `Err` the world is left exactly as it was. For loaders and editor
+    /// operations that mustn't leave half-applied state:
+    /// `world.transaction(|tx| { let e = tx.spawn(); tx.insert(e, load(e)?); Ok(e) })`.
+    /// Staging forks the world, so storages are only copied once written; the entity
+    /// allocator, tags and labels are cloned up front. Event queues and faults aren't
+    /// staged: events sent inside are kept either way.
+    pub fn transaction<R, E>(
+        &mut self,
+        f: impl FnOnce(&mut World) -> Result<R, E>,
+    ) -> Result<R, E> {
+        let mut staged = self.fork();
+        staged.events = std::mem::take(&mut self.events);
+        staged.faults = std::mem::take(&mut self.faults);
+        match f(&mut staged) {
+            Ok(value) => {
+                *self = staged;
+                Ok(value)
+            }
+            Err(err) => {
+                self.events = staged.events;
+                self.faults = staged.faults;
+                Err(err)
+            }
+        }
+    }
+
+    /// Captures the world as a template that can be stamped into fresh worlds.
+    /// Cheap: storages are shared rather than copied until someone writes to them.
+    pub fn as_template(&self) -> WorldTemplate {
+        WorldTemplate { world: self.fork() }
+    }
+
+    /// Captures just the given entities, with their components, tags, labels and markers,
+    /// as a prefab.
+    /// The prefab's entities are numbered from 0 in the order given; dead and repeated
+    /// entities are skipped. Entity handles held by components are rewritten through
+    /// `Component::map_entities`, so links between selected entities (e.g. a parent)
+    /// point at the prefab's copies; links to anything outside the selection are kept
+    /// as they were.
+    pub fn snapshot_entities(&self, entities: impl IntoIterator<Item = Entity>) -> WorldTemplate {
+        let mut world = World::new(self.size);
+        world.registry = self.registry.clone();
+        let mut pairs = Vec::new();
+        let mut seen = HashSet::new();
+        for entity in entities {
+            if self.is_alive(entity) && seen.insert(entity) {
+                pairs.push((entity, world.spawn()));
+            }
+        }
+        self.copy_entities(&mut world, &EntityMap::new(pairs));
+        world.clear_tracked();
+        WorldTemplate { world }
+    }
+
+    /// Spawns a copy of every entity in `template` into this world, remapping entity
+    /// handles between them like `snapshot_entities`. Returns the new entities in the
+    /// template's slot order.
+    pub fn spawn_template(&mut self, template: &WorldTemplate) -> Vec<Entity> {
+        let spawned: Vec<Entity> = template.world.entities().map(|_| self.spawn()).collect();
+        self.stamp_template(template, &spawned);
+        spawned
+    }
+
+    /// Copies the template's entities, in slot order, onto existing entities, overwriting
+    /// the components they share. Components the template lacks are left alone.
+    pub(crate) fn stamp_template(&mut self, template: &WorldTemplate, onto: &[Entity]) {
+        let pairs = template
+            .world
+            .entities()
+            .zip(onto.iter().copied())
+            .collect();
+        template.world.copy_entities(self, &EntityMap::new(pairs));
+    }
+
+    fn copy_entities(&self, dst: &mut World, map: &EntityMap) {
+        for entry in self.map.values() {
+            (entry.copy_fn)(entry.storage(), dst, map);
+        }
+        for &(from, to) in &map.pairs {
+            for tag in self.tags.tags_of(from) {
+                dst.tags.add_tag(tag, to);
+            }
+            for label in self.labels.labels_of(from) {
+                dst.labels.add(label, to);
+            }
+        }
+        self.markers.copy_into(&mut dst.markers, &map.pairs);
     }
 
-    /// Spawns a new entity.
+    /// Freezes the world into a read-only view that is `Send + Sync` and cheap to clone.
+    pub fn into_shared(self) -> SharedWorld {
+        SharedWorld::new(self)
+    }
+
+    /// Returns the number of spawned entities that haven't been despawned.
+    pub fn entity_count(&self) -> usize {
+        self.entities.read().alive()
+    }
+
+    /// Iterates spawned entities that haven't been despawned, in slot order.
+    /// The list is taken up front, so spawns made while iterating aren't seen.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        let allocator = self.entities.read();
+        let alive: Vec<Entity> = allocator
+            .generations
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| allocator.alive[index])
+            .map(|(index, &generation)| Entity::new(index, generation))
+            .collect();
+        alive.into_iter()
+    }
+
+    /// Despawns every entity at once, e.g. between levels, returning how many there were.
+    /// Storages, tags and labels are emptied but kept, with each removal recorded as
+    /// usual, and the allocator is trimmed like `gc`, so old handles stay dead.
+    #[track_caller]
+    pub fn clear_entities(&mut self) -> usize {
+        let alive: Vec<Entity> = self.entities().collect();
+        for &entity in &alive {
+            self.release(entity, None);
+        }
+        let tick = self.change_tick;
+        for entry in self.map.values_mut() {
+            if (entry.len_fn)(entry.storage()) > 0 {
+                (entry.clear_fn)(entry.storage_mut(tick));
+            }
+        }
+        self.tags.clear();
+        self.labels.clear();
+        self.markers.clear();
+        self.gc();
+        alive.len()
+    }
+
+    /// Trims entity bookkeeping after heavy churn: despawned slots at the end of the slot
+    /// range are forgotten and the allocator's buffers shrink to fit. Handles to trimmed
+    /// slots stay dead, since new slots start past their generation. Returns the number of
+    /// slots trimmed.
+    pub fn gc(&mut self) -> usize {
+        self.entities.get_mut().gc()
+    }
+
+    /// Returns the number of entities in each component storage, keyed by the component's
+    /// registered name (or its type name if it isn't registered).
+    pub fn component_counts(&self) -> impl Iterator<Item = (&'static str, usize)> + '_ {
+        self.map.iter().map(|(type_id, entry)| {
+            let name = self
+                .registry
+                .name_of_id(*type_id)
+                .unwrap_or(entry.type_name);
+            (name, (entry.len_fn)(entry.storage()))
+        })
+    }
+
+    /// Spawns a new entity, reusing a despawned slot if there is one.
     pub fn spawn(&mut self) -> component::Entity {
-        let entity = component::Entity(self.next_entity_id);
-        self.next_entity_id += 1;
+        self.entities.get_mut().spawn()
+    }
+
+    /// Spawns a new entity through a shared reference, taking the allocator's lock.
+    /// Lets systems running in parallel spawn through `WorldView::spawn`.
+    pub(crate) fn spawn_shared(&self) -> component::Entity {
+        self.entities.write().spawn()
+    }
+
+    /// Returns a read-only view of the entity, or `None` if it isn't alive.
+    pub fn entity(&self, entity: Entity) -> Option<EntityRef<'_>> {
+        self.is_alive(entity).then(|| EntityRef::new(self, entity))
+    }
+
+    /// Returns a read-write view of the entity, or `None` if it isn't alive.
+    pub fn entity_mut(&mut self, entity: Entity) -> Option<EntityMut<'_>> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        Some(EntityMut::new(self, entity))
+    }
+
+    /// Spawns an entity and returns a builder for adding its components and tags:
+    /// `world.spawn_entity().with(Position(0, 0)).tag("player").build()`.
+    pub fn spawn_entity(&mut self) -> EntityBuilder<'_> {
+        EntityBuilder::new(self)
+    }
+
+    /// Clears every storage's `added` and `removed` lists, the tags' change lists, and the
+    /// `despawned` events.
+    /// `Schedule::run` and `ParallelSchedule::run` call this at the end of each frame.
+    pub fn clear_tracked(&mut self) {
+        self.despawned.clear();
+        self.tags.clear_tracked();
+        let tick = self.change_tick;
+        for entry in self.map.values_mut() {
+            if (entry.tracked_fn)(entry.storage()) {
+                (entry.clear_tracked_fn)(entry.storage_mut(tick));
+            }
+        }
+    }
+
+    /// The tick component writes are currently stamped with. Starts at 1, so everything
+    /// written before the first `increment_change_tick` is newer than tick 0.
+    pub fn change_tick(&self) -> u64 {
+        self.change_tick
+    }
+
+    /// Advances the change tick, returning the new one. `Schedule` does this before each
+    /// system runs, so each system's writes get their own tick.
+    pub fn increment_change_tick(&mut self) -> u64 {
+        self.change_tick += 1;
+        self.change_tick
+    }
+
+    /// The tick `Added` and `Changed` query filters compare against: components added or
+    /// changed after it match. `Schedule` sets it to when the running system last ran.
+    pub fn last_change_tick(&self) -> u64 {
+        self.last_change_tick
+    }
+
+    pub fn set_last_change_tick(&mut self, tick: u64) {
+        self.last_change_tick = tick;
+    }
+
+    /// Sends an event, adding the queue for `T` if this is the first.
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) {
+        self.events_mut::<T>().send(event);
+    }
+
+    /// The queue for `T`, if one was added.
+    pub fn events<T: Send + Sync + 'static>(&self) -> Option<&Events<T>> {
+        self.events
+            .get(&TypeId::of::<T>())
+            .and_then(|events| events.as_any().downcast_ref())
+    }
+
+    /// The queue for `T`, added if missing.
+    pub fn events_mut<T: Send + Sync + 'static>(&mut self) -> &mut Events<T> {
+        self.events
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Events::<T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .expect("event queue type mismatch")
+    }
+
+    /// Swaps the buffers of every event queue, dropping events from two updates ago.
+    /// `Schedule::run` and `ParallelSchedule::run` call this at the end of each frame.
+    pub fn update_events(&mut self) {
+        for events in self.events.values_mut() {
+            events.update();
+        }
+    }
+
+    /// Applies and clears a buffer of deferred changes, in recorded order.
+    /// Components are `Copy`, so removals and despawns here run no destructors and the
+    /// flush never stalls on freeing component memory.
+    pub fn apply_commands(&mut self, commands: &mut CommandBuffer) {
+        commands.apply(self);
+    }
+
+    /// Spawns an entity with every component in the bundle.
+    pub fn spawn_bundle(&mut self, bundle: impl Bundle) -> Entity {
+        let entity = self.spawn();
+        bundle.insert(self, entity);
         entity
     }
 
+    /// Inserts every component in the bundle onto the entity, replacing existing ones.
+    pub fn insert_bundle(&mut self, entity: Entity, bundle: impl Bundle) {
+        bundle.insert(self, entity);
+    }
+
     /// Removes an entity from all component storage and tags.
-    /// Returns false if it was already marked dead.
+    /// Returns false if it was already dead or the handle is stale. Handles this world
+    /// never gave out are a fault.
+    #[track_caller]
     pub fn despawn(&mut self, entity: component::Entity) -> bool {
-        // Mark as dead for potential ID reuse
-        if !self.dead_entities.insert(entity.0) {
+        self.despawn_inner(entity, None)
+    }
+
+    /// Despawns like `despawn`, recording why in the `EntityDespawned` event.
+    #[track_caller]
+    pub fn despawn_with_reason(
+        &mut self,
+        entity: Entity,
+        reason: impl Into<Cow<'static, str>>,
+    ) -> bool {
+        self.despawn_inner(entity, Some(reason.into()))
+    }
+
+    /// Entities despawned since the last `clear_tracked`, in order, for on-kill triggers
+    /// and analytics.
+    pub fn despawned(&self) -> &[EntityDespawned] {
+        &self.despawned
+    }
+
+    #[track_caller]
+    fn despawn_inner(&mut self, entity: Entity, reason: Option<Cow<'static, str>>) -> bool {
+        if !self.despawnable(entity) {
+            return false;
+        }
+        if self.links.is_empty() {
+            self.destroy(entity, reason);
+            return true;
+        }
+        let Some(doomed) = self.resolve_links(entity) else {
             return false;
+        };
+        for entity in doomed {
+            self.destroy(entity, reason.clone());
+        }
+        true
+    }
+
+    /// Returns true if `entity` is alive. Despawning twice is fine, but raises a fault for
+    /// handles this world never gave out.
+    #[track_caller]
+    fn despawnable(&mut self, entity: Entity) -> bool {
+        if !self.is_dead(&entity) {
+            return true;
         }
+        if !self.entities.get_mut().issued(entity) {
+            self.fault(Fault::DeadEntity {
+                op: "despawn",
+                entity,
+            });
+        }
+        false
+    }
+
+    /// Frees a live entity and drops its components, tags and labels.
+    fn destroy(&mut self, entity: Entity, reason: Option<Cow<'static, str>>) {
+        self.release(entity, reason);
 
         // Remove entity from all component storages
+        // Check first so storages shared with other worlds aren't copied needlessly
+        let tick = self.change_tick;
         for entry in self.map.values_mut() {
-            (entry.remove_fn)(entry.inner.as_mut(), entity);
+            if (entry.has_fn)(entry.storage(), entity) {
+                (entry.remove_fn)(entry.storage_mut(tick), entity);
+            }
         }
 
-        // Remove all tags associated with the entity
+        self.forget(entity);
+    }
+
+    /// Drops the tags, labels and markers of a despawned entity.
+    fn forget(&mut self, entity: Entity) {
         self.tags.remove_all_tags(&entity);
-        true
+        self.labels.remove_all(entity);
+        self.markers.remove_all(entity.index());
+    }
+
+    /// Applies link policies for despawning `entity`: returns it along with every entity
+    /// cascading from it, after removing nullified links. Changes nothing and returns
+    /// `None` if a restricted link would be left dangling.
+    #[track_caller]
+    fn resolve_links(&mut self, entity: Entity) -> Option<Vec<Entity>> {
+        let links = self.links.clone();
+        let cascades: Vec<_> = links
+            .iter()
+            .filter(|rule| rule.policy == LinkPolicy::Cascade)
+            .collect();
+        let mut doomed = vec![entity];
+        let mut seen = HashSet::from([entity]);
+        let mut next = 0;
+        while let Some(&target) = doomed.get(next) {
+            next += 1;
+            for rule in &cascades {
+                for holder in (rule.holders)(self, target) {
+                    if seen.insert(holder) {
+                        doomed.push(holder);
+                    }
+                }
+            }
+        }
+        let mut nullified = Vec::new();
+        for &target in &doomed {
+            for rule in &links {
+                let mut outside = (rule.holders)(self, target)
+                    .into_iter()
+                    .filter(|holder| !seen.contains(holder));
+                match rule.policy {
+                    LinkPolicy::Cascade => {}
+                    LinkPolicy::Nullify => {
+                        nullified.extend(outside.map(|holder| (rule.remove, holder)));
+                    }
+                    LinkPolicy::Restrict => {
+                        if let Some(holder) = outside.next() {
+                            self.fault(Fault::Linked {
+                                entity: target,
+                                holder,
+                            });
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+        for (remove, holder) in nullified {
+            remove(self, holder);
+        }
+        Some(doomed)
+    }
+
+    /// Sets what despawning an entity does to `T`s linking to it, replacing any earlier
+    /// policy for `T`. Without one, links are left dangling. Each despawn then scans
+    /// every `T`, so keep policies to link components that are few or small.
+    pub fn set_link_policy<T: Component>(&mut self, policy: LinkPolicy) {
+        let rule = LinkRule {
+            component: TypeId::of::<T>(),
+            policy,
+            holders: link_holders::<T>,
+            remove: |world, holder| {
+                world.remove::<T>(holder);
+            },
+        };
+        self.links.retain(|known| known.component != rule.component);
+        self.links.push(rule);
+    }
+
+    /// Declares that at most one entity may have every component in `S` at once, e.g.
+    /// `world.require_unique::<(Camera, Active)>()` so two cameras can't both be active.
+    /// In debug builds, `insert` panics before giving an entity the last component of the
+    /// set while another entity already has them all. Writes straight to a storage aren't
+    /// checked, and release builds don't keep the rule at all.
+    pub fn require_unique<S: ComponentSet>(&mut self) {
+        #[cfg(debug_assertions)]
+        self.add_unique_rule::<S>();
+    }
+
+    #[cfg(debug_assertions)]
+    fn add_unique_rule<S: ComponentSet>(&mut self) {
+        let components = S::type_ids();
+        if !self.unique.iter().any(|rule| rule.components == components) {
+            self.unique.push(UniqueRule {
+                components,
+                name: std::any::type_name::<S>(),
+                holders: S::holders,
+            });
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[track_caller]
+    fn check_unique<T: Component>(&self, entity: Entity) {
+        let inserted = TypeId::of::<T>();
+        for rule in &self.unique {
+            let completes = rule.components.contains(&inserted)
+                && rule.components.iter().all(|&id| {
+                    id == inserted
+                        || self
+                            .map
+                            .get(&id)
+                            .is_some_and(|entry| (entry.has_fn)(entry.storage(), entity))
+                });
+            if !completes {
+                continue;
+            }
+            if let Some(other) = (rule.holders)(self).into_iter().find(|&e| e != entity) {
+                panic!(
+                    "{entity:?} can't have all of {} while {other:?} does",
+                    rule.name
+                );
+            }
+        }
+    }
+
+    /// Makes exactly this entity (index and generation) alive, e.g. when replaying a log.
+    /// Slots skipped over on the way are left dead and free.
+    pub(crate) fn restore_entity(&mut self, entity: Entity) {
+        self.entities.get_mut().restore(entity);
+    }
+
+    /// Raw pointers to the given storages, each copied first if another world shares it.
+    /// The parallel executor hands these to systems with disjoint write access.
+    pub(crate) fn storage_ptrs(
+        &mut self,
+        types: &HashSet<TypeId>,
+    ) -> HashMap<TypeId, *mut dyn Any> {
+        let tick = self.change_tick;
+        self.map
+            .iter_mut()
+            .filter(|(key, _)| types.contains(key))
+            .map(|(key, entry)| (*key, entry.storage_mut(tick) as *mut dyn Any))
+            .collect()
+    }
+
+    /// Identifies this world and its storage layout; see `layout`.
+    pub(crate) fn layout_key(&self) -> (u64, u64) {
+        (self.id, self.layout.load(Ordering::Relaxed))
+    }
+
+    /// Functions stamping the given storages with a change tick, for writers that hold on
+    /// to storage pointers across ticks.
+    pub(crate) fn tick_fns(
+        &self,
+        types: &HashSet<TypeId>,
+    ) -> HashMap<TypeId, fn(&mut dyn Any, u64)> {
+        self.map
+            .iter()
+            .filter(|(key, _)| types.contains(key))
+            .map(|(key, entry)| (*key, entry.set_tick_fn))
+            .collect()
+    }
+
+    /// Removes the entity's component of the given type, if the world has that storage.
+    pub(crate) fn remove_by_type_id(&mut self, type_id: TypeId, entity: Entity) {
+        if let Some(entry) = self.map.get_mut(&type_id)
+            && (entry.has_fn)(entry.storage(), entity)
+        {
+            (entry.remove_fn)(entry.storage_mut(self.change_tick), entity);
+        }
+    }
+
+    /// Bumps the slot's generation so existing handles go stale, and frees it for reuse.
+    fn release(&mut self, entity: Entity, reason: Option<Cow<'static, str>>) {
+        self.despawned.push(EntityDespawned { entity, reason });
+        self.entities.get_mut().release(entity.index());
+    }
+
+    /// Despawns entities that a quota (e.g. a `Ring` storage) evicted since the last
+    /// `clear_tracked`, returning how many were despawned. Ones already despawned are
+    /// skipped, so it's safe to call more than once per frame.
+    #[track_caller]
+    pub fn despawn_evicted(&mut self) -> usize {
+        let evicted: Vec<Entity> = self
+            .map
+            .values()
+            .flat_map(|entry| (entry.evicted_fn)(entry.storage()))
+            .copied()
+            .collect();
+        evicted
+            .into_iter()
+            .filter(|&entity| self.is_alive(entity) && self.despawn(entity))
+            .count()
+    }
+
+    /// Despawns every entity with a `T` for which `predicate` returns true, returning how
+    /// many were despawned. The predicate can look at the rest of the world, e.g. to skip
+    /// entities holding some other component. Each storage is visited once for the batch,
+    /// unless link policies are set, which despawn one at a time.
+    /// Rows whose entity isn't alive are skipped, with the same faults as `despawn`.
+    #[track_caller]
+    pub fn despawn_where<T: Component>(
+        &mut self,
+        mut predicate: impl FnMut(&World, Entity, &T) -> bool,
+    ) -> usize {
+        // Rows can outlive their entity, e.g. when added straight to a storage
+        let (doomed, dead): (Vec<Entity>, Vec<Entity>) = self
+            .iter::<T>()
+            .filter(|&(entity, data)| predicate(self, entity, data))
+            .map(|(entity, _)| entity)
+            .partition(|&entity| self.is_alive(entity));
+        for entity in dead {
+            self.despawnable(entity);
+        }
+        if doomed.is_empty() {
+            return 0;
+        }
+        if !self.links.is_empty() {
+            // Earlier despawns may cascade to later ones
+            return doomed
+                .into_iter()
+                .filter(|&entity| self.is_alive(entity) && self.despawn_inner(entity, None))
+                .count();
+        }
+        for &entity in &doomed {
+            self.release(entity, None);
+        }
+        let tick = self.change_tick;
+        for entry in self.map.values_mut() {
+            if !doomed.iter().any(|&e| (entry.has_fn)(entry.storage(), e)) {
+                continue;
+            }
+            let remove = entry.remove_fn;
+            let storage = entry.storage_mut(tick);
+            for &entity in &doomed {
+                remove(storage, entity);
+            }
+        }
+        for &entity in &doomed {
+            self.forget(entity);
+        }
+        doomed.len()
     }
 
     /// Adds a component type to the world.
@@ -83,16 +1121,8 @@ impl World {
         if self.map.contains_key(&key) {
             return false;
         }
-        self.map.insert(
-            key,
-            AnyStorageEntry {
-                inner: Box::new(set),
-                remove_fn: |any: &mut dyn Any, e: Entity| {
-                    let storage = any.downcast_mut::<Storage<T>>().expect("type mismatch");
-                    let _ = storage.remove_entity(e);
-                },
-            },
-        );
+        *self.layout.get_mut() += 1;
+        self.map.insert(key, AnyStorageEntry::new(set));
         debug_assert!(self.map.contains_key(&key), "Component not added to World2");
         true
     }
@@ -104,28 +1134,148 @@ impl World {
         if self.map.contains_key(&key) {
             return false;
         }
+        *self.layout.get_mut() += 1;
         let storage: Storage<T> = match kind {
             ComponentStorageKind::Sparse => Storage::<T>::new_sparse(self.size),
+            ComponentStorageKind::Paged => Storage::<T>::new_paged(),
             ComponentStorageKind::HashMap => Storage::<T>::new_hashmap(),
+            ComponentStorageKind::OrderedHashMap => Storage::<T>::new_ordered_hashmap(),
+            ComponentStorageKind::Ring(capacity) => {
+                let mut storage = Storage::<T>::new_sparse(self.size);
+                storage.set_quota(capacity, QuotaPolicy::EvictOldest);
+                storage
+            }
+            ComponentStorageKind::Pool(chunk_size) => {
+                Storage::<T>::new_pool(self.size, chunk_size)
+            }
         };
-        self.map.insert(
-            key,
-            AnyStorageEntry {
-                inner: Box::new(storage),
-                remove_fn: |any: &mut dyn Any, e: Entity| {
-                    let storage = any.downcast_mut::<Storage<T>>().expect("type mismatch");
-                    let _ = storage.remove_entity(e);
-                },
-            },
-        );
+        self.map.insert(key, AnyStorageEntry::new(storage));
+        true
+    }
+
+    /// Sets the entity's `T`, replacing any existing value.
+    /// Adds the storage (sparse) if this is the first `T` in the world.
+    /// Inserting onto a dead entity is a fault.
+    #[track_caller]
+    pub fn insert<T: Component>(&mut self, entity: Entity, value: T) {
+        if self.is_dead(&entity) {
+            self.fault(Fault::DeadEntity {
+                op: "insert",
+                entity,
+            });
+            return;
+        }
+        #[cfg(debug_assertions)]
+        self.check_unique::<T>(entity);
+        self.add::<T>();
+        self.get_mut::<T>()
+            .expect("storage was just added")
+            .set(value, entity);
+    }
+
+    /// Inserts the default `T` set with `ComponentRegistry::set_default`, replacing any
+    /// existing value. Returns false, inserting nothing, if `T` has no default.
+    #[track_caller]
+    pub fn insert_default<T: Component>(&mut self, entity: Entity) -> bool {
+        let Some(value) = self.registry.default_of::<T>() else {
+            return false;
+        };
+        self.insert(entity, value);
         true
     }
 
+    /// Removes and returns the entity's `T`, if it has one.
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        // Check first so a storage shared with another world isn't copied needlessly
+        if !self.has::<T>(entity) {
+            return None;
+        }
+        self.get_mut::<T>()?.remove_entity(entity)
+    }
+
+    /// Registers a hook run whenever an entity gains a `T`, e.g. to keep a spatial index
+    /// in sync. Adds the storage (sparse) if needed. See `Storage::on_insert`.
+    pub fn on_insert<T: Component>(&mut self, hook: impl Fn(Entity, &T) + Send + Sync + 'static) {
+        self.add::<T>();
+        self.get_mut::<T>()
+            .expect("storage was just added")
+            .on_insert(hook);
+    }
+
+    /// Registers a hook run with the old value whenever an entity loses its `T`, including
+    /// on despawn, e.g. to free a GPU handle. Adds the storage (sparse) if needed.
+    pub fn on_remove<T: Component>(&mut self, hook: impl Fn(Entity, &T) + Send + Sync + 'static) {
+        self.add::<T>();
+        self.get_mut::<T>()
+            .expect("storage was just added")
+            .on_remove(hook);
+    }
+
+    /// Gets the entity's `T`, if the world has that storage and the entity has one.
+    pub fn component<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.get::<T>()?.get(entity)
+    }
+
+    /// Mutable variant of `component`.
+    pub fn component_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        // Check first so a storage shared with another world isn't copied needlessly
+        if !self.has::<T>(entity) {
+            return None;
+        }
+        self.get_mut::<T>()?.get_mut(entity)
+    }
+
+    /// Returns true if the entity has a `T`.
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.get::<T>().is_some_and(|set| set.has(entity))
+    }
+
     /// Returns an iterator over the component storage, or empty if not present.
     pub fn iter<T: Component>(&self) -> impl Iterator<Item = (Entity, &T)> {
         self.get::<T>().into_iter().flat_map(|set| set.iter())
     }
 
+    /// Sets the entity's `T` marker. Returns false if it was already marked.
+    /// Marking a dead entity is a fault.
+    pub fn mark<T: Marker>(&mut self, entity: Entity) -> bool {
+        if self.is_dead(&entity) {
+            self.fault(Fault::DeadEntity { op: "mark", entity });
+            return false;
+        }
+        self.markers.get_mut::<T>().insert(entity.index())
+    }
+
+    /// Clears the entity's `T` marker. Returns false if it wasn't marked.
+    pub fn unmark<T: Marker>(&mut self, entity: Entity) -> bool {
+        !self.is_dead(&entity) && self.markers.get_mut::<T>().remove(entity.index())
+    }
+
+    pub fn is_marked<T: Marker>(&self, entity: Entity) -> bool {
+        !self.is_dead(&entity)
+            && self
+                .markers
+                .get::<T>()
+                .is_some_and(|bits| bits.contains(entity.index()))
+    }
+
+    /// Iterates the living entities marked with `T`, in slot order.
+    pub fn marked<T: Marker>(&self) -> impl Iterator<Item = Entity> + '_ {
+        let allocator = self.entities.read();
+        let marked: Vec<Entity> = self
+            .markers
+            .get::<T>()
+            .into_iter()
+            .flat_map(BitSet::iter)
+            .filter(|&index| allocator.alive.get(index) == Some(&true))
+            .map(|index| Entity::new(index, allocator.generations[index]))
+            .collect();
+        marked.into_iter()
+    }
+
+    pub fn marked_count<T: Marker>(&self) -> usize {
+        self.markers.get::<T>().map_or(0, BitSet::len)
+    }
+
     /// Returns an iterator over the component storage, or empty if not present.
     pub fn iter_mut<T: Component>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
         self.get_mut::<T>()
@@ -133,22 +1283,180 @@ impl World {
             .flat_map(|set| set.iter_mut())
     }
 
+    /// Fetches the components in `Q` for one entity, if it has all of them:
+    /// `world.fetch::<(&Position, &Velocity)>(e)`. Panics if `Q` writes.
+    pub fn fetch<Q: QueryData>(&self, entity: Entity) -> Option<Q::Item<'_>> {
+        let state = Q::init_state(&QueryWorld::read_only::<Q>(self));
+        // SAFETY: read-only, and a single item is fetched
+        unsafe { Q::fetch(state, entity) }
+    }
+
+    /// Like `fetch`, but `Q` may also write: `world.fetch_mut::<(&mut Position, &Velocity)>(e)`.
+    pub fn fetch_mut<Q: QueryData>(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        let state = Q::init_state(&QueryWorld::new::<Q>(self));
+        // SAFETY: the query world was made for `Q`, and a single item is fetched
+        unsafe { Q::fetch(state, entity) }
+    }
+
+    /// Queries every entity with all of `Q`'s components, e.g.
+    /// `world.query::<(&Position, &mut Velocity)>()`, joining the storages internally.
+    /// Storages `Q` writes are copied first if shared with another world.
+    pub fn query<Q: QueryData>(&mut self) -> Query<'_, Q> {
+        Query::new(QueryWorld::new::<Q>(self))
+    }
+
+    /// Like `query`, but only visits entities passing the filter `F`:
+    /// `world.query_filtered::<&Position, (With<Player>, Without<Dead>)>()`.
+    /// Filters only check presence, so their storages are never copied or borrowed mutably.
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&mut self) -> Query<'_, Q, F> {
+        Query::new(QueryWorld::new::<Q>(self))
+    }
+
+    /// Entities that gained a `T` since the last `clear_tracked`, with the components in `Q`:
+    /// `world.spawned::<Renderable, (&Sprite, &Position)>()`.
+    pub fn spawned<T: Component, Q: QueryData>(
+        &self,
+    ) -> impl Iterator<Item = (Entity, Q::Item<'_>)> {
+        Spawned::<T>::iter::<Q>(self)
+    }
+
+    /// Iterates every `(a, b)` pair across two component types, skipping an entity paired
+    /// with itself. Prune with `.filter`; for a spatial cull, iterate a grid instead.
+    pub fn iter_pairs<A: Component, B: Component>(
+        &self,
+    ) -> impl Iterator<Item = ((Entity, &A), (Entity, &B))> {
+        let b = self.get::<B>();
+        self.iter::<A>().flat_map(move |(ea, a)| {
+            b.into_iter()
+                .flat_map(|set| set.iter())
+                .filter(move |&(eb, _)| eb != ea)
+                .map(move |pair| ((ea, a), pair))
+        })
+    }
+
+    /// Visits every pair like `iter_pairs` with `A` mutable, calling `visit` only where
+    /// `keep` returns true. `A` and `B` must be different types.
+    pub fn for_each_pair_mut<A: Component, B: Component>(
+        &mut self,
+        mut keep: impl FnMut(&A, &B) -> bool,
+        mut visit: impl FnMut(Entity, &mut A, Entity, &B),
+    ) {
+        let (Some(a_set), Some(b_set)) = self.get_two_mut::<A, B>() else {
+            return;
+        };
+        let b_set: &Storage<B> = b_set;
+        for (ea, a) in a_set.iter_mut() {
+            for (eb, b) in b_set.iter() {
+                if ea != eb && keep(a, b) {
+                    visit(ea, a, eb, b);
+                }
+            }
+        }
+    }
+
+    /// Registers `T` as implementing the trait `Dyn`, for `iter_trait` and `iter_trait_mut`.
+    /// The casts are usually just `|c| c`:
+    /// `world.register_trait::<dyn Damageable, Armor>(|c| c, |c| c)`.
+    pub fn register_trait<Dyn: ?Sized + 'static, T: Component>(
+        &mut self,
+        cast: fn(&T) -> &Dyn,
+        cast_mut: fn(&mut T) -> &mut Dyn,
+    ) {
+        self.traits.register::<Dyn, T>(cast, cast_mut);
+    }
+
+    /// Iterates every component of every type registered as implementing `Dyn`.
+    pub fn iter_trait<Dyn: ?Sized + 'static>(&self) -> impl Iterator<Item = (Entity, &Dyn)> {
+        let casters = self.traits.casters::<Dyn>();
+        self.map
+            .iter()
+            .filter_map(move |(type_id, entry)| Some((casters?.get(type_id)?, entry)))
+            .flat_map(|(caster, entry)| caster.iter(entry.storage()))
+    }
+
+    /// Mutable variant of `iter_trait`.
+    pub fn iter_trait_mut<Dyn: ?Sized + 'static>(
+        &mut self,
+    ) -> impl Iterator<Item = (Entity, &mut Dyn)> {
+        let casters = self.traits.casters::<Dyn>();
+        let tick = self.change_tick;
+        self.map
+            .iter_mut()
+            .filter_map(move |(type_id, entry)| Some((casters?.get(type_id)?, entry)))
+            .flat_map(move |(caster, entry)| caster.iter_mut(entry.storage_mut(tick)))
+    }
+
     /// Retrieves storage for the component type from the world, if present.
     pub fn get<T: Component>(&self) -> Option<&Storage<T>> {
         let key = TypeId::of::<T>();
         let entry = self.map.get(&key)?;
-        entry.inner.downcast_ref::<Storage<T>>()
+        entry.storage().downcast_ref::<Storage<T>>()
     }
 
     /// Mutable variant of `get`.
     pub fn get_mut<T: Component>(&mut self) -> Option<&mut Storage<T>> {
         let entry = self.map.get_mut(&TypeId::of::<T>())?;
-        entry.inner.downcast_mut::<Storage<T>>()
+        entry
+            .storage_mut(self.change_tick)
+            .downcast_mut::<Storage<T>>()
+    }
+
+    /// Applies a JSON patch like `{ "entity": 42, "Position": { "x": 10.0 } }`.
+    /// Every other key names a component registered with `registry.register_json`; its
+    /// fields are merged over the entity's current value, or the whole value is inserted
+    /// if the entity doesn't have the component yet.
+    /// Every component is resolved and deserialized before anything is written, so a
+    /// patch that fails leaves the entity untouched.
+    #[cfg(feature = "json")]
+    pub fn apply(&mut self, patch: &serde_json::Value) -> Result<(), PatchError> {
+        let object = patch.as_object().ok_or(PatchError::NotAnObject)?;
+        let id = object
+            .get("entity")
+            .and_then(|v| v.as_u64())
+            .ok_or(PatchError::MissingEntity)? as usize;
+        let entity = Entity(id);
+        if self.is_dead(&entity) {
+            return Err(PatchError::DeadEntity(id));
+        }
+
+        let patches = object
+            .iter()
+            .filter(|(name, _)| name.as_str() != "entity")
+            .map(|(name, value)| self.registry.patch_fn(name)?(self, entity, value))
+            .collect::<Result<Vec<_>, PatchError>>()?;
+        for write in patches {
+            write(self);
+        }
+        Ok(())
     }
 }
 
+pub trait Component: Sync + Send + 'static + Sized + Copy + Clone {
+    /// Describes the component's fields for schema export.
+    /// `#[derive(Component)]` fills this in for structs.
+    fn fields() -> &'static [Field] {
+        &[]
+    }
+
+    /// ID that identifies the component type across builds.
+    /// Set with `#[component(uuid = "...")]` or `#[component(name = "...")]` on the derive.
+    fn stable_id() -> Option<StableId> {
+        None
+    }
 
-pub trait Component: Sync + Send + 'static + Sized + Copy + Clone {}
+    /// Rewrites any entity handles the component holds, e.g. a parent or target, when
+    /// entities are copied between worlds by `World::snapshot_entities` and
+    /// `World::spawn_template`. Implement it for components that refer to other entities.
+    fn map_entities(&mut self, _map: &mut dyn FnMut(Entity) -> Entity) {}
+
+    /// Checks the value's invariants, e.g. that a health is in range, returning why it's
+    /// invalid. Debug builds run it on every insert and `set`, so bad data panics at the
+    /// write. Set with `#[component(validate = "check_health")]` on the derive, naming a
+    /// `fn(&T) -> Result<(), E>` where `E: Display`.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
 
 macro_rules! impl_get_mut {
     ($name:ident, $( $ty:ident ),+) => {
@@ -156,6 +1464,7 @@ macro_rules! impl_get_mut {
             &mut self
         ) -> ( $( Option<&mut Storage<$ty>> ),+ ) {
             let keys = [ $( &TypeId::of::<$ty>() ),+ ];
+            let tick = self.change_tick;
             let slots = self.map.get_disjoint_mut(keys);
 
             // zip the slots with the types in order
@@ -163,7 +1472,7 @@ macro_rules! impl_get_mut {
             (
                 $(
                     it.next().unwrap()
-                        .and_then(|e| e.inner.downcast_mut::<Storage<$ty>>()),
+                        .and_then(|e| e.storage_mut(tick).downcast_mut::<Storage<$ty>>()),
                 )+
             )
         }
@@ -177,6 +1486,12 @@ impl World {
     impl_get_mut!(get_four_mut, A, B, C, D);
     impl_get_mut!(get_five_mut, A, B, C, D, E);
     impl_get_mut!(get_six_mut, A, B, C, D, E, F);
+    impl_get_mut!(get_seven_mut, A, B, C, D, E, F, G);
+    impl_get_mut!(get_eight_mut, A, B, C, D, E, F, G, H);
+    impl_get_mut!(get_nine_mut, A, B, C, D, E, F, G, H, I);
+    impl_get_mut!(get_ten_mut, A, B, C, D, E, F, G, H, I, J);
+    impl_get_mut!(get_eleven_mut, A, B, C, D, E, F, G, H, I, J, K);
+    impl_get_mut!(get_twelve_mut, A, B, C, D, E, F, G, H, I, J, K, L);
 }
 
 pub trait FetchMut<'a> {
@@ -191,71 +1506,30 @@ impl<'a, A: Component> FetchMut<'a> for (A,) {
     }
 }
 
-impl<'a, A: Component, B: Component> FetchMut<'a> for (A, B) {
-    type Output = (&'a mut Storage<A>, &'a mut Storage<B>);
-    fn fetch(world: &'a mut World) -> Option<Self::Output> {
-        let (a, b) = world.get_two_mut::<A, B>();
-        Some((a?, b?))
-    }
-}
-
-impl<'a, A: Component, B: Component, C: Component> FetchMut<'a> for (A, B, C) {
-    type Output = (
-        &'a mut Storage<A>,
-        &'a mut Storage<B>,
-        &'a mut Storage<C>,
-    );
-    fn fetch(world: &'a mut World) -> Option<Self::Output> {
-        let (a, b, c) = world.get_three_mut::<A, B, C>();
-        Some((a?, b?, c?))
-    }
-}
-
-impl<'a, A: Component, B: Component, C: Component, D: Component> FetchMut<'a> for (A, B, C, D) {
-    type Output = (
-        &'a mut Storage<A>,
-        &'a mut Storage<B>,
-        &'a mut Storage<C>,
-        &'a mut Storage<D>,
-    );
-    fn fetch(world: &'a mut World) -> Option<Self::Output> {
-        let (a, b, c, d) = world.get_four_mut::<A, B, C, D>();
-        Some((a?, b?, c?, d?))
-    }
-}
-
-impl<'a, A: Component, B: Component, C: Component, D: Component, E: Component> FetchMut<'a>
-    for (A, B, C, D, E)
-{
-    type Output = (
-        &'a mut Storage<A>,
-        &'a mut Storage<B>,
-        &'a mut Storage<C>,
-        &'a mut Storage<D>,
-        &'a mut Storage<E>,
-    );
-    fn fetch(world: &'a mut World) -> Option<Self::Output> {
-        let (a, b, c, d, e) = world.get_five_mut::<A, B, C, D, E>();
-        Some((a?, b?, c?, d?, e?))
-    }
+macro_rules! impl_fetch_mut {
+    ($get:ident, $( $ty:ident ),+) => {
+        impl<'a, $($ty: Component),+> FetchMut<'a> for ($($ty,)+) {
+            type Output = ($(&'a mut Storage<$ty>,)+);
+            #[allow(non_snake_case)]
+            fn fetch(world: &'a mut World) -> Option<Self::Output> {
+                let ($($ty,)+) = world.$get::<$($ty),+>();
+                Some(($($ty?,)+))
+            }
+        }
+    };
 }
 
-impl<'a, A: Component, B: Component, C: Component, D: Component, E: Component, F: Component>
-    FetchMut<'a> for (A, B, C, D, E, F)
-{
-    type Output = (
-        &'a mut Storage<A>,
-        &'a mut Storage<B>,
-        &'a mut Storage<C>,
-        &'a mut Storage<D>,
-        &'a mut Storage<E>,
-        &'a mut Storage<F>,
-    );
-    fn fetch(world: &'a mut World) -> Option<Self::Output> {
-        let (a, b, c, d, e, f) = world.get_six_mut::<A, B, C, D, E, F>();
-        Some((a?, b?, c?, d?, e?, f?))
-    }
-}
+impl_fetch_mut!(get_two_mut, A, B);
+impl_fetch_mut!(get_three_mut, A, B, C);
+impl_fetch_mut!(get_four_mut, A, B, C, D);
+impl_fetch_mut!(get_five_mut, A, B, C, D, E);
+impl_fetch_mut!(get_six_mut, A, B, C, D, E, F);
+impl_fetch_mut!(get_seven_mut, A, B, C, D, E, F, G);
+impl_fetch_mut!(get_eight_mut, A, B, C, D, E, F, G, H);
+impl_fetch_mut!(get_nine_mut, A, B, C, D, E, F, G, H, I);
+impl_fetch_mut!(get_ten_mut, A, B, C, D, E, F, G, H, I, J);
+impl_fetch_mut!(get_eleven_mut, A, B, C, D, E, F, G, H, I, J, K);
+impl_fetch_mut!(get_twelve_mut, A, B, C, D, E, F, G, H, I, J, K, L);
 
 #[cfg(test)]
 #[allow(dead_code)]
@@ -378,6 +1652,281 @@ mod test {
         assert!(fetched.is_some());
     }
 
+    #[test]
+    fn test_fetchmut_twelve() {
+        #[derive(Copy, Clone)]
+        struct N<const I: usize>;
+        impl<const I: usize> super::Component for N<I> {}
+
+        let mut world = super::World::new(4);
+        let e = world.spawn();
+        world.insert(e, N::<0>);
+        world.insert(e, N::<1>);
+        world.insert(e, N::<2>);
+        world.insert(e, N::<3>);
+        world.insert(e, N::<4>);
+        world.insert(e, N::<5>);
+        world.insert(e, N::<6>);
+        world.insert(e, N::<7>);
+        world.insert(e, N::<8>);
+        world.insert(e, N::<9>);
+        world.insert(e, N::<10>);
+        type Eleven = (N<0>, N<1>, N<2>, N<3>, N<4>, N<5>, N<6>, N<7>, N<8>, N<9>, N<10>);
+        type Twelve = (N<0>, N<1>, N<2>, N<3>, N<4>, N<5>, N<6>, N<7>, N<8>, N<9>, N<10>, N<11>);
+        assert!(<Eleven as super::FetchMut>::fetch(&mut world).is_some());
+        assert!(<Twelve as super::FetchMut>::fetch(&mut world).is_none());
+
+        world.insert(e, N::<11>);
+        let fetched = <Twelve as super::FetchMut>::fetch(&mut world).unwrap();
+        assert!(fetched.0.has(e) && fetched.11.has(e));
+    }
+
+    #[test]
+    fn transactions_commit_or_roll_back() {
+        let mut world = super::World::new(8);
+        let kept = world.spawn();
+        world.insert(kept, MyComponent { value: 1 });
+
+        let failed: Result<(), &str> = world.transaction(|tx| {
+            tx.despawn(kept);
+            let e = tx.spawn();
+            tx.insert(e, Other);
+            tx.tags.add_tag("loaded", e);
+            Err("missing asset")
+        });
+        assert_eq!(failed, Err("missing asset"));
+        assert!(world.is_alive(kept));
+        assert_eq!(world.entity_count(), 1);
+        assert!(world.get::<Other>().is_none());
+        assert_eq!(world.tags.count("loaded"), 0);
+
+        let spawned = world
+            .transaction(|tx| {
+                tx.insert(kept, MyComponent { value: 2 });
+                let e = tx.spawn();
+                tx.insert(e, Other);
+                Ok::<_, ()>(e)
+            })
+            .unwrap();
+        assert!(world.has::<Other>(spawned));
+        assert_eq!(world.component::<MyComponent>(kept).unwrap().value, 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "can't have all of")]
+    fn unique_sets_reject_a_second_holder() {
+        #[derive(Copy, Clone)]
+        struct Camera;
+        impl super::Component for Camera {}
+        #[derive(Copy, Clone)]
+        struct Active;
+        impl super::Component for Active {}
+
+        let mut world = super::World::new(4);
+        world.require_unique::<(Camera, Active)>();
+        let main = world.spawn_entity().with(Camera).with(Active).build();
+        let spare = world.spawn_entity().with(Camera).build();
+        let player = world.spawn_entity().with(Active).build();
+        // Re-inserting on the holder, or handing over, is fine
+        world.insert(main, Active);
+        world.remove::<Active>(main);
+        world.insert(spare, Active);
+        assert!(world.is_alive(player));
+        world.insert(main, Active);
+    }
+
+    #[test]
+    fn link_policies_apply_on_despawn() {
+        use super::{Entity, LinkPolicy};
+        use crate::fault::{Fault, FaultPolicy};
+
+        #[derive(Copy, Clone)]
+        struct ChildOf(Entity);
+        impl super::Component for ChildOf {
+            fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) {
+                self.0 = map(self.0);
+            }
+        }
+        #[derive(Copy, Clone)]
+        struct Target(Entity);
+        impl super::Component for Target {
+            fn map_entities(&mut self, map: &mut dyn FnMut(Entity) -> Entity) {
+                self.0 = map(self.0);
+            }
+        }
+
+        let mut world = super::World::new(8);
+        world.set_link_policy::<ChildOf>(LinkPolicy::Cascade);
+        world.set_link_policy::<Target>(LinkPolicy::Nullify);
+        let root = world.spawn();
+        let child = world.spawn();
+        let grandchild = world.spawn();
+        let hunter = world.spawn();
+        world.insert(child, ChildOf(root));
+        world.insert(grandchild, ChildOf(child));
+        world.insert(hunter, Target(grandchild));
+
+        assert!(world.despawn(root));
+        assert!(world.is_dead(&child) && world.is_dead(&grandchild));
+        assert!(world.is_alive(hunter));
+        assert!(!world.has::<Target>(hunter));
+
+        world.set_link_policy::<Target>(LinkPolicy::Restrict);
+        world.set_fault_policy(FaultPolicy::CollectErrors);
+        let prey = world.spawn();
+        world.insert(hunter, Target(prey));
+        assert!(!world.despawn(prey));
+        assert!(world.is_alive(prey));
+        assert_eq!(
+            world.take_faults(),
+            vec![Fault::Linked {
+                entity: prey,
+                holder: hunter
+            }]
+        );
+        world.remove::<Target>(hunter);
+        assert!(world.despawn(prey));
+    }
+
+    #[test]
+    fn clone_is_deep() {
+        let mut world = super::World::new(4);
+        world.add::<MyComponent>();
+        let e = world.spawn();
+        world
+            .get_mut::<MyComponent>()
+            .unwrap()
+            .add_entity(MyComponent { value: 1 }, e);
+        world.tags.add_tag("a", e);
+
+        let mut copy = world.clone();
+        copy.get_mut::<MyComponent>().unwrap().get_mut(e).unwrap().value = 2;
+        copy.tags.remove_tag("a", &e);
+        let spawned = copy.spawn();
+
+        assert_eq!(world.get::<MyComponent>().unwrap().get(e).unwrap().value, 1);
+        assert_eq!(copy.get::<MyComponent>().unwrap().get(e).unwrap().value, 2);
+        assert!(world.tags.has_tag("a", &e));
+        assert_eq!(world.spawn(), spawned);
+    }
+
+    #[test]
+    fn despawn_records_reasons() {
+        let mut world = super::World::new(4);
+        let a = world.spawn();
+        let b = world.spawn();
+        world.despawn_with_reason(a, "killed");
+        world.despawn(b);
+
+        let events = world.despawned();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].entity, events[0].reason.as_deref()), (a, Some("killed")));
+        assert_eq!((events[1].entity, events[1].reason.as_deref()), (b, None));
+
+        world.clear_tracked();
+        assert!(world.despawned().is_empty());
+    }
+
+    #[test]
+    fn lifecycle_hooks_fire() {
+        let log = std::sync::Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let mut world = super::World::new(8);
+        let inserted = log.clone();
+        world.on_insert::<MyComponent>(move |e, c| inserted.lock().push(("insert", e, c.value)));
+        let removed = log.clone();
+        world.on_remove::<MyComponent>(move |e, c| removed.lock().push(("remove", e, c.value)));
+
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, MyComponent { value: 1 });
+        world.insert(a, MyComponent { value: 2 });
+        world.insert(b, MyComponent { value: 3 });
+        world.remove::<MyComponent>(a);
+        world.despawn(b);
+
+        assert_eq!(
+            *log.lock(),
+            vec![
+                ("insert", a, 1),
+                ("insert", b, 3),
+                ("remove", a, 2),
+                ("remove", b, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn gc_trims_dead_slots_without_reviving_handles() {
+        let mut world = super::World::new(8);
+        let kept = world.spawn();
+        let middle = world.spawn();
+        let last = world.spawn();
+        world.despawn(last);
+        world.despawn(middle);
+        assert_eq!(world.gc(), 2);
+        assert_eq!(world.gc(), 0);
+        assert_eq!(world.entity_count(), 1);
+        assert_eq!(world.entities().collect::<Vec<_>>(), vec![kept]);
+
+        let reused = world.spawn();
+        assert_eq!(reused.index(), middle.index());
+        assert!(world.is_dead(&middle));
+        assert!(world.is_dead(&last));
+        assert!(world.is_alive(reused));
+        assert_eq!(world.entity_count(), 2);
+    }
+
+    #[test]
+    fn clear_entities_resets_for_the_next_level() {
+        let mut world = super::World::new(8);
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, MyComponent { value: 1 });
+        world.insert(b, Other);
+        world.tags.add_tag("enemy", b);
+        world.clear_tracked();
+
+        assert_eq!(world.clear_entities(), 2);
+        assert_eq!(world.entity_count(), 0);
+        assert!(world.is_dead(&a) && world.is_dead(&b));
+        assert_eq!(world.get::<MyComponent>().unwrap().len(), 0);
+        assert_eq!(world.get::<Other>().unwrap().removed, vec![b]);
+        assert_eq!(world.tags.removed(), vec![("enemy", b)]);
+        assert_eq!(world.despawned().len(), 2);
+
+        let next = world.spawn();
+        assert!(world.is_alive(next));
+        assert!(world.is_dead(&a));
+        world.insert(next, Other);
+        assert!(!world.has::<Other>(b));
+    }
+
+    #[test]
+    fn tag_changes_are_tracked() {
+        let mut world = super::World::new(4);
+        let a = world.spawn();
+        let b = world.spawn();
+        world.tags.add_tags(&["enemy", "burning"], a);
+        world.tags.add_tag("enemy", a);
+        world.tags.add_tag("enemy", b);
+        world.tags.remove_tag("burning", &a);
+        world.tags.remove_tag("burning", &b);
+        world.despawn(b);
+
+        assert_eq!(
+            world.tags.added(),
+            vec![("enemy", a), ("burning", a), ("enemy", b)]
+        );
+        assert_eq!(world.tags.added_with("enemy"), vec![a, b]);
+        assert_eq!(world.tags.removed(), vec![("burning", a), ("enemy", b)]);
+        assert_eq!(world.tags.removed_with("enemy"), vec![b]);
+
+        world.clear_tracked();
+        assert!(world.tags.added().is_empty());
+        assert!(world.tags.removed().is_empty());
+    }
+
     #[test]
     fn despawn_removes_components() {
         #[derive(Copy, Clone)]
@@ -405,5 +1954,237 @@ mod test {
 
         let store = world.get::<Position>().expect("missing storage");
         assert!(!store.has(e));
+
+        // The recycled slot starts out with no components
+        let recycled = world.spawn();
+        assert_eq!(recycled.index(), e.index());
+        let store = world.get::<Position>().expect("missing storage");
+        assert!(!store.has(recycled));
+        assert_eq!(store.len(), 0);
+    }
+
+    #[test]
+    fn debug_summarizes() {
+        let mut world = super::World::new(4);
+        world.add::<MyComponent>();
+        let e = world.spawn();
+        world
+            .get_mut::<MyComponent>()
+            .unwrap()
+            .add_entity(MyComponent { value: 1 }, e);
+        world.tags.add_tag("player", e);
+
+        let out = format!("{world:?}");
+        assert!(out.contains("entities: 1"));
+        assert!(out.contains("\"player\": 1"));
+    }
+
+    #[test]
+    fn ring_evictions_can_be_despawned() {
+        let mut world = super::World::new(4);
+        world.add_with_storage::<MyComponent>(super::ComponentStorageKind::Ring(2));
+        for value in 0..5 {
+            world.spawn_entity().with(MyComponent { value }).build();
+        }
+        assert_eq!(world.get::<MyComponent>().unwrap().len(), 2);
+        assert_eq!(world.entity_count(), 5);
+
+        assert_eq!(world.despawn_evicted(), 3);
+        assert_eq!(world.entity_count(), 2);
+        assert_eq!(world.despawn_evicted(), 0);
+        world.clear_tracked();
+        assert!(world.get::<MyComponent>().unwrap().evicted.is_empty());
+    }
+
+    #[test]
+    fn generations_wrap_within_their_bits() {
+        use crate::component::Entity;
+        let mut world = super::World::new(4);
+        let last = Entity::new(0, Entity::MAX_GENERATION);
+        world.restore_entity(last);
+        world.despawn(last);
+        assert_eq!(world.spawn(), Entity::new(0, 0));
+    }
+
+    #[test]
+    fn stale_handles_are_rejected() {
+        let mut world = super::World::new(4);
+        world.add::<MyComponent>();
+        let old = world.spawn();
+        world
+            .get_mut::<MyComponent>()
+            .unwrap()
+            .add_entity(MyComponent { value: 1 }, old);
+        world.despawn(old);
+
+        let new = world.spawn();
+        assert_eq!(new.index(), old.index());
+        assert_ne!(new, old);
+        world
+            .get_mut::<MyComponent>()
+            .unwrap()
+            .add_entity(MyComponent { value: 2 }, new);
+
+        assert!(world.is_dead(&old));
+        assert!(!world.is_dead(&new));
+        // Despawning twice isn't a fault, but a handle that was never given out is
+        world.set_fault_policy(super::FaultPolicy::Panic);
+        assert!(!world.despawn(old));
+        world.set_fault_policy(super::FaultPolicy::CollectErrors);
+        let unknown = crate::component::Entity::new(9, 0);
+        assert!(!world.despawn(unknown));
+        assert_eq!(
+            world.take_faults(),
+            vec![super::Fault::DeadEntity {
+                op: "despawn",
+                entity: unknown
+            }]
+        );
+        let store = world.get::<MyComponent>().unwrap();
+        assert!(store.get(old).is_none());
+        assert_eq!(store.get(new).unwrap().value, 2);
+        assert_eq!(world.entity_count(), 1);
+    }
+
+    #[test]
+    fn pairs_skip_self_and_prune() {
+        let mut world = super::World::new(4);
+        world.add::<MyComponent>();
+        world.add::<Other>();
+        let seeker = world.spawn();
+        let both = world.spawn();
+        let target = world.spawn();
+        world
+            .get_mut::<MyComponent>()
+            .unwrap()
+            .add_entity(MyComponent { value: 0 }, seeker);
+        world
+            .get_mut::<MyComponent>()
+            .unwrap()
+            .add_entity(MyComponent { value: 0 }, both);
+        world.get_mut::<Other>().unwrap().add_entity(Other, both);
+        world.get_mut::<Other>().unwrap().add_entity(Other, target);
+
+        assert_eq!(world.iter_pairs::<MyComponent, Other>().count(), 3);
+
+        world.for_each_pair_mut::<MyComponent, Other>(
+            |_, _| true,
+            |_, a, eb, _| {
+                if eb == target {
+                    a.value += 1;
+                }
+            },
+        );
+        let store = world.get::<MyComponent>().unwrap();
+        assert_eq!(store.get(seeker).unwrap().value, 1);
+        assert_eq!(store.get(both).unwrap().value, 1);
+    }
+
+    #[test]
+    fn alive_and_entities() {
+        let mut world = super::World::new(4);
+        let a = world.spawn();
+        let b = world.spawn();
+        let c = world.spawn();
+        world.despawn(b);
+
+        assert!(world.is_alive(a));
+        assert!(!world.is_alive(b));
+        assert!(!world.is_alive(super::Entity(99)));
+        assert_eq!(world.entities().collect::<Vec<_>>(), vec![a, c]);
+    }
+
+    #[test]
+    fn despawn_where_filters_with_world() {
+        let mut world = super::World::new(8);
+        world.add::<MyComponent>();
+        world.add::<Other>();
+        let mut spawn = |value: u32| {
+            let e = world.spawn();
+            world
+                .get_mut::<MyComponent>()
+                .unwrap()
+                .add_entity(MyComponent { value }, e);
+            e
+        };
+        let dead = spawn(0);
+        let revivable = spawn(0);
+        let healthy = spawn(5);
+        world
+            .get_mut::<Other>()
+            .unwrap()
+            .add_entity(Other, revivable);
+        world.get_mut::<Other>().unwrap().add_entity(Other, dead);
+        world.get_mut::<Other>().unwrap().remove_entity(dead);
+        world.tags.add_tag("enemy", dead);
+
+        let count = world.despawn_where::<MyComponent>(|world, e, health| {
+            health.value == 0 && !world.get::<Other>().unwrap().has(e)
+        });
+        assert_eq!(count, 1);
+        assert!(world.is_dead(&dead));
+        assert!(world.is_alive(revivable));
+        assert!(world.is_alive(healthy));
+        assert_eq!(world.get::<MyComponent>().unwrap().len(), 2);
+        assert!(!world.tags.has_tag("enemy", &dead));
+    }
+
+    #[test]
+    fn despawn_where_skips_rows_without_a_live_entity() {
+        let mut world = super::World::new(8);
+        world.set_fault_policy(super::FaultPolicy::CollectErrors);
+        let alive = world.spawn();
+        let unspawned = crate::component::Entity::new(5, 0);
+        world.add::<MyComponent>();
+        let storage = world.get_mut::<MyComponent>().unwrap();
+        storage.add_entity(MyComponent { value: 0 }, alive);
+        storage.add_entity(MyComponent { value: 0 }, unspawned);
+
+        assert_eq!(world.despawn_where::<MyComponent>(|_, _, _| true), 1);
+        assert!(world.is_dead(&alive));
+        assert_eq!(world.entity_count(), 0);
+        assert_eq!(
+            world.take_faults(),
+            vec![super::Fault::DeadEntity {
+                op: "despawn",
+                entity: unspawned
+            }]
+        );
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut world = super::World::new(4);
+        let e = world.spawn();
+        assert_eq!(world.remove::<MyComponent>(e).map(|c| c.value), None);
+
+        world.insert(e, MyComponent { value: 1 });
+        world.insert(e, MyComponent { value: 2 });
+        assert_eq!(world.get::<MyComponent>().unwrap().get(e).unwrap().value, 2);
+        assert_eq!(world.remove::<MyComponent>(e).map(|c| c.value), Some(2));
+        assert_eq!(world.remove::<MyComponent>(e).map(|c| c.value), None);
+    }
+
+    #[test]
+    fn single_component_access() {
+        let mut world = super::World::new(4);
+        let e = world.spawn();
+        assert!(world.component::<MyComponent>(e).is_none());
+        assert!(!world.has::<MyComponent>(e));
+
+        world.insert(e, MyComponent { value: 1 });
+        world.component_mut::<MyComponent>(e).unwrap().value += 1;
+        assert_eq!(world.component::<MyComponent>(e).unwrap().value, 2);
+        assert!(world.has::<MyComponent>(e));
+    }
+
+    #[test]
+    #[should_panic(expected = "insert: Entity(0) is not alive")]
+    fn fault_policy_panics() {
+        let mut world = super::World::new(4);
+        world.set_fault_policy(super::FaultPolicy::Panic);
+        let e = world.spawn();
+        world.despawn(e);
+        world.insert(e, MyComponent { value: 1 });
     }
 }